@@ -0,0 +1,88 @@
+//! Compares the tree-walking interpreter with the bytecode VM on the same
+//! programs. Run with `cargo bench`.
+
+use std::time::{Duration, Instant};
+
+use compiler::parser::ast::Program;
+use compiler::parser::parse_source;
+use compiler::runtime::codegen::compile;
+use compiler::runtime::interpreter::Interpreter;
+use compiler::runtime::vm::Vm;
+use compiler::toml_config::language_config::{LanguageKeywordManager, MooConfig};
+
+const PROGRAMS: &[(&str, &str)] = &[
+    (
+        "recursive fib(22)",
+        "\
+farmfunction 'num' fib(num n) {
+    if (n < 2) {
+        return n
+    }
+    return fib(n - 1) + fib(n - 2)
+}
+moo fib(22)",
+    ),
+    (
+        "counting loop",
+        "\
+num total = 0
+num i = 0
+while (i < 300000) {
+    if (i % 3 is 0) {
+        total = total + i
+    }
+    i++
+}
+moo total",
+    ),
+    (
+        "coop and barn_map",
+        "\
+coop 'num' counts = []
+for i in barn(20000) {
+    counts.add(i % 7)
+}
+barn_map 'num':'num' tally = {}
+for count in counts {
+    if (tally.contains(count)) {
+        tally[count] = tally[count] + 1
+    } else {
+        tally.put(count, 1)
+    }
+}
+moo tally",
+    ),
+];
+
+const RUNS: u32 = 5;
+
+/// Fastest of `RUNS` runs
+fn time(mut run: impl FnMut()) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            run();
+            start.elapsed()
+        })
+        .min()
+        .unwrap_or_default()
+}
+
+fn main() {
+    let manager = LanguageKeywordManager::from_config(MooConfig::from_default());
+    println!("{:<20} {:>14} {:>14} {:>8}", "program", "tree-walker", "vm", "speedup");
+    for (name, source) in PROGRAMS {
+        let program: Program = parse_source(source, &manager, "en").expect("benchmark programs parse");
+        let compiled = compile(&program).expect("benchmark programs compile");
+
+        let tree = time(|| Interpreter::new(&mut Vec::new()).run(&program).expect("runs"));
+        let vm = time(|| Vm::new(&mut Vec::new()).run(&compiled).expect("runs"));
+        println!(
+            "{:<20} {:>12.2?} {:>14.2?} {:>7.1}x",
+            name,
+            tree,
+            vm,
+            tree.as_secs_f64() / vm.as_secs_f64()
+        );
+    }
+}
@@ -1,6 +1,8 @@
-/*pub mod lexer;
+#[allow(clippy::module_inception)]
+pub mod lexer;
 pub mod errors;
+pub mod suggestions;
+pub mod terminators;
 
 pub use lexer::*;
-pub use errors::*;*/
-
+pub use errors::*;
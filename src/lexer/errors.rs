@@ -1,14 +1,29 @@
 use std::fmt;
+use crate::utils::tokens::Span;
 
 /// # LexerError
 /// This enum represents all the errors corresponding to the Lexer struct.
-/// ## Currently 2 variants
+/// ## Currently 9 variants
 /// - FileReadError(String)
 /// - TokenizationError(String)
+/// - UnknownLanguage(String)
+/// - UnexpectedCharacter { character, line, column }
+/// - UnterminatedString { line, column }
+/// - UnterminatedComment { line, column }
+/// - AmbiguousWord { word, keyword_language, name_language, line, column }
+/// - InvalidNumber { lexeme, span }
+/// - NumberOverflow { lexeme, span }
 #[derive(Debug)]
 pub enum LexerError {
     FileReadError(String),
     TokenizationError(String),
+    UnknownLanguage(String),
+    UnexpectedCharacter { character: char, line: usize, column: usize },
+    UnterminatedString { line: usize, column: usize },
+    UnterminatedComment { line: usize, column: usize },
+    AmbiguousWord { word: String, keyword_language: String, name_language: String, line: usize, column: usize },
+    InvalidNumber { lexeme: String, span: Span },
+    NumberOverflow { lexeme: String, span: Span },
 }
 
 impl fmt::Display for LexerError {
@@ -16,8 +31,31 @@ impl fmt::Display for LexerError {
         match self {
             LexerError::FileReadError(msg) => write!(f, "File read error: {}", msg),
             LexerError::TokenizationError(msg) => write!(f, "Tokenization error: {}", msg),
+            LexerError::UnknownLanguage(lang) => write!(f, "Unknown keyword language: {}", lang),
+            LexerError::UnexpectedCharacter { character, line, column } => {
+                write!(f, "Unexpected character '{}' at {}:{}", character, line, column)
+            }
+            LexerError::UnterminatedString { line, column } => {
+                write!(f, "Unterminated string starting at {}:{}", line, column)
+            }
+            LexerError::UnterminatedComment { line, column } => {
+                write!(f, "Unterminated comment starting at {}:{}", line, column)
+            }
+            LexerError::AmbiguousWord { word, keyword_language, name_language, line, column } => write!(
+                f,
+                "Ambiguous word '{}' at {}:{}: it is a {} keyword but is used as a {} name; rename it in mixed-language files",
+                word, line, column, keyword_language, name_language
+            ),
+            LexerError::InvalidNumber { lexeme, span } => {
+                write!(f, "Invalid number '{}' at {}", lexeme, span)
+            }
+            LexerError::NumberOverflow { lexeme, span } => write!(
+                f,
+                "Number '{}' at {} is too large; whole-number literals go up to {}",
+                lexeme, span, i64::MAX
+            ),
         }
     }
 }
 
-impl std::error::Error for LexerError {}
\ No newline at end of file
+impl std::error::Error for LexerError {}
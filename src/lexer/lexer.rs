@@ -0,0 +1,716 @@
+use crate::lexer::errors::LexerError;
+use crate::lexer::suggestions::language_name;
+use crate::toml_config::language_config::LanguageKeywordManager;
+use crate::utils::diagnostics::Diagnostic;
+use crate::utils::tokens::{Literal, Span, Token, TokenType};
+use crate::utils::unicode;
+
+/// Editors may save UTF-8 files with this mark at the start
+const BYTE_ORDER_MARK: char = '\u{FEFF}';
+
+/// Read a `# languages: en+es` pragma from the first line of a file.
+/// Returns the language spec to pass to `Lexer::new`.
+pub fn language_pragma(source: &str) -> Option<&str> {
+    let first_line = source.trim_start_matches(BYTE_ORDER_MARK).lines().next()?.trim();
+    let rest = first_line.strip_prefix('#')?.trim_start();
+    let spec = rest.strip_prefix("languages:")?.trim();
+    (!spec.is_empty()).then_some(spec)
+}
+
+/// `_` may only separate digits: no leading, trailing or doubled separators
+fn valid_digit_group(digits: &str) -> bool {
+    !digits.is_empty() && !digits.starts_with('_') && !digits.ends_with('_') && !digits.contains("__")
+}
+
+/// Checks every digit group of a base-10 literal like `1_000.5e-3`
+fn valid_decimal_lexeme(lexeme: &str) -> bool {
+    let (mantissa, exponent) = match lexeme.find(['e', 'E']) {
+        Some(index) => (&lexeme[..index], Some(lexeme[index + 1..].trim_start_matches(['+', '-']))),
+        None => (lexeme, None),
+    };
+    mantissa.split('.').all(valid_digit_group) && exponent.is_none_or(valid_digit_group)
+}
+
+fn is_type_keyword(token_type: &TokenType) -> bool {
+    matches!(
+        token_type.canonical(),
+        TokenType::Text | TokenType::Num | TokenType::Dec | TokenType::Boolean | TokenType::Coop | TokenType::BarnMap
+    )
+}
+
+/// # Lexer
+/// Turns MooLang source text into a flat list of `Token`s.
+///
+/// Keywords are resolved through a `LanguageKeywordManager`, so the same lexer
+/// handles `.moo`, `.muu` and any teacher-defined pack. A language spec may name
+/// several packs joined with `+` (e.g. `en+es`) to accept keywords from all of
+/// them; earlier packs win when a word is a keyword in more than one.
+/// Identifiers follow the Unicode XID rules, keywords are looked up after NFC
+/// normalisation, and columns are counted in grapheme clusters.
+///
+/// Comments and newlines are kept as `Comment` / `Newline` tokens; spaces and
+/// tabs are skipped. The token list always ends with `EOF`.
+pub struct Lexer<'a> {
+    source: &'a str,
+    keywords: &'a LanguageKeywordManager,
+    languages: Vec<String>,
+    position: usize,
+    line: usize,
+    line_start: usize,
+    tokens: Vec<Token>,
+    diagnostics: Vec<Diagnostic>,
+    first_keyword_language: Option<String>,
+    mixing_reported: bool,
+}
+
+impl<'a> Lexer<'a> {
+    /// Create a lexer for `source` using the keyword pack(s) named by
+    /// `language`, either a single code (`es`) or a mixed spec (`en+es`)
+    pub fn new(
+        source: &'a str,
+        keywords: &'a LanguageKeywordManager,
+        language: &str,
+    ) -> Result<Self, LexerError> {
+        let languages: Vec<String> = language.split('+')
+            .map(|lang| lang.trim().to_string())
+            .collect();
+        for lang in &languages {
+            if keywords.get_token_map(lang).is_none() {
+                return Err(LexerError::UnknownLanguage(lang.clone()));
+            }
+        }
+        Ok(Self {
+            source,
+            keywords,
+            languages,
+            position: 0,
+            line: 1,
+            line_start: 0,
+            tokens: Vec::new(),
+            diagnostics: Vec::new(),
+            first_keyword_language: None,
+            mixing_reported: false,
+        })
+    }
+
+    /// Lex the whole source, stopping at the first error
+    pub fn tokenize(self) -> Result<Vec<Token>, LexerError> {
+        self.tokenize_with_diagnostics().map(|(tokens, _)| tokens)
+    }
+
+    /// Lex the whole source, also returning lint warnings such as mixing
+    /// keywords from several languages in one file
+    pub fn tokenize_with_diagnostics(mut self) -> Result<(Vec<Token>, Vec<Diagnostic>), LexerError> {
+        if self.source.starts_with(BYTE_ORDER_MARK) {
+            self.position = BYTE_ORDER_MARK.len_utf8();
+            self.line_start = self.position;
+        }
+        while let Some(c) = self.peek() {
+            let start = self.position;
+            let (line, column) = self.location();
+            self.advance();
+
+            match c {
+                '\n' => {
+                    self.push(TokenType::Newline, start, line, column);
+                    self.new_line();
+                }
+                // Includes the no-break spaces word processors put in pasted code
+                c if c.is_whitespace() => {}
+                '#' => self.comment(start, line, column)?,
+                '"' => self.string(start, line, column)?,
+                '0'..='9' => self.number(start, line, column)?,
+                '+' => {
+                    let token_type = if self.matches('+') {
+                        TokenType::Increment
+                    } else if self.matches('=') {
+                        TokenType::PlusEquals
+                    } else {
+                        TokenType::Plus
+                    };
+                    self.push(token_type, start, line, column);
+                }
+                '-' => {
+                    let token_type = if self.matches('-') {
+                        TokenType::Decrement
+                    } else if self.matches('=') {
+                        TokenType::MinusEquals
+                    } else {
+                        TokenType::Minus
+                    };
+                    self.push(token_type, start, line, column);
+                }
+                '/' => {
+                    let token_type = match (self.matches('/'), self.matches('=')) {
+                        (true, true) => TokenType::IntegerDivideEquals,
+                        (true, false) => TokenType::IntegerDivide,
+                        (false, true) => TokenType::DivideEquals,
+                        (false, false) => TokenType::Divide,
+                    };
+                    self.push(token_type, start, line, column);
+                }
+                '*' => {
+                    let token_type = if self.matches('=') { TokenType::MultiplyEquals } else { TokenType::Multiply };
+                    self.push(token_type, start, line, column);
+                }
+                '%' => {
+                    let token_type = if self.matches('=') { TokenType::ModuloEquals } else { TokenType::Modulo };
+                    self.push(token_type, start, line, column);
+                }
+                '<' => self.push(TokenType::LessThan, start, line, column),
+                '>' => self.push(TokenType::GreaterThan, start, line, column),
+                '=' => self.push(TokenType::Equals, start, line, column),
+                '(' => self.push(TokenType::LeftParen, start, line, column),
+                ')' => self.push(TokenType::RightParen, start, line, column),
+                '{' => self.push(TokenType::LeftBrace, start, line, column),
+                '}' => self.push(TokenType::RightBrace, start, line, column),
+                '[' => self.push(TokenType::LeftBracket, start, line, column),
+                ']' => self.push(TokenType::RightBracket, start, line, column),
+                ',' => self.push(TokenType::Comma, start, line, column),
+                '.' => self.push(TokenType::Dot, start, line, column),
+                ':' => self.push(TokenType::Colon, start, line, column),
+                ';' => self.push(TokenType::Semicolon, start, line, column),
+                '\'' => self.push(TokenType::Quote, start, line, column),
+                c if unicode::is_identifier_start(c) => self.word(start, line, column)?,
+                character => {
+                    return Err(LexerError::UnexpectedCharacter { character, line, column });
+                }
+            }
+        }
+
+        let (line, column) = self.location();
+        self.tokens.push(Token::new(TokenType::EOF, "", line, column));
+        Ok((self.tokens, self.diagnostics))
+    }
+
+    // ================================
+    // Token scanners
+    // ================================
+
+    /// `# ...` to end of line, or `#* ... *#` spanning lines
+    fn comment(&mut self, start: usize, line: usize, column: usize) -> Result<(), LexerError> {
+        if self.matches('*') {
+            loop {
+                match self.advance() {
+                    Some('*') if self.matches('#') => break,
+                    Some('\n') => self.new_line(),
+                    Some(_) => {}
+                    None => return Err(LexerError::UnterminatedComment { line, column }),
+                }
+            }
+        } else {
+            while self.peek().is_some_and(|c| c != '\n') {
+                self.advance();
+            }
+        }
+        self.push(TokenType::Comment, start, line, column);
+        Ok(())
+    }
+
+    /// `"..."` with `\` escapes; strings may not span lines
+    fn string(&mut self, start: usize, line: usize, column: usize) -> Result<(), LexerError> {
+        loop {
+            match self.peek() {
+                Some('"') => {
+                    self.advance();
+                    break;
+                }
+                Some('\\') => {
+                    self.advance();
+                    if self.peek().is_some_and(|c| c != '\n') {
+                        self.advance();
+                    }
+                }
+                Some('\n') | None => return Err(LexerError::UnterminatedString { line, column }),
+                Some(_) => {
+                    self.advance();
+                }
+            }
+        }
+        self.push(TokenType::StringLiteral, start, line, column);
+        Ok(())
+    }
+
+    /// Integer and decimal literals. Supports `_` digit separators, `0x`/`0b`
+    /// prefixes and scientific notation; the parsed value is stored on the
+    /// token. Integers outside the `num` range (`i64`) are rejected here.
+    fn number(&mut self, start: usize, line: usize, column: usize) -> Result<(), LexerError> {
+        let radix = match (&self.source[start..self.position], self.peek()) {
+            ("0", Some('x' | 'X')) => 16,
+            ("0", Some('b' | 'B')) => 2,
+            _ => 10,
+        };
+
+        let literal = if radix != 10 {
+            self.advance();
+            let digits_start = self.position;
+            self.consume_digits(radix);
+            let digits = &self.source[digits_start..self.position];
+            self.check_number_end(start, line, column)?;
+            if !valid_digit_group(digits) {
+                return Err(self.invalid_number(start, line, column));
+            }
+            let value = i64::from_str_radix(&digits.replace('_', ""), radix)
+                .map_err(|_| self.number_overflow(start, line, column))?;
+            Literal::Integer(value)
+        } else {
+            self.consume_digits(10);
+            let mut is_decimal = false;
+            if self.peek() == Some('.') && self.peek_next().is_some_and(|c| c.is_ascii_digit()) {
+                is_decimal = true;
+                self.advance();
+                self.consume_digits(10);
+            }
+            if matches!(self.peek(), Some('e' | 'E')) {
+                is_decimal = true;
+                self.advance();
+                if matches!(self.peek(), Some('+' | '-')) {
+                    self.advance();
+                }
+                self.consume_digits(10);
+            }
+            self.check_number_end(start, line, column)?;
+
+            let lexeme = &self.source[start..self.position];
+            if !valid_decimal_lexeme(lexeme) {
+                return Err(self.invalid_number(start, line, column));
+            }
+            let cleaned = lexeme.replace('_', "");
+            if is_decimal {
+                let value: f64 = cleaned.parse()
+                    .map_err(|_| self.invalid_number(start, line, column))?;
+                if value.is_infinite() {
+                    return Err(self.number_overflow(start, line, column));
+                }
+                Literal::Decimal(value)
+            } else {
+                let value: i64 = cleaned.parse()
+                    .map_err(|_| self.number_overflow(start, line, column))?;
+                Literal::Integer(value)
+            }
+        };
+
+        let token_type = if matches!(literal, Literal::Decimal(_)) {
+            TokenType::DecimalLiteral
+        } else {
+            TokenType::IntegerLiteral
+        };
+        let lexeme = &self.source[start..self.position];
+        self.tokens.push(Token::new(token_type, lexeme, line, column).with_literal(literal));
+        Ok(())
+    }
+
+    fn consume_digits(&mut self, radix: u32) {
+        while self.peek().is_some_and(|c| c == '_' || c.is_digit(radix)) {
+            self.advance();
+        }
+    }
+
+    /// A number must not run straight into a name (`12cows`, `0b102`)
+    fn check_number_end(&mut self, start: usize, line: usize, column: usize) -> Result<(), LexerError> {
+        if self.peek().is_some_and(unicode::is_identifier_continue) {
+            self.consume_identifier();
+            return Err(self.invalid_number(start, line, column));
+        }
+        Ok(())
+    }
+
+    fn number_span(&self, start: usize, line: usize, column: usize) -> Span {
+        Span::new(line, column, unicode::grapheme_width(&self.source[start..self.position]))
+    }
+
+    fn invalid_number(&self, start: usize, line: usize, column: usize) -> LexerError {
+        LexerError::InvalidNumber {
+            lexeme: self.source[start..self.position].to_string(),
+            span: self.number_span(start, line, column),
+        }
+    }
+
+    fn number_overflow(&self, start: usize, line: usize, column: usize) -> LexerError {
+        LexerError::NumberOverflow {
+            lexeme: self.source[start..self.position].to_string(),
+            span: self.number_span(start, line, column),
+        }
+    }
+
+    /// Identifiers and keywords, including two-word keywords like `else if`
+    fn word(&mut self, start: usize, line: usize, column: usize) -> Result<(), LexerError> {
+        self.consume_identifier();
+        let first = &self.source[start..self.position];
+
+        if let Some((token_type, language)) = self.multi_word_keyword(first) {
+            let lexeme = &self.source[start..self.position];
+            self.note_keyword_language(lexeme, &language, line, column);
+            self.push(token_type, start, line, column);
+            return Ok(());
+        }
+
+        if let Some((keyword, language)) = self.lookup_keyword(first) {
+            self.check_ambiguous(first, &language, line, column)?;
+            self.note_keyword_language(first, &language, line, column);
+            self.push(keyword, start, line, column);
+            return Ok(());
+        }
+
+        let literal = self.languages.iter()
+            .find_map(|lang| self.keywords.get_boolean_literal(first, lang));
+        match literal {
+            Some(value) => {
+                let token = Token::new(TokenType::BooleanLiteral, first, line, column)
+                    .with_literal(Literal::Boolean(value));
+                self.tokens.push(token);
+            }
+            None => {
+                let builtin = self.languages.iter()
+                    .find_map(|lang| self.keywords.get_builtin(first, lang));
+                let mut token = Token::new(TokenType::Identifier, first, line, column);
+                token.literal = builtin.map(Literal::Builtin);
+                self.tokens.push(token);
+            }
+        }
+        Ok(())
+    }
+
+    /// Try to extend `first` with the next word on the same line into a
+    /// multi-word keyword. Rewinds if the pair is not a keyword.
+    fn multi_word_keyword(&mut self, first: &str) -> Option<(TokenType, String)> {
+        let checkpoint = self.position;
+        self.skip_horizontal_whitespace();
+        if !self.peek().is_some_and(unicode::is_identifier_start) {
+            self.position = checkpoint;
+            return None;
+        }
+
+        let second_start = self.position;
+        self.consume_identifier();
+        let candidate = format!("{} {}", first, &self.source[second_start..self.position]);
+        let keyword = self.lookup_keyword(&candidate);
+        if keyword.is_none() {
+            self.position = checkpoint;
+        }
+        keyword
+    }
+
+    /// Find `word` in the active keyword packs, in the order they were given
+    fn lookup_keyword(&self, word: &str) -> Option<(TokenType, String)> {
+        self.languages.iter().find_map(|lang| {
+            self.keywords.get_token_type(word, lang).map(|token| (token, lang.clone()))
+        })
+    }
+
+    // ================================
+    // Mixed-language checks
+    // ================================
+
+    /// Warn once when a file switches keyword language part way through
+    fn note_keyword_language(&mut self, word: &str, language: &str, line: usize, column: usize) {
+        if self.languages.len() < 2 || self.mixing_reported {
+            return;
+        }
+        match &self.first_keyword_language {
+            None => self.first_keyword_language = Some(language.to_string()),
+            Some(first) if first != language => {
+                // Shared words (same spelling in both packs) are not a switch
+                if self.keywords.get_token_type(word, first).is_some() {
+                    return;
+                }
+                self.diagnostics.push(
+                    Diagnostic::warning(
+                        format!(
+                            "mixing {} and {} keywords in one file",
+                            language_name(first), language_name(language)
+                        ),
+                        line,
+                        column,
+                    )
+                    .with_hint("pick one keyword language per file unless the class is bilingual"),
+                );
+                self.mixing_reported = true;
+            }
+            Some(_) => {}
+        }
+    }
+
+    /// In mixed mode a word like `es` is a Spanish keyword but a perfectly
+    /// good English name. Reject it where only a name can appear.
+    fn check_ambiguous(&mut self, word: &str, language: &str, line: usize, column: usize) -> Result<(), LexerError> {
+        if self.languages.len() < 2 {
+            return Ok(());
+        }
+        let Some(name_language) = self.languages.iter()
+            .find(|lang| self.keywords.get_token_type(word, lang).is_none())
+            .cloned()
+        else {
+            return Ok(());
+        };
+
+        if self.in_name_position() {
+            return Err(LexerError::AmbiguousWord {
+                word: word.to_string(),
+                keyword_language: language_name(language),
+                name_language: language_name(&name_language),
+                line,
+                column,
+            });
+        }
+        Ok(())
+    }
+
+    /// True when the word just lexed sits where the grammar only allows a name:
+    /// after a type (`num es`, `coop 'num' es`), after the function or `for`
+    /// keyword, after `.`, or as the target of `=`.
+    fn in_name_position(&mut self) -> bool {
+        let previous: Vec<&TokenType> = self.tokens.iter().rev()
+            .filter(|token| token.token_type != TokenType::Comment)
+            .take(2)
+            .map(|token| &token.token_type)
+            .collect();
+
+        let after_name_keyword = match previous.first() {
+            Some(token) if is_type_keyword(token) => {
+                // `'num'` inside a generic is a type, not a declaration
+                previous.get(1) != Some(&&TokenType::Quote)
+            }
+            Some(TokenType::Quote) => previous.get(1).is_some_and(|token| is_type_keyword(token)),
+            Some(token) => matches!(
+                token.canonical(),
+                TokenType::Farmfunction | TokenType::For | TokenType::Dot | TokenType::Stall
+            ),
+            None => false,
+        };
+        if after_name_keyword {
+            return true;
+        }
+
+        let checkpoint = self.position;
+        self.skip_horizontal_whitespace();
+        let assigned = self.peek() == Some('=');
+        self.position = checkpoint;
+        assigned
+    }
+
+    fn skip_horizontal_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ') | Some('\t')) {
+            self.advance();
+        }
+    }
+
+    fn consume_identifier(&mut self) {
+        while self.peek().is_some_and(unicode::is_identifier_continue) {
+            self.advance();
+        }
+    }
+
+    // ================================
+    // Cursor helpers
+    // ================================
+
+    fn peek(&self) -> Option<char> {
+        self.source[self.position..].chars().next()
+    }
+
+    fn peek_next(&self) -> Option<char> {
+        self.source[self.position..].chars().nth(1)
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += c.len_utf8();
+        Some(c)
+    }
+
+    fn matches(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn new_line(&mut self) {
+        self.line += 1;
+        self.line_start = self.position;
+    }
+
+    /// Current 1-based line and grapheme column
+    fn location(&self) -> (usize, usize) {
+        let column = unicode::grapheme_width(&self.source[self.line_start..self.position]) + 1;
+        (self.line, column)
+    }
+
+    fn push(&mut self, token_type: TokenType, start: usize, line: usize, column: usize) {
+        let lexeme = &self.source[start..self.position];
+        self.tokens.push(Token::new(token_type, lexeme, line, column));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::toml_config::language_config::MooConfig;
+
+    fn lex(source: &str, language: &str) -> Vec<Token> {
+        let manager = LanguageKeywordManager::from_config(MooConfig::from_default());
+        Lexer::new(source, &manager, language).unwrap().tokenize().unwrap()
+    }
+
+    fn types(tokens: &[Token]) -> Vec<TokenType> {
+        tokens.iter().map(|t| t.token_type.clone()).collect()
+    }
+
+    #[test]
+    fn test_basic_statement() {
+        let tokens = lex("num count = 10\nmoo \"hi\", count", "en");
+        assert_eq!(types(&tokens), vec![
+            TokenType::Num, TokenType::Identifier, TokenType::Equals, TokenType::IntegerLiteral,
+            TokenType::Newline, TokenType::Moo, TokenType::StringLiteral, TokenType::Comma,
+            TokenType::Identifier, TokenType::EOF,
+        ]);
+        assert_eq!(tokens[6].lexeme, "\"hi\"");
+        assert_eq!((tokens[6].line, tokens[6].column), (2, 5));
+    }
+
+    #[test]
+    fn test_compound_assignment_operators() {
+        let tokens = lex("a += 1; a -= 1; a *= 2; a /= 2; a //= 2; a %= 2; a++ - --a // b", "en");
+        let operators: Vec<TokenType> = types(&tokens).into_iter()
+            .filter(|t| !matches!(t, TokenType::Identifier | TokenType::IntegerLiteral | TokenType::Semicolon))
+            .collect();
+        assert_eq!(operators, vec![
+            TokenType::PlusEquals, TokenType::MinusEquals, TokenType::MultiplyEquals, TokenType::DivideEquals,
+            TokenType::IntegerDivideEquals, TokenType::ModuloEquals, TokenType::Increment, TokenType::Minus,
+            TokenType::Decrement, TokenType::IntegerDivide, TokenType::EOF,
+        ]);
+    }
+
+    #[test]
+    fn test_numeric_literals() {
+        let tokens = lex("1_000_000 0xFF 0b101 21.5 2.5e-3 1e3 animals.collect", "en");
+        let literals: Vec<Option<Literal>> = tokens.iter().take(6).map(|t| t.literal).collect();
+        assert_eq!(literals, vec![
+            Some(Literal::Integer(1_000_000)),
+            Some(Literal::Integer(255)),
+            Some(Literal::Integer(5)),
+            Some(Literal::Decimal(21.5)),
+            Some(Literal::Decimal(0.0025)),
+            Some(Literal::Decimal(1000.0)),
+        ]);
+        assert_eq!(tokens[0].token_type, TokenType::IntegerLiteral);
+        assert_eq!(tokens[3].token_type, TokenType::DecimalLiteral);
+        assert_eq!(tokens[7].token_type, TokenType::Dot);
+    }
+
+    #[test]
+    fn test_numeric_literal_errors() {
+        let manager = LanguageKeywordManager::from_config(MooConfig::from_default());
+        let lex_err = |source: &str| Lexer::new(source, &manager, "en").unwrap().tokenize().unwrap_err();
+
+        let error = lex_err("num big = 9_223_372_036_854_775_808");
+        match &error {
+            LexerError::NumberOverflow { lexeme, span } => {
+                assert_eq!(lexeme, "9_223_372_036_854_775_808");
+                assert_eq!(*span, Span::new(1, 11, 25));
+            }
+            other => panic!("unexpected error {:?}", other),
+        }
+        assert_eq!(
+            error.to_string(),
+            "Number '9_223_372_036_854_775_808' at 1:11 is too large; whole-number literals go up to 9223372036854775807"
+        );
+        assert!(matches!(lex_err("0x1_0000_0000_0000_0000"), LexerError::NumberOverflow { .. }));
+        for source in ["1__0", "1_", "0x", "1e", "12cows", "0b102"] {
+            assert!(matches!(lex_err(source), LexerError::InvalidNumber { .. }), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_multi_word_keywords() {
+        let tokens = lex("} sino si (x no es 3) {", "es");
+        assert_eq!(tokens[1].token_type, TokenType::SinoSi);
+        assert_eq!(tokens[1].lexeme, "sino si");
+        assert_eq!(tokens[4].token_type, TokenType::NoEs);
+    }
+
+    #[test]
+    fn test_unicode_identifiers_and_columns() {
+        let tokens = lex("numero tamañoCanasta = 6", "es");
+        assert_eq!(tokens[1].token_type, TokenType::Identifier);
+        assert_eq!(tokens[1].lexeme, "tamañoCanasta");
+        assert_eq!(tokens[2].column, 22);
+
+        // Combining marks count as one column
+        let tokens = lex("texto nin\u{303}o = \"a\"", "es");
+        assert_eq!(tokens[1].lexeme, "nin\u{303}o");
+        assert_eq!(tokens[2].column, 12);
+    }
+
+    #[test]
+    fn test_mixed_language_mode() {
+        let manager = LanguageKeywordManager::from_config(MooConfig::from_default());
+        let source = "num count = 1\nsi (count es 1) {\n    moo count\n}";
+        let (tokens, diagnostics) = Lexer::new(source, &manager, "en+es").unwrap()
+            .tokenize_with_diagnostics().unwrap();
+
+        assert_eq!(tokens[5].token_type, TokenType::If);
+        assert_eq!(tokens[8].token_type, TokenType::Es);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "mixing English and Spanish keywords in one file");
+        assert_eq!((diagnostics[0].line, diagnostics[0].column), (2, 1));
+    }
+
+    #[test]
+    fn test_mixed_language_ambiguity() {
+        let manager = LanguageKeywordManager::from_config(MooConfig::from_default());
+        for source in ["num es = 3", "es = 3", "coop 'num' es = [1]"] {
+            let result = Lexer::new(source, &manager, "en+es").unwrap().tokenize();
+            assert!(matches!(result, Err(LexerError::AmbiguousWord { .. })), "{}", source);
+        }
+        // Fine as a comparison, and fine in single-language mode
+        assert!(Lexer::new("x es 3", &manager, "en+es").unwrap().tokenize().is_ok());
+        assert!(Lexer::new("num es = 3", &manager, "en").unwrap().tokenize().is_ok());
+    }
+
+    #[test]
+    fn test_boolean_literals_follow_the_keyword_pack() {
+        let literals = |source: &str, language: &str| -> Vec<Literal> {
+            lex(source, language).into_iter().filter_map(|token| token.literal).collect()
+        };
+        assert_eq!(literals("true false", "en"), [Literal::Boolean(true), Literal::Boolean(false)]);
+        assert_eq!(literals("verdadero falso", "es"), [Literal::Boolean(true), Literal::Boolean(false)]);
+        // Another pack's literals are plain names
+        assert_eq!(types(&lex("verdadero", "en")), vec![TokenType::Identifier, TokenType::EOF]);
+        assert_eq!(types(&lex("true", "es")), vec![TokenType::Identifier, TokenType::EOF]);
+        assert_eq!(types(&lex("true verdadero", "en+es"))[..2], [TokenType::BooleanLiteral, TokenType::BooleanLiteral]);
+    }
+
+    #[test]
+    fn test_byte_order_mark_and_unicode_spaces() {
+        let tokens = lex("\u{FEFF}moo 1", "en");
+        assert_eq!(types(&tokens), vec![TokenType::Moo, TokenType::IntegerLiteral, TokenType::EOF]);
+        assert_eq!((tokens[0].line, tokens[0].column), (1, 1));
+
+        let tokens = lex("moo\u{00A0}1\u{3000}+ 2", "en");
+        assert_eq!(types(&tokens), vec![
+            TokenType::Moo, TokenType::IntegerLiteral, TokenType::Plus, TokenType::IntegerLiteral, TokenType::EOF,
+        ]);
+        assert_eq!(tokens[1].column, 5);
+    }
+
+    #[test]
+    fn test_language_pragma() {
+        assert_eq!(language_pragma("# languages: en+es\nmoo 1"), Some("en+es"));
+        assert_eq!(language_pragma("\u{FEFF}# languages: en+es\nmoo 1"), Some("en+es"));
+        assert_eq!(language_pragma("# farm inventory\nmoo 1"), None);
+    }
+
+    #[test]
+    fn test_comments_are_kept() {
+        let tokens = lex("#* multi\nline *#\nmoo 1 # trailing", "en");
+        assert_eq!(types(&tokens), vec![
+            TokenType::Comment, TokenType::Newline, TokenType::Moo, TokenType::IntegerLiteral,
+            TokenType::Comment, TokenType::EOF,
+        ]);
+        assert_eq!(tokens[2].line, 3);
+    }
+}
@@ -0,0 +1,283 @@
+use crate::toml_config::language_config::LanguageKeywordManager;
+use crate::utils::diagnostics::{Diagnostic, Severity};
+use crate::utils::tokens::{Token, TokenType};
+use crate::utils::unicode;
+
+/// A likely keyword the student meant to type
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeywordSuggestion {
+    /// The word is a small typo away from a keyword of the active language
+    NearMiss { keyword: String },
+    /// The word is a keyword of another configured language;
+    /// `keyword` is the equivalent in the active language
+    OtherLanguage { language: String, keyword: String },
+}
+
+impl KeywordSuggestion {
+    /// Student-facing hint text, e.g. "`if` is the English keyword; in Spanish use `si`"
+    pub fn hint(&self, word: &str, active_language: &str) -> String {
+        match self {
+            KeywordSuggestion::NearMiss { keyword } => format!("did you mean `{}`?", keyword),
+            KeywordSuggestion::OtherLanguage { language, keyword } => format!(
+                "`{}` is the {} keyword; in {} use `{}`",
+                word, language_name(language), language_name(active_language), keyword
+            ),
+        }
+    }
+}
+
+/// Human-readable name for a keyword pack code
+pub fn language_name(code: &str) -> String {
+    match code {
+        "en" => "English".to_string(),
+        "es" => "Spanish".to_string(),
+        "de" => "German".to_string(),
+        "ru" => "Russian".to_string(),
+        other => format!("'{}'", other),
+    }
+}
+
+/// Finds keywords a misspelled or wrong-language word was probably meant to be
+pub struct KeywordSuggester<'a> {
+    keywords: &'a LanguageKeywordManager,
+    language: String,
+}
+
+impl<'a> KeywordSuggester<'a> {
+    pub fn new(keywords: &'a LanguageKeywordManager, language: &str) -> Self {
+        Self {
+            keywords,
+            language: language.to_string(),
+        }
+    }
+
+    /// Suggest a keyword for `word`. Keywords of other languages take priority
+    /// over near-misses, since an exact match is the stronger signal.
+    pub fn suggest(&self, word: &str) -> Option<KeywordSuggestion> {
+        self.other_language(word).or_else(|| self.near_miss(word))
+    }
+
+    /// `word` is a keyword in another language: return that language and the
+    /// active language's keyword for the same construct
+    fn other_language(&self, word: &str) -> Option<KeywordSuggestion> {
+        let active = self.keywords.get_keyword_set(&self.language)?;
+        let default_language = self.keywords.default_language();
+        let mut languages: Vec<&String> = self.keywords.get_available_languages();
+        languages.retain(|lang| **lang != self.language);
+        // Prefer the configured default, then alphabetical for stable output
+        languages.sort_by_key(|lang| (**lang != default_language, lang.to_string()));
+
+        for lang in languages {
+            let matching = self.keywords.get_matching(lang);
+            let folded = unicode::fold_keyword(word, &matching);
+            let keyword_set = self.keywords.get_keyword_set(lang)?;
+            let field = keyword_set.entries().into_iter()
+                .find(|(_, keyword)| unicode::fold_keyword(keyword, &matching) == folded)
+                .map(|(field, _)| field);
+
+            if let Some(keyword) = field.and_then(|field| active.get(field)) {
+                return Some(KeywordSuggestion::OtherLanguage {
+                    language: lang.clone(),
+                    keyword: keyword.to_string(),
+                });
+            }
+        }
+        None
+    }
+
+    /// Closest single-word keyword of the active language within a
+    /// length-dependent edit distance
+    fn near_miss(&self, word: &str) -> Option<KeywordSuggestion> {
+        let word = unicode::nfc(word).to_lowercase();
+        let length = word.chars().count();
+        if length < 2 {
+            return None;
+        }
+        let max_distance = match length {
+            0..=4 => 1,
+            5..=8 => 2,
+            _ => 3,
+        };
+
+        let keyword_set = self.keywords.get_keyword_set(&self.language)?;
+        keyword_set.entries().into_iter()
+            .filter(|(_, keyword)| !keyword.contains(' '))
+            .map(|(_, keyword)| (edit_distance(&word, &unicode::nfc(keyword).to_lowercase()), keyword))
+            .filter(|(distance, _)| *distance > 0 && *distance <= max_distance)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, keyword)| KeywordSuggestion::NearMiss { keyword: keyword.to_string() })
+    }
+}
+
+/// Optimal string alignment distance: insertions, deletions, substitutions
+/// and transpositions of adjacent characters all cost 1
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut table = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in table.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in table[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            table[i][j] = (table[i - 1][j] + 1)
+                .min(table[i][j - 1] + 1)
+                .min(table[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                table[i][j] = table[i][j].min(table[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    table[a.len()][b.len()]
+}
+
+/// Scan a token stream for identifiers that sit where only a keyword makes
+/// sense and suggest the keyword the student probably meant.
+///
+/// An identifier is suspicious when it starts a statement and is directly
+/// followed by something that cannot follow an expression on the same line
+/// (`mo "hi"`, `farmfuntion feed(...)`). Keywords from other languages are
+/// also reported before `(`, which catches `if (x)` in a `.muu` file.
+pub fn keyword_hints(
+    tokens: &[Token],
+    keywords: &LanguageKeywordManager,
+    language: &str,
+) -> Vec<Diagnostic> {
+    let suggester = KeywordSuggester::new(keywords, language);
+    let mut hints = Vec::new();
+    let mut statement_start = true;
+
+    for (index, token) in tokens.iter().enumerate() {
+        match token.token_type {
+            TokenType::Comment => continue,
+            TokenType::Identifier if statement_start => {
+                if let Some(next) = tokens.get(index + 1) {
+                    if let Some(diagnostic) = suspicious_identifier(&suggester, token, next, language) {
+                        hints.push(diagnostic);
+                    }
+                }
+            }
+            _ => {}
+        }
+        statement_start = matches!(
+            token.token_type,
+            TokenType::Newline | TokenType::LeftBrace | TokenType::RightBrace | TokenType::Semicolon
+        );
+    }
+    hints
+}
+
+/// Give `diagnostic` the hint of the keyword hint on its line that comes
+/// before it, if any: `mo "hi"` fails at `"hi"` because `mo` is not `moo`.
+pub fn attach_keyword_hint(diagnostic: Diagnostic, hints: &[Diagnostic]) -> Diagnostic {
+    let found = hints
+        .iter()
+        .rfind(|hint| hint.line == diagnostic.line && hint.column <= diagnostic.column)
+        .and_then(|hint| hint.hint.clone());
+    match found {
+        Some(hint) if diagnostic.hint.is_none() => diagnostic.with_hint(hint),
+        _ => diagnostic,
+    }
+}
+
+fn suspicious_identifier(
+    suggester: &KeywordSuggester,
+    token: &Token,
+    next: &Token,
+    language: &str,
+) -> Option<Diagnostic> {
+    if next.line != token.line {
+        return None;
+    }
+    let cannot_follow_expression = matches!(
+        next.token_type,
+        TokenType::Identifier
+            | TokenType::StringLiteral
+            | TokenType::IntegerLiteral
+            | TokenType::DecimalLiteral
+            | TokenType::BooleanLiteral
+            | TokenType::Quote
+            | TokenType::LeftBrace
+    );
+
+    let suggestion = suggester.suggest(&token.lexeme)?;
+    let reported = match suggestion {
+        KeywordSuggestion::NearMiss { .. } => cannot_follow_expression,
+        KeywordSuggestion::OtherLanguage { .. } => {
+            cannot_follow_expression || next.token_type == TokenType::LeftParen
+        }
+    };
+    if !reported {
+        return None;
+    }
+
+    let message = match &suggestion {
+        KeywordSuggestion::NearMiss { .. } => format!("`{}` is not a keyword", token.lexeme),
+        KeywordSuggestion::OtherLanguage { .. } => {
+            format!("`{}` is not a keyword in {}", token.lexeme, language_name(language))
+        }
+    };
+    Some(
+        Diagnostic::new(Severity::Hint, message, token.line, token.column)
+            .with_hint(suggestion.hint(&token.lexeme, language)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::toml_config::language_config::MooConfig;
+
+    fn hints(source: &str, language: &str) -> Vec<Diagnostic> {
+        let manager = LanguageKeywordManager::from_config(MooConfig::from_default());
+        let tokens = Lexer::new(source, &manager, language).unwrap().tokenize().unwrap();
+        keyword_hints(&tokens, &manager, language)
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("farmfuntion", "farmfunction"), 1);
+        assert_eq!(edit_distance("mo", "moo"), 1);
+        assert_eq!(edit_distance("wihle", "while"), 1);
+        assert_eq!(edit_distance("cow", "cow"), 0);
+    }
+
+    #[test]
+    fn test_near_miss_hints() {
+        let found = hints("mo \"hi\"\nfarmfuntion feed(num n) {\n}", "en");
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].hint.as_deref(), Some("did you mean `moo`?"));
+        assert_eq!(found[1].hint.as_deref(), Some("did you mean `farmfunction`?"));
+        assert_eq!(found[1].line, 2);
+    }
+
+    #[test]
+    fn test_other_language_hint() {
+        let found = hints("if (x es 1) {\n}", "es");
+        assert_eq!(found.len(), 1);
+        assert_eq!(
+            found[0].hint.as_deref(),
+            Some("`if` is the English keyword; in Spanish use `si`")
+        );
+    }
+
+    #[test]
+    fn test_attach_keyword_hint() {
+        let found = hints("mo \"hi\"\nmoo 1", "en");
+        let error = attach_keyword_hint(Diagnostic::error("unexpected `\"hi\"`", 1, 4), &found);
+        assert_eq!(error.hint.as_deref(), Some("did you mean `moo`?"));
+        let elsewhere = attach_keyword_hint(Diagnostic::error("unexpected `1`", 2, 5), &found);
+        assert_eq!(elsewhere.hint, None);
+    }
+
+    #[test]
+    fn test_valid_code_has_no_hints() {
+        assert!(hints("moo count\ncount = count + 1\nfeed(count)", "en").is_empty());
+    }
+}
@@ -0,0 +1,286 @@
+//! Automatic statement termination.
+//!
+//! MooLang statements end at a line break, so semicolons are optional. This
+//! pass runs between the lexer and the parser and decides which `Newline`
+//! tokens actually end a statement:
+//!
+//! 1. Inside `( )`, `[ ]` and map-literal `{ }` newlines never end a statement.
+//!    Block braces (`if (...) {`, `farmfunction f() {`) do not suppress them.
+//!    A `{` opens a map literal when it appears where an expression starts
+//!    (after `=`, `(`, `[`, `,`, `:`, an operator, `moo` or `return`).
+//! 2. A newline right after a binary operator, `=`, `,`, `.` or `:` continues
+//!    the statement on the next line.
+//! 3. A `}` followed by line breaks and then `else` / `else if` is joined onto
+//!    one line, with a warning, since `else` cannot start a statement.
+//! 4. Any other newline ends the statement. Runs of terminators collapse to one.
+//!
+//! An explicit `;` always ends a statement. Lines that begin with a binary
+//! operator, `(` or `[` straight after a finished statement are reported,
+//! because the student usually meant them to continue the previous line.
+//!
+//! The output keeps `Newline` and `Semicolon` as terminators and drops
+//! comments, which the parser never needs.
+
+use crate::utils::diagnostics::Diagnostic;
+use crate::utils::tokens::{Token, TokenType};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Bracket {
+    Paren,
+    Square,
+    MapBrace,
+    BlockBrace,
+}
+
+/// Filter `tokens` down to the newlines that end statements, following the
+/// rules in the module documentation. Returns the filtered tokens and any
+/// warnings about ambiguous line breaks.
+pub fn terminate_statements(tokens: &[Token]) -> (Vec<Token>, Vec<Diagnostic>) {
+    let mut output: Vec<Token> = Vec::with_capacity(tokens.len());
+    let mut diagnostics = Vec::new();
+    let mut brackets: Vec<Bracket> = Vec::new();
+
+    let significant: Vec<&Token> = tokens.iter()
+        .filter(|token| token.token_type != TokenType::Comment)
+        .collect();
+
+    let mut index = 0;
+    while index < significant.len() {
+        let token = significant[index];
+        let previous = output.last().map(|t: &Token| t.token_type.canonical());
+
+        match token.token_type.canonical() {
+            TokenType::Newline => {
+                // Look past any blank lines to the next real token
+                let mut next_index = index;
+                while significant.get(next_index).is_some_and(|next| next.token_type == TokenType::Newline) {
+                    next_index += 1;
+                }
+                let Some(&next) = significant.get(next_index) else {
+                    // Tokens without a trailing `EOF` end at their last line break
+                    if previous.as_ref().is_some_and(|prev| !is_terminator(prev)) {
+                        output.push(token.clone());
+                    }
+                    break;
+                };
+
+                let inside_expression = matches!(
+                    brackets.last(),
+                    Some(Bracket::Paren | Bracket::Square | Bracket::MapBrace)
+                );
+                let continues = previous.as_ref().is_none_or(|prev| {
+                    is_terminator(prev) || continues_statement(prev)
+                });
+
+                if inside_expression || continues {
+                    index = next_index;
+                    continue;
+                }
+
+                if previous == Some(TokenType::RightBrace)
+                    && matches!(next.token_type.canonical(), TokenType::Else | TokenType::ElseIf)
+                {
+                    diagnostics.push(
+                        Diagnostic::warning(
+                            format!("line break before `{}`", next.lexeme),
+                            next.line,
+                            next.column,
+                        )
+                        .with_hint(format!("write `}} {}` on the same line as the closing brace", next.lexeme)),
+                    );
+                    index = next_index;
+                    continue;
+                }
+
+                if let Some(diagnostic) = dangling_line_start(next) {
+                    diagnostics.push(diagnostic);
+                }
+                output.push(token.clone());
+                index = next_index;
+                continue;
+            }
+            TokenType::Semicolon if previous.as_ref().is_some_and(is_terminator) => {
+                output.pop();
+            }
+            TokenType::LeftParen => brackets.push(Bracket::Paren),
+            TokenType::LeftBracket => brackets.push(Bracket::Square),
+            TokenType::LeftBrace => {
+                let opens_literal = previous.as_ref().is_some_and(starts_expression);
+                brackets.push(if opens_literal { Bracket::MapBrace } else { Bracket::BlockBrace });
+            }
+            TokenType::RightParen | TokenType::RightBracket => {
+                brackets.pop();
+            }
+            TokenType::RightBrace => {
+                // A block's last statement may omit its terminator
+                let closes_block = brackets.pop() == Some(Bracket::BlockBrace);
+                let unterminated = previous.as_ref()
+                    .is_some_and(|prev| !is_terminator(prev) && *prev != TokenType::LeftBrace);
+                if closes_block && unterminated {
+                    output.push(Token::new(TokenType::Newline, "", token.line, token.column));
+                }
+            }
+            _ => {}
+        }
+        output.push(token.clone());
+        index += 1;
+    }
+
+    (output, diagnostics)
+}
+
+fn is_terminator(token_type: &TokenType) -> bool {
+    matches!(token_type, TokenType::Newline | TokenType::Semicolon)
+}
+
+fn is_binary_operator(token_type: &TokenType) -> bool {
+    matches!(
+        token_type,
+        TokenType::Plus
+            | TokenType::Minus
+            | TokenType::Multiply
+            | TokenType::Divide
+            | TokenType::IntegerDivide
+            | TokenType::Modulo
+            | TokenType::LessThan
+            | TokenType::GreaterThan
+            | TokenType::Is
+            | TokenType::IsNot
+    )
+}
+
+fn is_compound_assignment(token_type: &TokenType) -> bool {
+    matches!(
+        token_type,
+        TokenType::PlusEquals
+            | TokenType::MinusEquals
+            | TokenType::MultiplyEquals
+            | TokenType::DivideEquals
+            | TokenType::IntegerDivideEquals
+            | TokenType::ModuloEquals
+    )
+}
+
+/// Tokens after which a line break cannot end the statement
+fn continues_statement(token_type: &TokenType) -> bool {
+    is_binary_operator(token_type)
+        || is_compound_assignment(token_type)
+        || matches!(
+            token_type,
+            TokenType::Equals
+                | TokenType::Comma
+                | TokenType::Dot
+                | TokenType::Colon
+                | TokenType::LeftParen
+                | TokenType::LeftBracket
+                | TokenType::LeftBrace
+        )
+}
+
+/// Tokens after which a `{` begins a map literal rather than a block
+pub(crate) fn starts_expression(token_type: &TokenType) -> bool {
+    is_binary_operator(token_type)
+        || is_compound_assignment(token_type)
+        || matches!(
+            token_type,
+            TokenType::Equals
+                | TokenType::LeftParen
+                | TokenType::LeftBracket
+                | TokenType::Comma
+                | TokenType::Colon
+                | TokenType::Moo
+                | TokenType::Return
+        )
+}
+
+/// Warn about a line that starts with something that only makes sense as a
+/// continuation of the previous line
+fn dangling_line_start(token: &Token) -> Option<Diagnostic> {
+    let token_type = token.token_type.canonical();
+    if is_binary_operator(&token_type) && token_type != TokenType::Minus {
+        return Some(
+            Diagnostic::warning(
+                format!("line starts with `{}` but the previous line already ended the statement", token.lexeme),
+                token.line,
+                token.column,
+            )
+            .with_hint(format!("move `{}` to the end of the previous line", token.lexeme)),
+        );
+    }
+    if matches!(token_type, TokenType::LeftParen | TokenType::LeftBracket) {
+        return Some(
+            Diagnostic::warning(
+                format!("line starts with `{}` and is read as a new statement", token.lexeme),
+                token.line,
+                token.column,
+            )
+            .with_hint("if this continues a call or index on the previous line, join the lines"),
+        );
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::toml_config::language_config::{LanguageKeywordManager, MooConfig};
+
+    fn terminate(source: &str) -> (Vec<TokenType>, Vec<Diagnostic>) {
+        let manager = LanguageKeywordManager::from_config(MooConfig::from_default());
+        let tokens = Lexer::new(source, &manager, "en").unwrap().tokenize().unwrap();
+        let (tokens, diagnostics) = terminate_statements(&tokens);
+        (tokens.into_iter().map(|t| t.token_type).collect(), diagnostics)
+    }
+
+    fn count_terminators(types: &[TokenType]) -> usize {
+        types.iter().filter(|t| is_terminator(t)).count()
+    }
+
+    #[test]
+    fn test_newlines_end_statements() {
+        let (types, diagnostics) = terminate("num a = 1\n\n\nnum b = 2; moo a\n");
+        assert_eq!(count_terminators(&types), 3);
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_tokens_without_eof() {
+        let manager = LanguageKeywordManager::from_config(MooConfig::from_default());
+        let mut tokens = Lexer::new("moo 1\n\n", &manager, "en").unwrap().tokenize().unwrap();
+        assert_eq!(tokens.pop().map(|t| t.token_type), Some(TokenType::EOF));
+        let (tokens, _) = terminate_statements(&tokens);
+        let types: Vec<TokenType> = tokens.into_iter().map(|t| t.token_type).collect();
+        assert_eq!(types, vec![TokenType::Moo, TokenType::IntegerLiteral, TokenType::Newline]);
+    }
+
+    #[test]
+    fn test_continuation_lines() {
+        let source = "num total = a +\n    b\nmoo \"a\",\n    total\nfeed(\n  1,\n  2\n)\n";
+        let (types, _) = terminate(source);
+        assert_eq!(count_terminators(&types), 3);
+    }
+
+    #[test]
+    fn test_map_literal_vs_block() {
+        let source = "barn_map 'text':'num' m = {\n  \"cow\": 5,\n  \"pig\": 3\n}\nif (x) {\n  moo 1\n  moo 2\n}\n";
+        let (types, _) = terminate(source);
+        // map declaration, two block statements, the if statement
+        assert_eq!(count_terminators(&types), 4);
+    }
+
+    #[test]
+    fn test_else_on_next_line() {
+        let (types, diagnostics) = terminate("if (x) {\n  moo 1\n}\n\nelse {\n  moo 2\n}\n");
+        assert!(!types.windows(2).any(|w| w == [TokenType::Newline, TokenType::Else]));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line, 5);
+    }
+
+    #[test]
+    fn test_dangling_operator_warning() {
+        let (_, diagnostics) = terminate("num total = a\n  + b\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "line starts with `+` but the previous line already ended the statement");
+    }
+}
@@ -3,9 +3,14 @@ pub mod utils{
     pub mod file_reader;
     pub mod file_reader_errors;
     pub mod tokens;
+    pub mod diagnostics;
+    pub mod unicode;
 }
 pub mod toml_config{
     pub mod language_config;
     pub mod config_errors;
 }
-//pub mod parser;
\ No newline at end of file
+pub mod parser;
+pub mod semantic;
+pub mod runtime;
+pub mod modules;
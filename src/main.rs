@@ -1,10 +1,302 @@
+use compiler::lexer::suggestions::{attach_keyword_hint, keyword_hints};
+use compiler::lexer::terminators::terminate_statements;
+use compiler::lexer::Lexer;
+use compiler::modules::{file_language, load_modules};
+use compiler::parser::precedence::precedence_table_text;
+use compiler::parser::printer::ast_tree;
+use compiler::parser::Parser;
+use compiler::runtime::bytecode::{disassemble, CompiledProgram};
+use compiler::runtime::codegen::compile;
+use compiler::runtime::errors::RuntimeError;
+use compiler::runtime::interpreter::Interpreter;
+use compiler::runtime::object_file::{read_object, write_object, EXTENSION};
+use compiler::runtime::vm::Vm;
+use compiler::semantic::{analyze_flow, check_types, resolve_names};
+use compiler::utils::diagnostics::Severity;
+use compiler::toml_config::language_config::{LanguageKeywordManager, MooConfig};
+use compiler::utils::file_reader::FileType;
+use compiler::utils::tokens::token_table;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 
-use compiler::toml_config::language_config;
-use std::path::Path;
+/// Stack for the compiler thread. The tree-walking interpreter follows
+/// `farmfunction` calls on the Rust stack, up to `MAX_CALL_DEPTH` of them;
+/// a debug build needs about 64 MB for that many calls of a one-line body.
+/// The rest leaves room for longer bodies and for the parser and checkers,
+/// which recurse once per level of nesting in the source.
+const STACK_SIZE: usize = 256 * 1024 * 1024;
 
-fn main() {
-    let path = Path::new("/home/batman/Documents/proyects/compiler/src/moo_lang.toml");
-    let config = language_config::MooConfig::from_toml(&path);
-    println!("{:?}", config);
+const USAGE: &str = "\
+usage: moolang [options] <file.moo | file.muu | file.moob>
+       moolang disasm [options] <file.moo | file.muu | file.moob>
+       moolang build [options] <file.moo | file.muu> [-o <file.moob>]
 
+Checks the program and runs it. `disasm` prints the compiled bytecode
+instead, `build` saves it to a `.moob` file that runs without its source,
+and the dump options print a stage's output.
+
+options:
+  --tree-walker    run with the tree-walking interpreter instead of the VM
+  --seed <n>       seed `random`, so every run prints the same numbers
+  -o <path>        where `build` writes (default: the source with `.moob`)
+  --dump-tokens    print the lexer's tokens
+  --dump-ast       print the syntax tree
+  --json           print the dumps as JSON (the syntax tree if no dump is
+                   chosen) instead of running the program
+  --lang <code>    keyword pack to use, e.g. `es` or `en+es`
+                   (default: `# languages:` pragma, then file extension)
+  --config <path>  keyword configuration file
+  -h, --help       show this help and the operator precedence table";
+
+#[derive(Default, PartialEq)]
+enum Command {
+    #[default]
+    Run,
+    Disasm,
+    Build,
+}
+
+#[derive(Default)]
+struct Options {
+    command: Command,
+    file: Option<PathBuf>,
+    output: Option<PathBuf>,
+    tree_walker: bool,
+    seed: Option<u64>,
+    dump_tokens: bool,
+    dump_ast: bool,
+    json: bool,
+    language: Option<String>,
+    config: Option<PathBuf>,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options::default();
+        let mut first = true;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "disasm" if first => options.command = Command::Disasm,
+                "build" if first => options.command = Command::Build,
+                "-o" | "--output" => options.output = Some(args.next().ok_or("-o needs a path")?.into()),
+                "--tree-walker" => options.tree_walker = true,
+                "--seed" => {
+                    let seed = args.next().ok_or("--seed needs a number")?;
+                    let seed = seed.parse().map_err(|_| format!("--seed needs a whole number from 0, found `{}`", seed))?;
+                    options.seed = Some(seed);
+                }
+                "--dump-tokens" => options.dump_tokens = true,
+                "--dump-ast" => options.dump_ast = true,
+                "--json" => options.json = true,
+                "--lang" => options.language = Some(args.next().ok_or("--lang needs a language code")?),
+                "--config" => options.config = Some(args.next().ok_or("--config needs a path")?.into()),
+                "-h" | "--help" => {
+                    return Err(format!("{}\n\noperators, highest precedence first:\n{}", USAGE, precedence_table_text()))
+                }
+                flag if flag.starts_with('-') => return Err(format!("unknown option `{}`\n\n{}", flag, USAGE)),
+                _ if options.file.is_some() => return Err(format!("only one source file is allowed\n\n{}", USAGE)),
+                _ => options.file = Some(arg.into()),
+            }
+            first = false;
+        }
+        if options.file.is_none() {
+            return Err(USAGE.to_string());
+        }
+        // `--json` alone dumps the syntax tree
+        if options.json && !options.dump_tokens {
+            options.dump_ast = true;
+        }
+        if options.output.is_some() && options.command != Command::Build {
+            return Err(format!("-o is only used with `build`\n\n{}", USAGE));
+        }
+        Ok(options)
+    }
+}
+
+fn main() -> ExitCode {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::FAILURE;
+        }
+    };
+    let result = std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || run(&options))
+        .map_err(|error| error.to_string())
+        .and_then(|thread| thread.join().unwrap_or_else(|_| Err("the compiler crashed".to_string())));
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("{}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    let path = options.file.as_deref().expect("checked by Options::parse");
+    let manager = match &options.config {
+        Some(config) => LanguageKeywordManager::from_file(config).map_err(|error| error.to_string())?,
+        None => LanguageKeywordManager::from_config(MooConfig::from_default()),
+    };
+    if path.extension().is_some_and(|extension| extension == EXTENSION) {
+        return run_object(options, path, &manager);
+    }
+    let source = FileType::read_file(path).map_err(|error| error.to_string())?;
+    let language = detect_language(options, path, &source);
+
+    let (tokens, warnings) = Lexer::new(&source, &manager, &language)
+        .and_then(|lexer| lexer.tokenize_with_diagnostics())
+        .map_err(|error| error.to_string())?;
+    for warning in &warnings {
+        eprintln!("{}", warning);
+    }
+
+    let (terminated, warnings) = terminate_statements(&tokens);
+    for warning in &warnings {
+        eprintln!("{}", warning);
+    }
+    let (program, errors) = Parser::new(terminated).parse_with_recovery();
+
+    if options.json {
+        let json = match (options.dump_tokens, options.dump_ast) {
+            (true, true) => serde_json::json!({ "tokens": tokens, "ast": program }),
+            (true, false) => serde_json::json!(tokens),
+            _ => serde_json::json!(program),
+        };
+        print_out(&format!("{}\n", serde_json::to_string_pretty(&json).map_err(|error| error.to_string())?))?;
+    } else {
+        if options.dump_tokens {
+            print_out(&token_table(&tokens))?;
+        }
+        if options.dump_ast {
+            print_out(&ast_tree(&program))?;
+        }
+    }
+
+    let hints = keyword_hints(&tokens, &manager, &language);
+    for error in &errors {
+        eprintln!("error: {}", error);
+        if let Some(hint) = attach_keyword_hint(error.to_diagnostic(), &hints).hint {
+            eprintln!("  hint: {}", hint);
+        }
+    }
+    if !errors.is_empty() {
+        return Err(format!("{}: {} syntax error(s)", path.display(), errors.len()));
+    }
+    let program = load_modules(program, path, &manager).map_err(|error| error.to_string())?;
+
+    let names = resolve_names(&program);
+    for diagnostic in &names {
+        eprintln!("{}", diagnostic);
+    }
+    let name_errors = names.iter().filter(|d| d.severity == Severity::Error).count();
+    if name_errors > 0 {
+        return Err(format!("{}: {} name error(s)", path.display(), name_errors));
+    }
+
+    let type_errors = check_types(&program);
+    for error in &type_errors {
+        eprintln!("{}", error);
+    }
+    if !type_errors.is_empty() {
+        return Err(format!("{}: {} type error(s)", path.display(), type_errors.len()));
+    }
+
+    let flow = analyze_flow(&program);
+    for diagnostic in &flow {
+        eprintln!("{}", diagnostic);
+    }
+    let flow_errors = flow.iter().filter(|d| d.severity == Severity::Error).count();
+    if flow_errors > 0 {
+        return Err(format!("{}: {} control-flow error(s)", path.display(), flow_errors));
+    }
+
+    if options.dump_tokens || options.dump_ast {
+        return Ok(());
+    }
+    if options.command == Command::Build {
+        let output = options.output.clone().unwrap_or_else(|| path.with_extension(EXTENSION));
+        let compiled = compile(&program).map_err(|error| format!("{}: {}", path.display(), error))?;
+        std::fs::write(&output, write_object(&compiled, manager.version(), &language))
+            .map_err(|error| format!("{}: {}", output.display(), error))?;
+        return Ok(());
+    }
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    let result = if options.tree_walker && options.command == Command::Run {
+        let mut interpreter = Interpreter::new(&mut out);
+        if let Some(seed) = options.seed {
+            interpreter = interpreter.with_seed(seed);
+        }
+        interpreter.run(&program)
+    } else {
+        compile(&program).and_then(|compiled| execute(options, &compiled, &mut out))
+    };
+    runtime_result(result, path)
+}
+
+/// Disassemble or run a program compiled earlier with `build`
+fn run_object(options: &Options, path: &Path, manager: &LanguageKeywordManager) -> Result<(), String> {
+    match options.command {
+        Command::Build => return Err(format!("{} is already compiled", path.display())),
+        _ if options.tree_walker || options.dump_tokens || options.dump_ast => {
+            return Err(format!("{} has no source; it can only be run or disassembled", path.display()))
+        }
+        _ => {}
+    }
+    let bytes = std::fs::read(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+    let (_, compiled) =
+        read_object(&bytes, manager.version()).map_err(|error| format!("{}: {}", path.display(), error))?;
+    let stdout = std::io::stdout();
+    runtime_result(execute(options, &compiled, &mut stdout.lock()), path)
+}
+
+/// Report a runtime error of the program at `path`. Output closing early,
+/// as in `moolang farm.moo | head`, is not one: the reader has what it
+/// wanted, so the program just stops.
+fn runtime_result(result: Result<(), RuntimeError>, path: &Path) -> Result<(), String> {
+    match result {
+        Ok(()) | Err(RuntimeError::Output { kind: ErrorKind::BrokenPipe, .. }) => Ok(()),
+        Err(error) => {
+            eprintln!("error: {}", error);
+            Err(format!("{}: runtime error", path.display()))
+        }
+    }
+}
+
+/// Print a dump to standard output, stopping quietly if it was closed early
+fn print_out(text: &str) -> Result<(), String> {
+    let mut stdout = std::io::stdout().lock();
+    match stdout.write_all(text.as_bytes()).and_then(|()| stdout.flush()) {
+        Err(error) if error.kind() == ErrorKind::BrokenPipe => std::process::exit(0),
+        result => result.map_err(|error| format!("could not write output: {}", error)),
+    }
+}
+
+fn execute(options: &Options, compiled: &CompiledProgram, out: &mut dyn Write) -> Result<(), RuntimeError> {
+    if options.command == Command::Disasm {
+        out.write_all(disassemble(compiled).as_bytes()).map_err(|error| RuntimeError::Output {
+            message: error.to_string(),
+            kind: error.kind(),
+        })
+    } else {
+        let mut vm = Vm::new(out);
+        if let Some(seed) = options.seed {
+            vm = vm.with_seed(seed);
+        }
+        vm.run(compiled)
+    }
+}
+
+/// `--lang` wins, then a `# languages:` pragma, then the file extension.
+/// Brought files always use their own pragma or extension.
+fn detect_language(options: &Options, path: &Path, source: &str) -> String {
+    match &options.language {
+        Some(language) => language.clone(),
+        None => file_language(path, source),
+    }
 }
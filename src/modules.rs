@@ -0,0 +1,343 @@
+//! `bring`: splitting a program across source files.
+//!
+//! `load_modules` replaces each `bring "path"` with the statements of the
+//! file it names, found relative to the file that brings it. Every file is
+//! lexed with its own keywords, so a `.muu` program can bring a `.moo`
+//! library: once parsed, both are the same tree and the library's
+//! farmfunctions are called the same way from either language.
+//!
+//! A file brought a second time adds nothing. A file that brings itself
+//! back, directly or through other files, is an error.
+//!
+//! Modules have no scope of their own: a brought file's top-level
+//! statements run where it is brought, and its globals, farmfunctions and
+//! stalls join the importer's top level. A name declared in both is reported
+//! as declared twice, with a hint pointing at the other file. Every span of
+//! a brought file carries its path in `Span::file`, so errors found in it
+//! later, up to runtime errors, name the file they are in.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::lexer::language_pragma;
+use crate::parser::ast::{Expr, ExprKind, FunctionDecl, Program, Stmt, StmtKind};
+use crate::parser::{parse_source, ParseError};
+use crate::toml_config::language_config::LanguageKeywordManager;
+use crate::utils::diagnostics::Diagnostic;
+use crate::utils::file_reader::FileType;
+use crate::utils::file_reader_errors::FileReaderError;
+use crate::utils::tokens::{file_name, Span};
+
+/// # ModuleError
+/// Errors produced while loading brought files.
+/// ## Currently 3 variants
+/// - Read { path, error, span }: the file could not be read
+/// - Syntax { path, errors }: the file has syntax errors
+/// - Cycle { chain, span }: the file is already being loaded; `chain` runs
+///   from that file back to itself
+#[derive(Debug)]
+pub enum ModuleError {
+    Read { path: PathBuf, error: FileReaderError, span: Span },
+    Syntax { path: PathBuf, errors: Vec<ParseError> },
+    Cycle { chain: Vec<PathBuf>, span: Span },
+}
+
+impl fmt::Display for ModuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModuleError::Read { path, error, span } => {
+                write!(f, "Cannot bring `{}` at {}: {}", path.display(), span, error)
+            }
+            ModuleError::Syntax { path, errors } => {
+                for error in errors {
+                    writeln!(f, "error: {}: {}", path.display(), error)?;
+                }
+                write!(f, "{}: {} syntax error(s)", path.display(), errors.len())
+            }
+            ModuleError::Cycle { chain, span } => {
+                let chain: Vec<String> = chain.iter().map(|path| path.display().to_string()).collect();
+                write!(f, "Import cycle at {}: {}", span, chain.join(" -> "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ModuleError {}
+
+impl ModuleError {
+    /// Where the file was brought, if the error points at a `bring`
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            ModuleError::Read { span, .. } | ModuleError::Cycle { span, .. } => Some(*span),
+            ModuleError::Syntax { .. } => None,
+        }
+    }
+
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let span = self.span().unwrap_or_default();
+        Diagnostic::error_at(self.to_string(), span)
+    }
+}
+
+/// The keywords a file is written in: its `# languages:` pragma, then its
+/// extension (`.muu` is Spanish, anything else English)
+pub fn file_language(path: &Path, source: &str) -> String {
+    if let Some(language) = language_pragma(source) {
+        return language.to_string();
+    }
+    match FileType::check_extension(path) {
+        Ok(FileType::Muu(_)) => "es".to_string(),
+        _ => "en".to_string(),
+    }
+}
+
+/// Replace every `bring` in `program`, the parsed contents of `path`, with
+/// the statements of the file it names
+pub fn load_modules(
+    program: Program,
+    path: &Path,
+    keywords: &LanguageKeywordManager,
+) -> Result<Program, ModuleError> {
+    let canonical = canonical(path);
+    let mut loader = Loader {
+        keywords,
+        loaded: HashSet::from([canonical.clone()]),
+        loading: vec![(canonical, path.to_path_buf())],
+    };
+    let statements = loader.statements(program.statements, directory(path))?;
+    Ok(Program { statements })
+}
+
+struct Loader<'a> {
+    keywords: &'a LanguageKeywordManager,
+    /// Every file brought so far, by canonical path
+    loaded: HashSet<PathBuf>,
+    /// The files being loaded, outermost first, by canonical path and by
+    /// the path they were brought as
+    loading: Vec<(PathBuf, PathBuf)>,
+}
+
+impl Loader<'_> {
+    fn statements(&mut self, statements: Vec<Stmt>, directory: &Path) -> Result<Vec<Stmt>, ModuleError> {
+        let mut loaded = Vec::with_capacity(statements.len());
+        for stmt in statements {
+            match stmt.kind {
+                StmtKind::Bring { path } => loaded.extend(self.bring(&directory.join(path), stmt.span)?),
+                _ => loaded.push(stmt),
+            }
+        }
+        Ok(loaded)
+    }
+
+    fn bring(&mut self, path: &Path, span: Span) -> Result<Vec<Stmt>, ModuleError> {
+        let source = FileType::read_file(path)
+            .map_err(|error| ModuleError::Read { path: path.to_path_buf(), error, span })?;
+        let canonical = canonical(path);
+        if let Some(start) = self.loading.iter().position(|(loading, _)| *loading == canonical) {
+            let mut chain: Vec<PathBuf> = self.loading[start..].iter().map(|(_, shown)| shown.clone()).collect();
+            chain.push(path.to_path_buf());
+            return Err(ModuleError::Cycle { chain, span });
+        }
+        if !self.loaded.insert(canonical.clone()) {
+            return Ok(Vec::new());
+        }
+
+        let mut program = parse_source(&source, self.keywords, &file_language(path, &source))
+            .map_err(|errors| ModuleError::Syntax { path: path.to_path_buf(), errors })?;
+        let file = Some(file_name(&path.display().to_string()));
+        for stmt in &mut program.statements {
+            mark_stmt(stmt, file);
+        }
+        self.loading.push((canonical, path.to_path_buf()));
+        let statements = self.statements(program.statements, directory(path));
+        self.loading.pop();
+        statements
+    }
+}
+
+/// Record in every span of `stmt` the file it was brought from
+fn mark_stmt(stmt: &mut Stmt, file: Option<&'static str>) {
+    stmt.span = stmt.span.in_file(file);
+    match &mut stmt.kind {
+        StmtKind::VarDecl { initializer, .. } => initializer.iter_mut().for_each(|value| mark_expr(value, file)),
+        StmtKind::Assign { target, value } | StmtKind::CompoundAssign { target, value, .. } => {
+            mark_expr(target, file);
+            mark_expr(value, file);
+        }
+        StmtKind::MultiAssign { targets, values } => {
+            targets.iter_mut().chain(values).for_each(|expr| mark_expr(expr, file));
+        }
+        StmtKind::Print { values } => values.iter_mut().for_each(|value| mark_expr(value, file)),
+        StmtKind::If { branches, else_branch } => {
+            for (condition, body) in branches {
+                mark_expr(condition, file);
+                body.iter_mut().for_each(|stmt| mark_stmt(stmt, file));
+            }
+            else_branch.iter_mut().flatten().for_each(|stmt| mark_stmt(stmt, file));
+        }
+        StmtKind::While { condition: expr, body } | StmtKind::For { iterable: expr, body, .. } => {
+            mark_expr(expr, file);
+            body.iter_mut().for_each(|stmt| mark_stmt(stmt, file));
+        }
+        StmtKind::Function(function) => mark_function(function, file),
+        StmtKind::Stall(stall) => {
+            stall.span = stall.span.in_file(file);
+            for field in &mut stall.fields {
+                field.span = field.span.in_file(file);
+                field.initializer.iter_mut().for_each(|value| mark_expr(value, file));
+            }
+            stall.functions.iter_mut().for_each(|function| mark_function(function, file));
+        }
+        StmtKind::Return(value) => value.iter_mut().for_each(|value| mark_expr(value, file)),
+        StmtKind::Expression(expr) => mark_expr(expr, file),
+        StmtKind::Break | StmtKind::Continue | StmtKind::Bring { .. } | StmtKind::Error => {}
+    }
+}
+
+fn mark_function(function: &mut FunctionDecl, file: Option<&'static str>) {
+    function.span = function.span.in_file(file);
+    for param in &mut function.params {
+        param.span = param.span.in_file(file);
+    }
+    function.body.iter_mut().for_each(|stmt| mark_stmt(stmt, file));
+}
+
+fn mark_expr(expr: &mut Expr, file: Option<&'static str>) {
+    expr.span = expr.span.in_file(file);
+    match &mut expr.kind {
+        ExprKind::CoopLiteral(items) | ExprKind::Range(items) => items.iter_mut().for_each(|item| mark_expr(item, file)),
+        ExprKind::BarnMapLiteral(entries) => {
+            for (key, value) in entries {
+                mark_expr(key, file);
+                mark_expr(value, file);
+            }
+        }
+        ExprKind::Grouping(inner)
+        | ExprKind::Unary { operand: inner, .. }
+        | ExprKind::Update { target: inner, .. }
+        | ExprKind::Member { object: inner, .. } => mark_expr(inner, file),
+        ExprKind::Binary { left, right, .. } | ExprKind::Index { object: left, index: right } => {
+            mark_expr(left, file);
+            mark_expr(right, file);
+        }
+        ExprKind::Call { callee, args, .. } => {
+            mark_expr(callee, file);
+            args.iter_mut().for_each(|arg| mark_expr(arg, file));
+        }
+        ExprKind::Integer(_)
+        | ExprKind::Decimal(_)
+        | ExprKind::Text(_)
+        | ExprKind::Boolean(_)
+        | ExprKind::Variable(_)
+        | ExprKind::Error => {}
+    }
+}
+
+/// Two paths to the same file load it once
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn directory(path: &Path) -> &Path {
+    path.parent().unwrap_or(Path::new(""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::interpreter::Interpreter;
+    use crate::semantic::resolve_names;
+    use crate::toml_config::language_config::MooConfig;
+
+    /// Write `files` to a fresh directory and load the first one
+    fn load(test: &str, files: &[(&str, &str)]) -> Result<Program, ModuleError> {
+        let directory = std::env::temp_dir().join(format!("moolang-modules-{}-{}", test, std::process::id()));
+        std::fs::create_dir_all(directory.join("lib")).unwrap();
+        for (name, source) in files {
+            std::fs::write(directory.join(name), source).unwrap();
+        }
+        let manager = LanguageKeywordManager::from_config(MooConfig::from_default());
+        let path = directory.join(files[0].0);
+        let source = FileType::read_file(&path).unwrap();
+        let program = parse_source(&source, &manager, &file_language(&path, &source)).unwrap();
+        let result = load_modules(program, &path, &manager);
+        std::fs::remove_dir_all(&directory).unwrap();
+        result
+    }
+
+    fn run(program: &Program) -> String {
+        let mut out = Vec::new();
+        Interpreter::new(&mut out).run(program).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_spanish_program_brings_english_library() {
+        let program = load("languages", &[
+            ("main.muu", "traer \"lib/tools.moo\"\ntraer \"lib/tools.moo\"\nmuuu double(2)\n"),
+            ("lib/tools.moo", "farmfunction 'num' double(num n) {\n    return n * 2\n}\n"),
+        ]).unwrap();
+        // The second `traer` adds nothing
+        assert_eq!(program.statements.len(), 2);
+        assert!(resolve_names(&program).is_empty());
+        assert_eq!(run(&program), "4\n");
+    }
+
+    #[test]
+    fn test_errors_name_the_brought_file() {
+        let program = load("locations", &[
+            ("main.moo", "bring \"lib/c.moo\"\nmoo half(4)\n"),
+            ("lib/c.moo", "farmfunction 'num' half(num n) {\n    return n // missing\n}\n"),
+        ]).unwrap();
+        let diagnostics = resolve_names(&program);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].file.is_some_and(|file| file.ends_with("lib/c.moo")), "{:?}", diagnostics[0]);
+        assert_eq!((diagnostics[0].line, diagnostics[0].column), (2, 17));
+        assert!(diagnostics[0].to_string().contains("lib/c.moo:2:17"), "{}", diagnostics[0]);
+
+        let program = load("runtime", &[
+            ("main.moo", "bring \"lib/c.moo\"\nmoo half(4)\n"),
+            ("lib/c.moo", "farmfunction 'num' half(num n) {\n    return n // 0\n}\n"),
+        ]).unwrap();
+        let error = Interpreter::new(&mut Vec::new()).run(&program).unwrap_err();
+        assert!(error.to_string().contains("lib/c.moo:2:12"), "{}", error);
+    }
+
+    #[test]
+    fn test_brought_top_level_joins_the_importer() {
+        // Top-level code runs where the file is brought
+        let program = load("inline", &[
+            ("main.moo", "moo \"before\"\nbring \"lib/c.moo\"\nmoo herd\n"),
+            ("lib/c.moo", "num herd = 3\nmoo \"library\"\n"),
+        ]).unwrap();
+        assert_eq!(run(&program), "before\nlibrary\n3\n");
+
+        // So a name declared in both files collides
+        let program = load("collision", &[
+            ("main.moo", "bring \"lib/c.moo\"\nnum herd = 4\nmoo herd\n"),
+            ("lib/c.moo", "num herd = 3\n"),
+        ]).unwrap();
+        let diagnostics = resolve_names(&program);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "`herd` is already declared in this scope");
+        assert_eq!((diagnostics[0].file, diagnostics[0].line), (None, 2));
+        let hint = diagnostics[0].hint.as_deref().unwrap();
+        assert!(hint.starts_with("the first declaration is at ") && hint.ends_with("lib/c.moo:1:1"), "{}", hint);
+    }
+
+    #[test]
+    fn test_cycles_and_missing_files() {
+        let error = load("cycle", &[
+            ("a.moo", "bring \"lib/b.moo\"\n"),
+            ("lib/b.moo", "bring \"../a.moo\"\n"),
+        ]).unwrap_err();
+        let ModuleError::Cycle { chain, .. } = &error else { panic!("expected a cycle, got {}", error) };
+        let names: Vec<_> = chain.iter().map(|path| path.file_name().unwrap().to_str().unwrap()).collect();
+        assert_eq!(names, ["a.moo", "b.moo", "a.moo"]);
+
+        let error = load("missing", &[("a.moo", "bring \"nowhere.moo\"\n")]).unwrap_err();
+        assert!(matches!(error, ModuleError::Read { .. }));
+        assert_eq!(error.span().map(|span| span.line), Some(1));
+    }
+}
@@ -0,0 +1,10 @@
+#[allow(clippy::module_inception)]
+pub mod parser;
+pub mod ast;
+pub mod cst;
+pub mod errors;
+pub mod precedence;
+pub mod printer;
+
+pub use parser::*;
+pub use errors::*;
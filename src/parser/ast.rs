@@ -0,0 +1,269 @@
+use serde::Serialize;
+
+use crate::utils::tokens::Span;
+
+/// The variable a stall method's instance is bound to. The self keyword of
+/// every pack parses to `ExprKind::Variable(SELF)`.
+pub const SELF: &str = "self";
+
+/// A whole source file
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Program {
+    pub statements: Vec<Stmt>,
+}
+
+/// A declared type such as `num`, `coop 'text'`, `barn_map 'text':'num'`
+/// or a stall name.
+/// Nested collection types inside quotes (`coop 'coop'`) have no parameters,
+/// so collection element types are optional.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum TypeExpr {
+    Text,
+    Num,
+    Dec,
+    Boolean,
+    Coop(Option<Box<TypeExpr>>),
+    BarnMap(Option<(Box<TypeExpr>, Box<TypeExpr>)>),
+    /// `barn`, the type of `barn(start, end, step)`
+    Range,
+    /// An instance of the stall with this name, e.g. `Cow`
+    Stall(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum StmtKind {
+    /// `num count = 10`
+    VarDecl { ty: TypeExpr, name: String, initializer: Option<Expr> },
+    /// `total = total + count`, `animals[0] = "cow"`
+    Assign { target: Expr, value: Expr },
+    /// `total += count`, `counts[i] -= 1`
+    CompoundAssign { target: Expr, op: BinaryOp, value: Expr },
+    /// `a, b = b, a`: every value is found before any target is assigned
+    MultiAssign { targets: Vec<Expr>, values: Vec<Expr> },
+    /// `moo "Added", count, animal`
+    Print { values: Vec<Expr> },
+    /// `if` / `else if` chain with optional `else`
+    If { branches: Vec<(Expr, Vec<Stmt>)>, else_branch: Option<Vec<Stmt>> },
+    While { condition: Expr, body: Vec<Stmt> },
+    /// `for animal in animals { ... }`, or `for key, value in inventory { ... }`
+    /// with `value` naming each barn_map entry's value
+    For { variable: String, value: Option<String>, iterable: Expr, body: Vec<Stmt> },
+    Function(FunctionDecl),
+    Stall(StallDecl),
+    Return(Option<Expr>),
+    /// `leave`: exit the innermost loop
+    Break,
+    /// `skip`: go on to the innermost loop's next round
+    Continue,
+    /// `bring "farm_tools.moo"`, replaced by the file's statements when
+    /// modules are loaded
+    Bring { path: String },
+    Expression(Expr),
+    /// Placeholder for a statement that failed to parse
+    Error,
+}
+
+/// `farmfunction 'num' calculateTotal(num a, num b) { ... }`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FunctionDecl {
+    pub name: String,
+    pub params: Vec<Param>,
+    pub return_type: Option<TypeExpr>,
+    pub body: Vec<Stmt>,
+    pub span: Span,
+}
+
+/// `stall Cow { text name; farmfunction Cow(text n) { ... } ... }`. The
+/// farmfunction named like the stall is its constructor; the others are
+/// methods, called on an instance that they refer to as `self`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StallDecl {
+    pub name: String,
+    pub fields: Vec<FieldDecl>,
+    /// The constructor and the methods, in source order
+    pub functions: Vec<FunctionDecl>,
+    pub span: Span,
+}
+
+/// `num age = 0` inside a stall. The initializer runs at the start of the
+/// constructor, for each new instance.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldDecl {
+    pub ty: TypeExpr,
+    pub name: String,
+    pub initializer: Option<Expr>,
+    pub span: Span,
+}
+
+impl StallDecl {
+    pub fn constructor(&self) -> Option<&FunctionDecl> {
+        self.functions.iter().find(|method| method.name == self.name)
+    }
+
+    /// The methods, without the constructor
+    pub fn methods(&self) -> impl Iterator<Item = &FunctionDecl> {
+        self.functions.iter().filter(|method| method.name != self.name)
+    }
+
+    /// What runs on each new instance: the field initializers, as
+    /// assignments to `self`, followed by the constructor's body
+    pub fn init(&self) -> FunctionDecl {
+        let constructor = self.constructor();
+        let mut body: Vec<Stmt> = self.fields.iter()
+            .filter_map(|field| {
+                let value = field.initializer.clone()?;
+                let receiver = Expr { kind: ExprKind::Variable(SELF.to_string()), span: field.span };
+                let target = Expr {
+                    kind: ExprKind::Member { object: Box::new(receiver), name: field.name.clone() },
+                    span: field.span,
+                };
+                Some(Stmt { kind: StmtKind::Assign { target, value }, span: field.span })
+            })
+            .collect();
+        body.extend(constructor.into_iter().flat_map(|constructor| constructor.body.iter().cloned()));
+        FunctionDecl {
+            name: self.name.clone(),
+            params: constructor.map_or_else(Vec::new, |constructor| constructor.params.clone()),
+            return_type: None,
+            body,
+            span: constructor.map_or(self.span, |constructor| constructor.span),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Param {
+    pub ty: TypeExpr,
+    pub name: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum ExprKind {
+    Integer(i64),
+    Decimal(f64),
+    Text(String),
+    Boolean(bool),
+    Variable(String),
+    /// `["cow", "pig"]`
+    CoopLiteral(Vec<Expr>),
+    /// `{"cow": 5}`
+    BarnMapLiteral(Vec<(Expr, Expr)>),
+    /// `barn(10)`
+    Range(Vec<Expr>),
+    /// `(a + b)`, kept so printers can reproduce the student's parentheses
+    Grouping(Box<Expr>),
+    Unary { op: UnaryOp, operand: Box<Expr> },
+    Binary { op: BinaryOp, left: Box<Expr>, right: Box<Expr> },
+    /// `++count` (prefix) or `count++` (postfix)
+    Update { op: UpdateOp, prefix: bool, target: Box<Expr> },
+    /// `builtin` is the canonical name of the builtin function or method
+    /// the callee is spelled as in its keyword pack, e.g. `length` for
+    /// `largo(t)`; a declaration of the same name hides it
+    Call {
+        callee: Box<Expr>,
+        args: Vec<Expr>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        builtin: Option<&'static str>,
+    },
+    /// `animals[index]`
+    Index { object: Box<Expr>, index: Box<Expr> },
+    /// `animals.collect`, or a stall field such as `daisy.name`
+    Member { object: Box<Expr>, name: String },
+    /// Placeholder for an expression that failed to parse
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum UnaryOp {
+    Negate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    IntegerDivide,
+    Modulo,
+    Less,
+    Greater,
+    Is,
+    IsNot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum UpdateOp {
+    Increment,
+    Decrement,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+impl Stmt {
+    pub fn new(kind: StmtKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+impl BinaryOp {
+    /// Source symbol in English, e.g. `is not`
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Subtract => "-",
+            BinaryOp::Multiply => "*",
+            BinaryOp::Divide => "/",
+            BinaryOp::IntegerDivide => "//",
+            BinaryOp::Modulo => "%",
+            BinaryOp::Less => "<",
+            BinaryOp::Greater => ">",
+            BinaryOp::Is => "is",
+            BinaryOp::IsNot => "is not",
+        }
+    }
+}
+
+impl UpdateOp {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            UpdateOp::Increment => "++",
+            UpdateOp::Decrement => "--",
+        }
+    }
+}
+
+impl std::fmt::Display for TypeExpr {
+    /// English source spelling, e.g. `barn_map 'text':'num'`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypeExpr::Text => write!(f, "text"),
+            TypeExpr::Num => write!(f, "num"),
+            TypeExpr::Dec => write!(f, "dec"),
+            TypeExpr::Boolean => write!(f, "boolean"),
+            TypeExpr::Coop(None) => write!(f, "coop"),
+            TypeExpr::Coop(Some(element)) => write!(f, "coop '{}'", element),
+            TypeExpr::BarnMap(None) => write!(f, "barn_map"),
+            TypeExpr::BarnMap(Some((key, value))) => write!(f, "barn_map '{}':'{}'", key, value),
+            TypeExpr::Range => write!(f, "barn"),
+            TypeExpr::Stall(name) => write!(f, "{}", name),
+        }
+    }
+}
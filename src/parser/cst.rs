@@ -0,0 +1,376 @@
+//! Lossless concrete syntax tree.
+//!
+//! The CST keeps every byte of the source: each lexer token plus the spaces
+//! and tabs between them (`Whitespace`), line breaks (`Newline`) and comments
+//! (`Comment`). `SyntaxTree::text` reproduces the original file exactly,
+//! which is what formatters, translators and editor tooling need.
+//!
+//! The tree is deliberately shallow. A `Program` holds `Statement` nodes and
+//! the trivia between them; a statement holds its tokens, up to and including
+//! its terminator, and a `Block` node for each `{ ... }` body it owns. The
+//! typed AST is a view over this tree: `SyntaxNode::ast` lowers a single
+//! statement node and `SyntaxTree::ast` lowers the whole file.
+
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use crate::lexer::terminators::{starts_expression, terminate_statements};
+use crate::lexer::{Lexer, LexerError};
+use crate::parser::ast::{Program, Stmt};
+use crate::parser::errors::ParseError;
+use crate::parser::parser::Parser;
+use crate::toml_config::language_config::LanguageKeywordManager;
+use crate::utils::tokens::{Literal, Token, TokenType};
+use crate::utils::unicode;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyntaxKind {
+    Program,
+    Statement,
+    /// `{ ... }` body of an `if`, `while`, `for` or `farmfunction`
+    Block,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(Token),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxNode {
+    pub kind: SyntaxKind,
+    pub children: Vec<SyntaxElement>,
+}
+
+/// A whole source file as a lossless tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxTree {
+    root: SyntaxNode,
+}
+
+impl SyntaxTree {
+    /// Lex `source` and build its tree
+    pub fn parse(
+        source: &str,
+        keywords: &LanguageKeywordManager,
+        language: &str,
+    ) -> Result<Self, LexerError> {
+        let tokens = Lexer::new(source, keywords, language)?.tokenize()?;
+        Ok(Self::from_tokens(source, &tokens))
+    }
+
+    /// Build a tree from the lexer's output for `source`. The tokens must be
+    /// the unfiltered lexer output, before `terminate_statements`.
+    pub fn from_tokens(source: &str, tokens: &[Token]) -> Self {
+        let (terminated, _) = terminate_statements(tokens);
+        let terminators: HashSet<(usize, usize)> = terminated.iter()
+            .filter(|token| is_terminator(&token.token_type) && !token.lexeme.is_empty())
+            .map(|token| (token.line, token.column))
+            .collect();
+
+        let mut builder = Builder {
+            tokens: with_whitespace(source, tokens),
+            position: 0,
+            terminators,
+        };
+        let mut children = builder.statements(false);
+        // Whatever is left is the final EOF token
+        children.extend(builder.tokens.drain(builder.position..).map(SyntaxElement::Token));
+        Self { root: SyntaxNode { kind: SyntaxKind::Program, children } }
+    }
+
+    pub fn root(&self) -> &SyntaxNode {
+        &self.root
+    }
+
+    /// The exact source text the tree was built from
+    pub fn text(&self) -> String {
+        self.root.text()
+    }
+
+    /// Top-level statement nodes, in source order
+    pub fn statements(&self) -> impl Iterator<Item = &SyntaxNode> {
+        self.root.child_nodes()
+    }
+
+    /// Lower the tree to the typed AST, one statement node at a time
+    pub fn ast(&self) -> Result<Program, Vec<ParseError>> {
+        let mut statements = Vec::new();
+        let mut errors = Vec::new();
+        for node in self.statements() {
+            match node.ast() {
+                Ok(stmt) => statements.extend(stmt),
+                Err(mut found) => errors.append(&mut found),
+            }
+        }
+        if errors.is_empty() {
+            Ok(Program { statements })
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Rewrite every keyword in the keyword pack of `language`, keeping all
+    /// other text (names, literals, spacing, comments) byte-for-byte.
+    /// Keywords with no counterpart in `language` are left as written.
+    /// Builtin names and boolean literals are translated too.
+    pub fn translate(&self, keywords: &LanguageKeywordManager, language: &str) -> String {
+        let keyword_set = keywords.get_keyword_set(language);
+        let builtin_set = keywords.get_builtin_set(language);
+        let targets: Vec<(TokenType, &str)> = keyword_set
+            .map(|set| set.entries())
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(_, word)| {
+                keywords.get_token_type(word, language).map(|token| (token.canonical(), word))
+            })
+            .collect();
+
+        let mut text = String::new();
+        for token in self.root.tokens() {
+            let canonical = token.token_type.canonical();
+            let word = match (&token.literal, keyword_set) {
+                (Some(Literal::Boolean(value)), Some(set)) if canonical == TokenType::BooleanLiteral => {
+                    Some(if *value { set.true_literal.as_str() } else { set.false_literal.as_str() })
+                }
+                (Some(Literal::Builtin(name)), _) => builtin_set.and_then(|set| set.get(name)),
+                _ => targets.iter().find(|(token_type, _)| *token_type == canonical).map(|(_, word)| *word),
+            };
+            text.push_str(word.unwrap_or(&token.lexeme));
+        }
+        text
+    }
+
+    /// Indented outline of the tree, one token per line
+    pub fn outline(&self) -> String {
+        let mut text = String::new();
+        self.root.outline(0, &mut text);
+        text
+    }
+}
+
+impl SyntaxNode {
+    /// Every token under this node, trivia included, in source order
+    pub fn tokens(&self) -> Vec<&Token> {
+        let mut tokens = Vec::new();
+        self.collect_tokens(&mut tokens);
+        tokens
+    }
+
+    fn collect_tokens<'a>(&'a self, tokens: &mut Vec<&'a Token>) {
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(node) => node.collect_tokens(tokens),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+    }
+
+    pub fn child_nodes(&self) -> impl Iterator<Item = &SyntaxNode> {
+        self.children.iter().filter_map(|child| match child {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+
+    pub fn text(&self) -> String {
+        self.tokens().into_iter().map(|token| token.lexeme.as_str()).collect()
+    }
+
+    /// Lower a `Statement` node to its AST statement. Returns `None` for a
+    /// node that only holds separators such as a stray `;`.
+    pub fn ast(&self) -> Result<Option<Stmt>, Vec<ParseError>> {
+        let mut tokens: Vec<Token> = self.tokens().into_iter()
+            .filter(|token| token.token_type != TokenType::Whitespace)
+            .cloned()
+            .collect();
+        let (line, column) = tokens.last().map_or((1, 1), |token| (token.line, token.column));
+        tokens.push(Token::new(TokenType::EOF, "", line, column));
+
+        let (tokens, _) = terminate_statements(&tokens);
+        let mut program = Parser::new(tokens).parse_program()?;
+        Ok(program.statements.pop())
+    }
+
+    fn outline(&self, depth: usize, text: &mut String) {
+        let _ = writeln!(text, "{}{:?}", "  ".repeat(depth), self.kind);
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(node) => node.outline(depth + 1, text),
+                SyntaxElement::Token(token) => {
+                    let _ = writeln!(text, "{}{:?} {:?}", "  ".repeat(depth + 1), token.token_type, token.lexeme);
+                }
+            }
+        }
+    }
+}
+
+fn is_terminator(token_type: &TokenType) -> bool {
+    matches!(token_type, TokenType::Newline | TokenType::Semicolon)
+}
+
+fn is_trivia(token_type: &TokenType) -> bool {
+    matches!(token_type, TokenType::Whitespace | TokenType::Newline | TokenType::Comment)
+}
+
+/// Interleave `Whitespace` tokens for the gaps the lexer skipped. Lexemes are
+/// raw source slices, so the gaps are exactly the text between them.
+fn with_whitespace(source: &str, tokens: &[Token]) -> Vec<Token> {
+    let mut output = Vec::with_capacity(tokens.len() * 2);
+    let mut rest = source;
+    for token in tokens {
+        let skipped = rest.len() - rest.trim_start_matches([' ', '\t', '\r']).len();
+        if skipped > 0 {
+            let gap = &rest[..skipped];
+            let column = token.column.saturating_sub(unicode::grapheme_width(gap)).max(1);
+            output.push(Token::new(TokenType::Whitespace, gap, token.line, column));
+            rest = &rest[skipped..];
+        }
+        debug_assert!(rest.starts_with(token.lexeme.as_str()), "lexeme {:?} is not raw source", token.lexeme);
+        rest = &rest[token.lexeme.len()..];
+        output.push(token.clone());
+    }
+    output
+}
+
+struct Builder {
+    tokens: Vec<Token>,
+    position: usize,
+    /// Positions of the newlines and semicolons that end statements
+    terminators: HashSet<(usize, usize)>,
+}
+
+impl Builder {
+    /// Statements and the trivia between them, up to a closing `}` (inside a
+    /// block) or the end of the file
+    fn statements(&mut self, in_block: bool) -> Vec<SyntaxElement> {
+        let mut children = Vec::new();
+        loop {
+            let token = &self.tokens[self.position];
+            match token.token_type {
+                TokenType::EOF => break,
+                TokenType::RightBrace if in_block => break,
+                ref token_type if is_trivia(token_type) || self.ends_statement(token) => {
+                    children.push(SyntaxElement::Token(self.advance()));
+                }
+                _ => children.push(SyntaxElement::Node(self.statement())),
+            }
+        }
+        children
+    }
+
+    fn statement(&mut self) -> SyntaxNode {
+        let mut children = Vec::new();
+        // Open `(`, `[` and map-literal `{` inside this statement
+        let mut depth = 0usize;
+        let mut previous: Option<TokenType> = None;
+
+        loop {
+            let token = &self.tokens[self.position];
+            let token_type = token.token_type.canonical();
+            match token_type {
+                TokenType::EOF => break,
+                TokenType::RightBrace if depth == 0 && !children.is_empty() => break,
+                TokenType::LeftBrace if depth == 0 && !previous.as_ref().is_some_and(starts_expression) => {
+                    children.push(SyntaxElement::Node(self.block()));
+                    previous = Some(TokenType::RightBrace);
+                    continue;
+                }
+                _ if depth == 0 && self.ends_statement(token) => {
+                    children.push(SyntaxElement::Token(self.advance()));
+                    break;
+                }
+                TokenType::LeftParen | TokenType::LeftBracket | TokenType::LeftBrace => depth += 1,
+                TokenType::RightParen | TokenType::RightBracket | TokenType::RightBrace => {
+                    depth = depth.saturating_sub(1);
+                }
+                _ => {}
+            }
+            if !is_trivia(&token_type) {
+                previous = Some(token_type);
+            }
+            children.push(SyntaxElement::Token(self.advance()));
+        }
+        SyntaxNode { kind: SyntaxKind::Statement, children }
+    }
+
+    fn block(&mut self) -> SyntaxNode {
+        let mut children = vec![SyntaxElement::Token(self.advance())];
+        children.extend(self.statements(true));
+        if self.tokens[self.position].token_type == TokenType::RightBrace {
+            children.push(SyntaxElement::Token(self.advance()));
+        }
+        SyntaxNode { kind: SyntaxKind::Block, children }
+    }
+
+    fn ends_statement(&self, token: &Token) -> bool {
+        is_terminator(&token.token_type) && self.terminators.contains(&(token.line, token.column))
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.position].clone();
+        self.position += 1;
+        token
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_source;
+    use crate::toml_config::language_config::MooConfig;
+
+    fn manager() -> LanguageKeywordManager {
+        LanguageKeywordManager::from_config(MooConfig::from_default())
+    }
+
+    #[test]
+    fn test_round_trip_is_byte_exact() {
+        let manager = manager();
+        let sources = [
+            ("en", include_str!("../lexer/english_testing.moo")),
+            ("es", include_str!("../lexer/spanish_testing.muu")),
+            ("en", "  num   total = a +\t\n    b   # trailing\r\n\n#* block\ncomment *#\nmoo total;moo 1"),
+        ];
+        for (language, source) in sources {
+            let tree = SyntaxTree::parse(source, &manager, language).unwrap();
+            assert_eq!(tree.text(), source);
+        }
+    }
+
+    #[test]
+    fn test_statement_and_block_structure() {
+        let manager = manager();
+        let source = "if (x) {\n    moo 1\n    moo 2\n} else {\n    moo 3\n}\nbarn_map 'text':'num' m = {\n  \"cow\": 5\n}\n";
+        let tree = SyntaxTree::parse(source, &manager, "en").unwrap();
+
+        let statements: Vec<&SyntaxNode> = tree.statements().collect();
+        assert_eq!(statements.len(), 2);
+        let blocks: Vec<&SyntaxNode> = statements[0].child_nodes().collect();
+        assert_eq!(blocks.len(), 2);
+        assert!(blocks.iter().all(|block| block.kind == SyntaxKind::Block));
+        assert_eq!(blocks[0].child_nodes().count(), 2);
+        // A map literal is part of its statement, not a block
+        assert_eq!(statements[1].child_nodes().count(), 0);
+    }
+
+    #[test]
+    fn test_ast_is_a_view_over_the_tree() {
+        let manager = manager();
+        let source = include_str!("../lexer/english_testing.moo");
+        let tree = SyntaxTree::parse(source, &manager, "en").unwrap();
+        assert_eq!(tree.ast().unwrap(), parse_source(source, &manager, "en").unwrap());
+    }
+
+    #[test]
+    fn test_translation_keeps_layout() {
+        let manager = manager();
+        let source = "coop 'text'  animals = [\"cow\"]   # the herd\nfor animal in animals {\n\tmoo animal, true, length(animal)\n}\n";
+        let tree = SyntaxTree::parse(source, &manager, "en").unwrap();
+        assert_eq!(
+            tree.translate(&manager, "es"),
+            "granja 'texto'  animals = [\"cow\"]   # the herd\npara animal en animals {\n\tmuuu animal, verdadero, largo(animal)\n}\n"
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use std::fmt;
+use crate::lexer::errors::LexerError;
+use crate::utils::diagnostics::Diagnostic;
+use crate::utils::tokens::{Span, TokenType};
+
+/// Tokens that can start a type
+pub const TYPE_KEYWORDS: &[TokenType] = &[
+    TokenType::Text, TokenType::Num, TokenType::Dec,
+    TokenType::Boolean, TokenType::Coop, TokenType::BarnMap, TokenType::Barn,
+];
+
+/// Tokens that can start an expression
+pub const EXPRESSION_START: &[TokenType] = &[
+    TokenType::Identifier, TokenType::IntegerLiteral, TokenType::DecimalLiteral,
+    TokenType::StringLiteral, TokenType::BooleanLiteral, TokenType::LeftParen,
+    TokenType::LeftBracket, TokenType::LeftBrace, TokenType::Barn, TokenType::SelfKw,
+    TokenType::Minus, TokenType::Increment, TokenType::Decrement,
+];
+
+/// # ParseError
+/// Errors produced while building the AST from tokens.
+/// ## Currently 6 variants
+/// - Lexer(LexerError): the source could not be tokenized
+/// - UnexpectedToken { expected, found, span }: `expected` lists every token
+///   that would have been accepted
+/// - ChainedComparison { span }: `a < b < c` without parentheses
+/// - InvalidAssignmentTarget { span }: e.g. `f() = 3`
+/// - MisplacedKeyword { keyword, place, span }: a keyword used where it is
+///   not allowed, e.g. `bring` inside a block or `leave` outside a loop
+/// - AssignmentCount { targets, values, span }: e.g. `a, b = 1`
+#[derive(Debug)]
+pub enum ParseError {
+    Lexer(LexerError),
+    UnexpectedToken { expected: Vec<TokenType>, found: String, span: Span },
+    ChainedComparison { span: Span },
+    InvalidAssignmentTarget { span: Span },
+    MisplacedKeyword { keyword: String, place: &'static str, span: Span },
+    AssignmentCount { targets: usize, values: usize, span: Span },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Lexer(error) => write!(f, "{}", error),
+            ParseError::UnexpectedToken { expected, found, span } => {
+                write!(f, "Expected {} but found {} at {}", describe_expected(expected), found, span)
+            }
+            ParseError::ChainedComparison { span } => write!(
+                f,
+                "Comparisons cannot be chained at {}; use parentheses to group them",
+                span
+            ),
+            ParseError::InvalidAssignmentTarget { span } => write!(
+                f,
+                "Invalid assignment target at {}; only variables, indexes and members can be assigned",
+                span
+            ),
+            ParseError::MisplacedKeyword { keyword, place, span } => {
+                write!(f, "`{}` at {} must be {}", keyword, span, place)
+            }
+            ParseError::AssignmentCount { targets, values, span } => write!(
+                f,
+                "Cannot assign {} value(s) to {} target(s) at {}; give one value per target",
+                values, targets, span
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl ParseError {
+    /// Where the error was found, if it points at source code
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            ParseError::Lexer(_) => None,
+            ParseError::UnexpectedToken { span, .. }
+            | ParseError::ChainedComparison { span }
+            | ParseError::InvalidAssignmentTarget { span }
+            | ParseError::MisplacedKeyword { span, .. }
+            | ParseError::AssignmentCount { span, .. } => Some(*span),
+        }
+    }
+
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let span = self.span().unwrap_or_default();
+        Diagnostic::error_at(self.to_string(), span)
+    }
+}
+
+/// Render an expected-token list for students, e.g. "`)` or `,`".
+/// The type and expression-start sets are collapsed to "a type" and
+/// "an expression".
+pub fn describe_expected(expected: &[TokenType]) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    let mut remaining: Vec<&TokenType> = expected.iter().collect();
+
+    for (set, name) in [(TYPE_KEYWORDS, "a type"), (EXPRESSION_START, "an expression")] {
+        if set.iter().all(|token| remaining.contains(&token)) {
+            remaining.retain(|token| !set.contains(token));
+            parts.push(name.to_string());
+        }
+    }
+    parts.extend(remaining.into_iter().map(|token| token.describe()));
+
+    match parts.len() {
+        0 => "nothing".to_string(),
+        1 => parts.remove(0),
+        _ => {
+            let last = parts.pop().unwrap_or_default();
+            format!("{} or {}", parts.join(", "), last)
+        }
+    }
+}
+
+impl From<LexerError> for ParseError {
+    fn from(error: LexerError) -> Self {
+        ParseError::Lexer(error)
+    }
+}
@@ -0,0 +1,1039 @@
+use crate::lexer::terminators::terminate_statements;
+use crate::lexer::Lexer;
+use crate::parser::ast::*;
+use crate::parser::errors::{ParseError, EXPRESSION_START, TYPE_KEYWORDS};
+use crate::parser::precedence::{self, Associativity, Fixity};
+use crate::toml_config::language_config::LanguageKeywordManager;
+use crate::utils::tokens::{Literal, Span, Token, TokenType};
+use crate::utils::unicode;
+
+/// Lex, terminate and parse a source file in one step.
+/// Returns every syntax error found, not just the first.
+pub fn parse_source(
+    source: &str,
+    keywords: &LanguageKeywordManager,
+    language: &str,
+) -> Result<Program, Vec<ParseError>> {
+    let tokens = Lexer::new(source, keywords, language)
+        .and_then(|lexer| lexer.tokenize())
+        .map_err(|error| vec![ParseError::from(error)])?;
+    let (tokens, _) = terminate_statements(&tokens);
+    Parser::new(tokens).parse_program()
+}
+
+/// # Parser
+/// Builds the AST from the token stream produced by `terminate_statements`.
+///
+/// Statements are parsed by recursive descent. Expressions use a Pratt parser
+/// whose binding powers come from `precedence::PRECEDENCE_TABLE`. Keyword
+/// tokens are compared in their canonical (English) form, so every keyword
+/// pack produces the same tree.
+///
+/// Syntax errors do not stop the parser. A statement that fails to parse is
+/// replaced by a `StmtKind::Error` node and parsing resumes at the next
+/// statement boundary: a terminator, a `}` or a statement keyword. A broken
+/// `if` / `while` / `for` header becomes an `ExprKind::Error` so the body is
+/// still parsed.
+pub struct Parser {
+    tokens: Vec<Token>,
+    current: usize,
+    errors: Vec<ParseError>,
+    /// How many blocks enclose the statement being parsed
+    depth: usize,
+    /// How many loops enclose it, within the innermost function
+    loops: usize,
+}
+
+impl Parser {
+    /// `tokens` must end with `EOF`
+    pub fn new(tokens: Vec<Token>) -> Self {
+        Self { tokens, current: 0, errors: Vec::new(), depth: 0, loops: 0 }
+    }
+
+    /// Parse the whole program, failing with every syntax error found
+    pub fn parse_program(&mut self) -> Result<Program, Vec<ParseError>> {
+        let (program, errors) = self.parse_with_recovery();
+        if errors.is_empty() {
+            Ok(program)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Parse the whole program, returning a tree with `Error` placeholders
+    /// alongside the syntax errors. Useful for tools that work on broken code.
+    pub fn parse_with_recovery(&mut self) -> (Program, Vec<ParseError>) {
+        let mut statements = Vec::new();
+        self.skip_terminators();
+        while !self.is_at_end() {
+            statements.push(self.statement_or_error());
+            self.skip_terminators();
+        }
+        (Program { statements }, std::mem::take(&mut self.errors))
+    }
+
+    // ================================
+    // Error recovery
+    // ================================
+
+    fn statement_or_error(&mut self) -> Stmt {
+        let start = self.current;
+        match self.statement() {
+            Ok(stmt) => stmt,
+            Err(error) => {
+                let span = error.span().unwrap_or_else(|| self.peek().span());
+                self.errors.push(error);
+                self.synchronize();
+                // Always make progress, e.g. past a stray `}` at top level
+                if self.current == start {
+                    self.advance();
+                }
+                Stmt::new(StmtKind::Error, span)
+            }
+        }
+    }
+
+    /// Skip to the next statement boundary. Braces opened while skipping are
+    /// skipped as a unit so a broken header does not orphan its block.
+    fn synchronize(&mut self) {
+        let mut depth = 0usize;
+        while !self.is_at_end() {
+            match self.peek_type() {
+                TokenType::Newline | TokenType::Semicolon if depth == 0 => {
+                    self.advance();
+                    return;
+                }
+                TokenType::RightBrace if depth == 0 => return,
+                TokenType::RightBrace => depth -= 1,
+                TokenType::LeftBrace => depth += 1,
+                token_type if depth == 0 && starts_statement(&token_type) => return,
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+
+    /// Condition of an `if` / `while` / `for`. On error, skips to the `{` that
+    /// opens the body and returns an `Error` expression.
+    fn header_expression(&mut self) -> Expr {
+        match self.expression() {
+            Ok(expr) => expr,
+            Err(error) => {
+                let span = error.span().unwrap_or_else(|| self.peek().span());
+                self.errors.push(error);
+                while !matches!(
+                    self.peek_type(),
+                    TokenType::LeftBrace | TokenType::Newline | TokenType::Semicolon | TokenType::EOF
+                ) {
+                    self.advance();
+                }
+                Expr::new(ExprKind::Error, span)
+            }
+        }
+    }
+
+    // ================================
+    // Statements
+    // ================================
+
+    fn statement(&mut self) -> Result<Stmt, ParseError> {
+        let stmt = match self.peek_type() {
+            t if is_type_keyword(&t) => self.var_declaration()?,
+            // `barn steps = ...` declares a range; `barn(10)` is an expression
+            TokenType::Barn if self.check_next(&TokenType::Identifier) => self.var_declaration()?,
+            // `Cow daisy = ...` declares a stall instance
+            TokenType::Identifier if self.check_next(&TokenType::Identifier) => self.var_declaration()?,
+            TokenType::Moo => self.print_statement()?,
+            TokenType::If => self.if_statement()?,
+            TokenType::While => self.while_statement()?,
+            TokenType::For => self.for_statement()?,
+            TokenType::Farmfunction => self.function_declaration()?,
+            TokenType::Stall => self.stall_declaration()?,
+            TokenType::Return => self.return_statement()?,
+            TokenType::Bring => self.bring_statement()?,
+            TokenType::Leave | TokenType::Skip => self.loop_jump()?,
+            _ => self.expression_statement()?,
+        };
+        self.end_of_statement()?;
+        Ok(stmt)
+    }
+
+    /// Statements end at a terminator, before a block's `}`, or at EOF
+    fn end_of_statement(&mut self) -> Result<(), ParseError> {
+        match self.peek_type() {
+            TokenType::Newline | TokenType::Semicolon => {
+                self.advance();
+                Ok(())
+            }
+            TokenType::RightBrace | TokenType::EOF => Ok(()),
+            _ => Err(self.unexpected(&[TokenType::Newline, TokenType::Semicolon, TokenType::RightBrace])),
+        }
+    }
+
+    fn var_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.peek().span();
+        let ty = self.type_expr()?;
+        let name = self.identifier()?;
+        let initializer = if self.matches(&TokenType::Equals) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        Ok(Stmt::new(StmtKind::VarDecl { ty, name, initializer }, start.to(self.previous_span())))
+    }
+
+    fn print_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.advance().span();
+        let mut values = Vec::new();
+        if !self.at_statement_end() {
+            values.push(self.expression()?);
+            while self.matches(&TokenType::Comma) {
+                values.push(self.expression()?);
+            }
+        }
+        Ok(Stmt::new(StmtKind::Print { values }, start.to(self.previous_span())))
+    }
+
+    fn if_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.advance().span();
+        let mut branches = vec![(self.header_expression(), self.block()?)];
+        let mut else_branch = None;
+
+        loop {
+            if self.matches(&TokenType::ElseIf) {
+                branches.push((self.header_expression(), self.block()?));
+            } else if self.matches(&TokenType::Else) {
+                if self.matches(&TokenType::If) {
+                    branches.push((self.header_expression(), self.block()?));
+                } else {
+                    else_branch = Some(self.block()?);
+                    break;
+                }
+            } else {
+                break;
+            }
+        }
+        Ok(Stmt::new(StmtKind::If { branches, else_branch }, start))
+    }
+
+    fn while_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.advance().span();
+        let condition = self.header_expression();
+        let body = self.loop_body()?;
+        Ok(Stmt::new(StmtKind::While { condition, body }, start))
+    }
+
+    fn for_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.advance().span();
+        let variable = self.identifier()?;
+        let value = if self.matches(&TokenType::Comma) { Some(self.identifier()?) } else { None };
+        self.expect(&TokenType::In)?;
+        let iterable = self.header_expression();
+        let body = self.loop_body()?;
+        Ok(Stmt::new(StmtKind::For { variable, value, iterable, body }, start))
+    }
+
+    fn function_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let decl = self.function()?;
+        let span = decl.span;
+        Ok(Stmt::new(StmtKind::Function(decl), span))
+    }
+
+    /// `farmfunction 'type' name(params) { body }`
+    fn function(&mut self) -> Result<FunctionDecl, ParseError> {
+        let start = self.advance().span();
+        let return_type = if self.check(&TokenType::Quote) {
+            Some(self.quoted_type()?)
+        } else {
+            None
+        };
+        let name = self.identifier()?;
+
+        self.expect(&TokenType::LeftParen)?;
+        let mut params = Vec::new();
+        if !self.check(&TokenType::RightParen) {
+            loop {
+                // A lone name is a parameter missing its type, not a stall type
+                if self.check(&TokenType::Identifier) && !self.check_next(&TokenType::Identifier) {
+                    return Err(self.unexpected(TYPE_KEYWORDS));
+                }
+                let param_start = self.peek().span();
+                let ty = self.type_expr()?;
+                let name = self.identifier()?;
+                params.push(Param { ty, name, span: param_start.to(self.previous_span()) });
+                if !self.matches(&TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+        self.expect(&TokenType::RightParen)?;
+        // `leave` and `skip` cannot reach a loop outside the function
+        let loops = std::mem::take(&mut self.loops);
+        let body = self.block();
+        self.loops = loops;
+        let body = body?;
+
+        Ok(FunctionDecl { name, params, return_type, body, span: start })
+    }
+
+    /// `stall Cow { fields and farmfunctions }`, one member per line
+    fn stall_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.advance().span();
+        let name = self.identifier()?;
+        self.expect(&TokenType::LeftBrace)?;
+
+        let mut fields = Vec::new();
+        let mut functions = Vec::new();
+        self.skip_terminators();
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            if self.check(&TokenType::Farmfunction) {
+                functions.push(self.function()?);
+            } else {
+                let field_start = self.peek().span();
+                let ty = self.type_expr()?;
+                let name = self.identifier()?;
+                let initializer = if self.matches(&TokenType::Equals) {
+                    Some(self.expression()?)
+                } else {
+                    None
+                };
+                fields.push(FieldDecl { ty, name, initializer, span: field_start.to(self.previous_span()) });
+            }
+            self.end_of_statement()?;
+            self.skip_terminators();
+        }
+        self.expect(&TokenType::RightBrace)?;
+
+        let decl = StallDecl { name, fields, functions, span: start };
+        Ok(Stmt::new(StmtKind::Stall(decl), start))
+    }
+
+    fn return_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.advance().span();
+        let value = if self.at_statement_end() {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        Ok(Stmt::new(StmtKind::Return(value), start.to(self.previous_span())))
+    }
+
+    /// `bring "farm_tools.moo"`, only at the top level of a file
+    fn bring_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.advance();
+        if self.depth > 0 {
+            return Err(ParseError::MisplacedKeyword {
+                span: keyword.span(),
+                keyword: keyword.lexeme,
+                place: "at the top level of a file",
+            });
+        }
+        let path = self.expect(&TokenType::StringLiteral)?;
+        let span = keyword.span().to(path.span());
+        Ok(Stmt::new(StmtKind::Bring { path: unescape(&path.lexeme) }, span))
+    }
+
+    /// `leave` or `skip`, only inside a loop
+    fn loop_jump(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.advance();
+        if self.loops == 0 {
+            return Err(ParseError::MisplacedKeyword {
+                span: keyword.span(),
+                keyword: keyword.lexeme,
+                place: "inside a loop",
+            });
+        }
+        let kind = if keyword.token_type.canonical() == TokenType::Leave { StmtKind::Break } else { StmtKind::Continue };
+        Ok(Stmt::new(kind, keyword.span()))
+    }
+
+    /// An expression, or an assignment when followed by `=`, `+=`, ... or
+    /// by more targets
+    fn expression_statement(&mut self) -> Result<Stmt, ParseError> {
+        let expr = self.expression()?;
+        if let Some(op) = compound_op(&self.peek_type()) {
+            self.advance();
+            if !is_assignable(&expr) {
+                return Err(ParseError::InvalidAssignmentTarget { span: expr.span });
+            }
+            let value = self.expression()?;
+            let span = expr.span.to(value.span);
+            return Ok(Stmt::new(StmtKind::CompoundAssign { target: expr, op, value }, span));
+        }
+        if self.check(&TokenType::Comma) {
+            return self.multiple_assignment(expr);
+        }
+        if self.matches(&TokenType::Equals) {
+            if !is_assignable(&expr) {
+                return Err(ParseError::InvalidAssignmentTarget { span: expr.span });
+            }
+            let value = self.expression()?;
+            let span = expr.span.to(value.span);
+            return Ok(Stmt::new(StmtKind::Assign { target: expr, value }, span));
+        }
+        let span = expr.span;
+        Ok(Stmt::new(StmtKind::Expression(expr), span))
+    }
+
+    /// `a, b = b, a`, after the first target
+    fn multiple_assignment(&mut self, first: Expr) -> Result<Stmt, ParseError> {
+        let mut targets = vec![first];
+        while self.matches(&TokenType::Comma) {
+            targets.push(self.expression()?);
+        }
+        if let Some(target) = targets.iter().find(|target| !is_assignable(target)) {
+            return Err(ParseError::InvalidAssignmentTarget { span: target.span });
+        }
+        self.expect(&TokenType::Equals)?;
+        let mut values = vec![self.expression()?];
+        while self.matches(&TokenType::Comma) {
+            values.push(self.expression()?);
+        }
+        let span = targets[0].span.to(self.previous_span());
+        if targets.len() != values.len() {
+            return Err(ParseError::AssignmentCount { targets: targets.len(), values: values.len(), span });
+        }
+        Ok(Stmt::new(StmtKind::MultiAssign { targets, values }, span))
+    }
+
+    fn loop_body(&mut self) -> Result<Vec<Stmt>, ParseError> {
+        self.loops += 1;
+        let body = self.block();
+        self.loops -= 1;
+        body
+    }
+
+    /// `{ statements }`
+    fn block(&mut self) -> Result<Vec<Stmt>, ParseError> {
+        self.expect(&TokenType::LeftBrace)?;
+        let mut statements = Vec::new();
+        self.depth += 1;
+        self.skip_terminators();
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            statements.push(self.statement_or_error());
+            self.skip_terminators();
+        }
+        self.depth -= 1;
+        self.expect(&TokenType::RightBrace)?;
+        Ok(statements)
+    }
+
+    // ================================
+    // Types
+    // ================================
+
+    /// `num`, `coop 'text'`, `barn_map 'text':'num'`, `Cow`
+    fn type_expr(&mut self) -> Result<TypeExpr, ParseError> {
+        match self.simple_type()? {
+            TypeExpr::Coop(_) if self.check(&TokenType::Quote) => {
+                let element = self.quoted_type()?;
+                Ok(TypeExpr::Coop(Some(Box::new(element))))
+            }
+            TypeExpr::BarnMap(_) if self.check(&TokenType::Quote) => {
+                let key = self.quoted_type()?;
+                self.expect(&TokenType::Colon)?;
+                let value = self.quoted_type()?;
+                Ok(TypeExpr::BarnMap(Some((Box::new(key), Box::new(value)))))
+            }
+            ty => Ok(ty),
+        }
+    }
+
+    /// `'text'`
+    fn quoted_type(&mut self) -> Result<TypeExpr, ParseError> {
+        self.expect(&TokenType::Quote)?;
+        let ty = self.simple_type()?;
+        self.expect(&TokenType::Quote)?;
+        Ok(ty)
+    }
+
+    fn simple_type(&mut self) -> Result<TypeExpr, ParseError> {
+        let ty = match self.peek_type() {
+            TokenType::Text => TypeExpr::Text,
+            TokenType::Num => TypeExpr::Num,
+            TokenType::Dec => TypeExpr::Dec,
+            TokenType::Boolean => TypeExpr::Boolean,
+            TokenType::Coop => TypeExpr::Coop(None),
+            TokenType::BarnMap => TypeExpr::BarnMap(None),
+            TokenType::Barn => TypeExpr::Range,
+            TokenType::Identifier => TypeExpr::Stall(unicode::nfc(&self.peek().lexeme)),
+            _ => return Err(self.unexpected(TYPE_KEYWORDS)),
+        };
+        self.advance();
+        Ok(ty)
+    }
+
+    // ================================
+    // Expressions (Pratt)
+    // ================================
+
+    pub fn expression(&mut self) -> Result<Expr, ParseError> {
+        self.expression_bp(0)
+    }
+
+    fn expression_bp(&mut self, min_bp: u8) -> Result<Expr, ParseError> {
+        let mut left = self.prefix()?;
+        let mut last_non_associative: Option<u8> = None;
+
+        loop {
+            let token_type = self.peek_type();
+
+            if let Some(info) = precedence::lookup(&token_type, Fixity::Postfix) {
+                let (left_bp, _) = info.binding_power();
+                if left_bp < min_bp {
+                    break;
+                }
+                left = self.postfix(left)?;
+                continue;
+            }
+
+            let Some(info) = precedence::lookup(&token_type, Fixity::Infix) else {
+                break;
+            };
+            let (left_bp, right_bp) = info.binding_power();
+            if left_bp < min_bp {
+                break;
+            }
+            if info.associativity == Associativity::NonAssociative {
+                if last_non_associative == Some(info.level) {
+                    return Err(ParseError::ChainedComparison { span: self.peek().span() });
+                }
+                last_non_associative = Some(info.level);
+            }
+
+            self.advance();
+            let right = self.expression_bp(right_bp)?;
+            let span = left.span.to(right.span);
+            left = Expr::new(
+                ExprKind::Binary { op: binary_op(&token_type), left: Box::new(left), right: Box::new(right) },
+                span,
+            );
+        }
+        Ok(left)
+    }
+
+    fn prefix(&mut self) -> Result<Expr, ParseError> {
+        // A token that cannot start an expression is left for recovery
+        let operator = precedence::lookup(&self.peek_type(), Fixity::Prefix);
+        if operator.is_none() && !EXPRESSION_START.contains(&self.peek_type()) {
+            return Err(self.unexpected(EXPRESSION_START));
+        }
+        let token = self.advance();
+        let span = token.span();
+        let kind = match token.token_type.canonical() {
+            TokenType::IntegerLiteral | TokenType::DecimalLiteral | TokenType::BooleanLiteral => {
+                match token.literal {
+                    Some(Literal::Integer(value)) => ExprKind::Integer(value),
+                    Some(Literal::Decimal(value)) => ExprKind::Decimal(value),
+                    Some(Literal::Boolean(value)) => ExprKind::Boolean(value),
+                    Some(Literal::Builtin(_)) | None => {
+                        return Err(self.unexpected_at(&token, std::slice::from_ref(&token.token_type)))
+                    }
+                }
+            }
+            TokenType::StringLiteral => ExprKind::Text(unescape(&token.lexeme)),
+            TokenType::Identifier => ExprKind::Variable(unicode::nfc(&token.lexeme)),
+            TokenType::SelfKw => ExprKind::Variable(SELF.to_string()),
+            TokenType::LeftParen => {
+                let inner = self.expression()?;
+                self.expect(&TokenType::RightParen)?;
+                ExprKind::Grouping(Box::new(inner))
+            }
+            TokenType::LeftBracket => {
+                let elements = self.comma_separated(&TokenType::RightBracket, Self::expression)?;
+                ExprKind::CoopLiteral(elements)
+            }
+            TokenType::LeftBrace => {
+                let entries = self.comma_separated(&TokenType::RightBrace, |parser| {
+                    let key = parser.expression()?;
+                    parser.expect(&TokenType::Colon)?;
+                    Ok((key, parser.expression()?))
+                })?;
+                ExprKind::BarnMapLiteral(entries)
+            }
+            TokenType::Barn => {
+                self.expect(&TokenType::LeftParen)?;
+                ExprKind::Range(self.comma_separated(&TokenType::RightParen, Self::expression)?)
+            }
+            token_type => {
+                let Some(info) = operator else {
+                    return Err(self.unexpected_at(&token, EXPRESSION_START));
+                };
+                let (_, right_bp) = info.binding_power();
+                let operand = Box::new(self.expression_bp(right_bp)?);
+                match token_type {
+                    TokenType::Minus => ExprKind::Unary { op: UnaryOp::Negate, operand },
+                    TokenType::Increment => ExprKind::Update { op: UpdateOp::Increment, prefix: true, target: operand },
+                    _ => ExprKind::Update { op: UpdateOp::Decrement, prefix: true, target: operand },
+                }
+            }
+        };
+        Ok(Expr::new(kind, span.to(self.previous_span())))
+    }
+
+    /// Calls, indexing, member access and postfix `++` / `--`
+    fn postfix(&mut self, left: Expr) -> Result<Expr, ParseError> {
+        // The name a call target ends with, e.g. `largo` in `largo(t)`
+        let callee_name = self.tokens[self.current.saturating_sub(1)].clone();
+        let token = self.advance();
+        let kind = match token.token_type {
+            TokenType::LeftParen => {
+                let args = self.comma_separated(&TokenType::RightParen, Self::expression)?;
+                let builtin = match left.kind {
+                    ExprKind::Variable(_) | ExprKind::Member { .. } => callee_name.builtin(),
+                    _ => None,
+                };
+                ExprKind::Call { callee: Box::new(left.clone()), args, builtin }
+            }
+            TokenType::LeftBracket => {
+                let index = self.expression()?;
+                self.expect(&TokenType::RightBracket)?;
+                ExprKind::Index { object: Box::new(left.clone()), index: Box::new(index) }
+            }
+            TokenType::Dot => {
+                let name = self.identifier()?;
+                ExprKind::Member { object: Box::new(left.clone()), name }
+            }
+            TokenType::Increment => ExprKind::Update { op: UpdateOp::Increment, prefix: false, target: Box::new(left.clone()) },
+            _ => ExprKind::Update { op: UpdateOp::Decrement, prefix: false, target: Box::new(left.clone()) },
+        };
+        Ok(Expr::new(kind, left.span.to(self.previous_span())))
+    }
+
+    /// Items up to and including `close`, separated by commas; a trailing
+    /// comma is allowed
+    fn comma_separated<T>(
+        &mut self,
+        close: &TokenType,
+        mut item: impl FnMut(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<Vec<T>, ParseError> {
+        let mut items = Vec::new();
+        while !self.check(close) {
+            items.push(item(self)?);
+            if !self.matches(&TokenType::Comma) {
+                break;
+            }
+        }
+        self.expect(close)?;
+        Ok(items)
+    }
+
+    // ================================
+    // Token helpers
+    // ================================
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.current.min(self.tokens.len() - 1)]
+    }
+
+    fn peek_type(&self) -> TokenType {
+        self.peek().token_type.canonical()
+    }
+
+    fn previous_span(&self) -> Span {
+        self.tokens[self.current.saturating_sub(1)].span()
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.peek().clone();
+        if !self.is_at_end() {
+            self.current += 1;
+        }
+        token
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.peek().token_type == TokenType::EOF
+    }
+
+    fn check(&self, token_type: &TokenType) -> bool {
+        self.peek_type() == *token_type
+    }
+
+    /// Whether the token after the current one is `token_type`
+    fn check_next(&self, token_type: &TokenType) -> bool {
+        self.tokens.get(self.current + 1).is_some_and(|token| token.token_type.canonical() == *token_type)
+    }
+
+    fn matches(&mut self, token_type: &TokenType) -> bool {
+        if self.check(token_type) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token_type: &TokenType) -> Result<Token, ParseError> {
+        if self.check(token_type) {
+            Ok(self.advance())
+        } else {
+            Err(self.unexpected(std::slice::from_ref(token_type)))
+        }
+    }
+
+    fn identifier(&mut self) -> Result<String, ParseError> {
+        if self.check(&TokenType::Identifier) {
+            Ok(unicode::nfc(&self.advance().lexeme))
+        } else {
+            Err(self.unexpected(&[TokenType::Identifier]))
+        }
+    }
+
+    fn at_statement_end(&self) -> bool {
+        matches!(
+            self.peek_type(),
+            TokenType::Newline | TokenType::Semicolon | TokenType::RightBrace | TokenType::EOF
+        )
+    }
+
+    fn skip_terminators(&mut self) {
+        while matches!(self.peek_type(), TokenType::Newline | TokenType::Semicolon) {
+            self.advance();
+        }
+    }
+
+    fn unexpected(&self, expected: &[TokenType]) -> ParseError {
+        self.unexpected_at(self.peek(), expected)
+    }
+
+    fn unexpected_at(&self, token: &Token, expected: &[TokenType]) -> ParseError {
+        ParseError::UnexpectedToken {
+            expected: expected.to_vec(),
+            found: describe_token(token),
+            span: token.span(),
+        }
+    }
+}
+
+fn is_type_keyword(token_type: &TokenType) -> bool {
+    matches!(
+        token_type,
+        TokenType::Text | TokenType::Num | TokenType::Dec | TokenType::Boolean | TokenType::Coop | TokenType::BarnMap
+    )
+}
+
+/// Tokens that begin a statement and are safe places to resume after an error
+fn starts_statement(token_type: &TokenType) -> bool {
+    is_type_keyword(token_type)
+        || matches!(
+            token_type,
+            TokenType::Moo
+                | TokenType::If
+                | TokenType::While
+                | TokenType::For
+                | TokenType::Farmfunction
+                | TokenType::Stall
+                | TokenType::Return
+                | TokenType::Bring
+                | TokenType::Leave
+                | TokenType::Skip
+        )
+}
+
+fn is_assignable(expr: &Expr) -> bool {
+    matches!(expr.kind, ExprKind::Variable(_) | ExprKind::Index { .. } | ExprKind::Member { .. })
+}
+
+/// The operator of a compound assignment, e.g. `Add` for `+=`
+fn compound_op(token_type: &TokenType) -> Option<BinaryOp> {
+    match token_type {
+        TokenType::PlusEquals => Some(BinaryOp::Add),
+        TokenType::MinusEquals => Some(BinaryOp::Subtract),
+        TokenType::MultiplyEquals => Some(BinaryOp::Multiply),
+        TokenType::DivideEquals => Some(BinaryOp::Divide),
+        TokenType::IntegerDivideEquals => Some(BinaryOp::IntegerDivide),
+        TokenType::ModuloEquals => Some(BinaryOp::Modulo),
+        _ => None,
+    }
+}
+
+fn binary_op(token_type: &TokenType) -> BinaryOp {
+    match token_type {
+        TokenType::Plus => BinaryOp::Add,
+        TokenType::Minus => BinaryOp::Subtract,
+        TokenType::Multiply => BinaryOp::Multiply,
+        TokenType::Divide => BinaryOp::Divide,
+        TokenType::IntegerDivide => BinaryOp::IntegerDivide,
+        TokenType::Modulo => BinaryOp::Modulo,
+        TokenType::LessThan => BinaryOp::Less,
+        TokenType::GreaterThan => BinaryOp::Greater,
+        TokenType::Is => BinaryOp::Is,
+        _ => BinaryOp::IsNot,
+    }
+}
+
+/// How a token is named in error messages
+pub fn describe_token(token: &Token) -> String {
+    match token.token_type {
+        TokenType::EOF => "end of file".to_string(),
+        TokenType::Newline if token.lexeme.is_empty() => "end of block".to_string(),
+        TokenType::Newline => "end of line".to_string(),
+        _ => format!("`{}`", token.lexeme),
+    }
+}
+
+/// Strip the quotes from a string literal and resolve `\` escapes
+fn unescape(lexeme: &str) -> String {
+    let inner = &lexeme[1..lexeme.len() - 1];
+    let mut text = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => text.push('\n'),
+            Some('t') => text.push('\t'),
+            Some(other) => text.push(other),
+            None => {}
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::toml_config::language_config::MooConfig;
+
+    fn parse(source: &str, language: &str) -> Program {
+        let manager = LanguageKeywordManager::from_config(MooConfig::from_default());
+        parse_source(source, &manager, language).unwrap()
+    }
+
+    fn parse_expr(source: &str) -> Expr {
+        match parse(source, "en").statements.remove(0).kind {
+            StmtKind::Expression(expr) => expr,
+            other => panic!("expected expression, got {:?}", other),
+        }
+    }
+
+    /// Fully parenthesised rendering, to check grouping
+    fn show(expr: &Expr) -> String {
+        match &expr.kind {
+            ExprKind::Integer(value) => value.to_string(),
+            ExprKind::Variable(name) => name.clone(),
+            ExprKind::Grouping(inner) => show(inner),
+            ExprKind::Unary { operand, .. } => format!("(-{})", show(operand)),
+            ExprKind::Binary { op, left, right } => format!("({} {} {})", show(left), op.symbol(), show(right)),
+            ExprKind::Update { op, prefix: true, target } => format!("({}{})", op.symbol(), show(target)),
+            ExprKind::Update { op, prefix: false, target } => format!("({}{})", show(target), op.symbol()),
+            ExprKind::Call { callee, args, .. } => {
+                let args: Vec<String> = args.iter().map(show).collect();
+                format!("{}({})", show(callee), args.join(", "))
+            }
+            ExprKind::Index { object, index } => format!("{}[{}]", show(object), show(index)),
+            ExprKind::Member { object, name } => format!("{}.{}", show(object), name),
+            other => format!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_precedence_and_associativity() {
+        assert_eq!(show(&parse_expr("1 + 2 * 3 - 4")), "((1 + (2 * 3)) - 4)");
+        assert_eq!(show(&parse_expr("a - b - c // d % e")), "((a - b) - ((c // d) % e))");
+        assert_eq!(show(&parse_expr("-a * b")), "((-a) * b)");
+        assert_eq!(show(&parse_expr("a + 1 < b is c")), "(((a + 1) < b) is c)");
+        assert_eq!(show(&parse_expr("x is not (y + 1)")), "(x is not (y + 1))");
+    }
+
+    #[test]
+    fn test_postfix_operators() {
+        assert_eq!(show(&parse_expr("animals[index]")), "animals[index]");
+        assert_eq!(show(&parse_expr("animals.collect() + 1")), "(animals.collect() + 1)");
+        assert_eq!(show(&parse_expr("details.get(animal).get(\"age\")")), "details.get(animal).get(Text(\"age\"))");
+        assert_eq!(show(&parse_expr("-count++")), "(-(count++))");
+        assert_eq!(show(&parse_expr("++count")), "(++count)");
+    }
+
+    #[test]
+    fn test_chained_comparison_is_rejected() {
+        let manager = LanguageKeywordManager::from_config(MooConfig::from_default());
+        let errors = parse_source("a < b < c", &manager, "en").unwrap_err();
+        assert!(matches!(errors[..], [ParseError::ChainedComparison { .. }]));
+    }
+
+    #[test]
+    fn test_missing_expression_at_end_of_file() {
+        let manager = LanguageKeywordManager::from_config(MooConfig::from_default());
+        let errors = parse_source("moo (", &manager, "en").unwrap_err();
+        assert_eq!(errors[0].to_string(), "Expected an expression but found end of file at 1:6");
+
+        let error = Parser::new(vec![Token::new(TokenType::EOF, "", 1, 1)]).expression().unwrap_err();
+        assert_eq!(error.to_string(), "Expected an expression but found end of file at 1:1");
+    }
+
+    fn parse_recovering(source: &str) -> (Program, Vec<ParseError>) {
+        let manager = LanguageKeywordManager::from_config(MooConfig::from_default());
+        let tokens = Lexer::new(source, &manager, "en").unwrap().tokenize().unwrap();
+        let (tokens, _) = terminate_statements(&tokens);
+        Parser::new(tokens).parse_with_recovery()
+    }
+
+    #[test]
+    fn test_reports_every_syntax_error() {
+        let source = "num = 5
+moo \"ok\"
+count = (1 +
+farmfunction f(num a {
+    moo a
+}
+moo \"still parsed\"
+";
+        let (program, errors) = parse_recovering(source);
+
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        assert_eq!(errors[0].span().unwrap().line, 1);
+        assert_eq!(errors[1].span().unwrap().line, 4);
+        assert_eq!(errors[2].span().unwrap().line, 4);
+
+        let kinds: Vec<bool> = program.statements.iter().map(|s| s.kind == StmtKind::Error).collect();
+        assert_eq!(kinds, vec![true, false, true, true, false]);
+    }
+
+    #[test]
+    fn test_broken_condition_keeps_body() {
+        let (program, errors) = parse_recovering("while (count <) {
+    moo count
+}
+");
+        assert_eq!(errors.len(), 1);
+        match &program.statements[0].kind {
+            StmtKind::While { condition, body } => {
+                assert_eq!(condition.kind, ExprKind::Error);
+                assert_eq!(body.len(), 1);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_expected_token_lists() {
+        let (_, errors) = parse_recovering("coop 'text animals\nmoo (1 + 2\nnum x = )");
+        let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(messages, vec![
+            "Expected `'` but found `animals` at 1:12",
+            // Line breaks inside parentheses do not end the statement
+            "Expected `)` but found `num` at 3:1",
+            "Expected an expression but found `)` at 3:9",
+        ]);
+    }
+
+    #[test]
+    fn test_bring_only_at_top_level() {
+        let program = parse("traer \"herramientas.moo\"", "es");
+        assert_eq!(program.statements[0].kind, StmtKind::Bring { path: "herramientas.moo".to_string() });
+
+        let (_, errors) = parse_recovering("if yes {\n    bring \"tools.moo\"\n}");
+        let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(messages, vec!["`bring` at 2:5 must be at the top level of a file"]);
+    }
+
+    #[test]
+    fn test_leave_and_skip_only_in_loops() {
+        let program = parse("mientras verdadero {\n    salir\n}", "es");
+        let StmtKind::While { body, .. } = &program.statements[0].kind else { panic!("expected a loop") };
+        assert_eq!(body[0].kind, StmtKind::Break);
+
+        // A function inside a loop starts outside any loop
+        let (_, errors) = parse_recovering("skip\nfor i in barn(3) {\n    farmfunction f() {\n        leave\n    }\n    skip\n}");
+        let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(messages, vec![
+            "`skip` at 1:1 must be inside a loop",
+            "`leave` at 4:9 must be inside a loop",
+        ]);
+    }
+
+    #[test]
+    fn test_compound_and_multiple_assignment() {
+        let program = parse("counts[i] += 1\na, b =\n    b, a", "en");
+        assert!(matches!(&program.statements[0].kind, StmtKind::CompoundAssign { op: BinaryOp::Add, .. }));
+        let StmtKind::MultiAssign { targets, values } = &program.statements[1].kind else { panic!("expected a swap") };
+        assert_eq!((targets.len(), values.len()), (2, 2));
+
+        let (_, errors) = parse_recovering("a, b = 1\na, f() = 1, 2\nf() -= 1");
+        let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(messages, vec![
+            "Cannot assign 1 value(s) to 2 target(s) at 1:1; give one value per target",
+            "Invalid assignment target at 2:4; only variables, indexes and members can be assigned",
+            "Invalid assignment target at 3:1; only variables, indexes and members can be assigned",
+        ]);
+    }
+
+    #[test]
+    fn test_statements() {
+        let program = parse(
+            "farmfunction 'num' calculateTotal(num a, coop 'num' b) {\n    return a + b[0]\n}\n\
+             barn_map 'text':'num' inventory = {\"cow\": 5}\n\
+             for index in barn(10) { moo \"Index:\", index }\n\
+             if (x is 1) {\n} else if (x is 2) {\n} else {\n    x = 3\n}",
+            "en",
+        );
+        assert_eq!(program.statements.len(), 4);
+        match &program.statements[0].kind {
+            StmtKind::Function(decl) => {
+                assert_eq!(decl.return_type, Some(TypeExpr::Num));
+                assert_eq!(decl.params[1].ty, TypeExpr::Coop(Some(Box::new(TypeExpr::Num))));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        match &program.statements[3].kind {
+            StmtKind::If { branches, else_branch } => {
+                assert_eq!(branches.len(), 2);
+                assert!(matches!(else_branch.as_deref(), Some([Stmt { kind: StmtKind::Assign { .. }, .. }])));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_stall_declaration() {
+        let program = parse(
+            "establo Vaca {\n    texto nombre\n    numero edad = 1\n    funciongranja Vaca(texto n) {\n        este.nombre = n\n    }\n}\nVaca v = Vaca(\"Daisy\")",
+            "es",
+        );
+        let StmtKind::Stall(stall) = &program.statements[0].kind else {
+            panic!("unexpected {:?}", program.statements[0].kind);
+        };
+        assert_eq!(stall.name, "Vaca");
+        assert_eq!(stall.fields.iter().map(|field| field.name.as_str()).collect::<Vec<_>>(), ["nombre", "edad"]);
+        let constructor = stall.constructor().unwrap();
+        assert_eq!(stall.methods().count(), 0);
+        match &constructor.body[0].kind {
+            StmtKind::Assign { target, .. } => {
+                let ExprKind::Member { object, name } = &target.kind else { panic!("unexpected {:?}", target) };
+                assert_eq!(object.kind, ExprKind::Variable(SELF.to_string()));
+                assert_eq!(name, "nombre");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(matches!(
+            &program.statements[1].kind,
+            StmtKind::VarDecl { ty: TypeExpr::Stall(name), .. } if name == "Vaca"
+        ));
+    }
+
+    #[test]
+    fn test_spanish_matches_english() {
+        let english = parse("num total = 0\nfor n in barn(3) {\n    total = total + n\n}", "en");
+        let spanish = parse("numero total = 0\npara n en granero(3) {\n    total = total + n\n}", "es");
+        // Same tree apart from the columns, which shift with keyword length
+        let strip_spans = |program: Program| {
+            let debug = format!("{:?}", program.statements);
+            debug.split("span: Span {")
+                .map(|part| part.split_once('}').map_or(part, |(_, rest)| rest))
+                .collect::<String>()
+        };
+        assert_eq!(strip_spans(english), strip_spans(spanish));
+    }
+
+    #[test]
+    fn test_documentation_examples_parse() {
+        let source = include_str!("../lexer/english_testing.moo");
+        assert!(parse(source, "en").statements.len() > 20);
+        let source = include_str!("../lexer/spanish_testing.muu");
+        assert!(parse(source, "es").statements.len() > 20);
+    }
+}
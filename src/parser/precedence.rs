@@ -0,0 +1,125 @@
+//! Operator precedence and associativity for MooLang expressions.
+//!
+//! The table is plain data so it can be printed for a class
+//! (`precedence_table_text`, shown by `--help`) and is the single source of
+//! truth for the Pratt parser's binding powers. Higher levels bind tighter.
+
+use std::fmt::Write;
+use crate::utils::tokens::TokenType;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Associativity {
+    Left,
+    Right,
+    /// `a < b < c` is rejected; students must add parentheses
+    NonAssociative,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fixity {
+    Prefix,
+    Infix,
+    Postfix,
+}
+
+/// One row of the precedence table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OperatorInfo {
+    pub token: TokenType,
+    pub symbol: &'static str,
+    pub fixity: Fixity,
+    pub level: u8,
+    pub associativity: Associativity,
+    pub description: &'static str,
+}
+
+const fn op(
+    token: TokenType,
+    symbol: &'static str,
+    fixity: Fixity,
+    level: u8,
+    associativity: Associativity,
+    description: &'static str,
+) -> OperatorInfo {
+    OperatorInfo { token, symbol, fixity, level, associativity, description }
+}
+
+/// Every operator, lowest precedence first. Tokens are canonical (English);
+/// `es` / `no es` share the rows of `is` / `is not`.
+pub const PRECEDENCE_TABLE: &[OperatorInfo] = &[
+    op(TokenType::Is, "is", Fixity::Infix, 1, Associativity::NonAssociative, "equality"),
+    op(TokenType::IsNot, "is not", Fixity::Infix, 1, Associativity::NonAssociative, "inequality"),
+    op(TokenType::LessThan, "<", Fixity::Infix, 2, Associativity::NonAssociative, "less than"),
+    op(TokenType::GreaterThan, ">", Fixity::Infix, 2, Associativity::NonAssociative, "greater than"),
+    op(TokenType::Plus, "+", Fixity::Infix, 3, Associativity::Left, "addition"),
+    op(TokenType::Minus, "-", Fixity::Infix, 3, Associativity::Left, "subtraction"),
+    op(TokenType::Multiply, "*", Fixity::Infix, 4, Associativity::Left, "multiplication"),
+    op(TokenType::Divide, "/", Fixity::Infix, 4, Associativity::Left, "division"),
+    op(TokenType::IntegerDivide, "//", Fixity::Infix, 4, Associativity::Left, "integer division"),
+    op(TokenType::Modulo, "%", Fixity::Infix, 4, Associativity::Left, "remainder"),
+    op(TokenType::Minus, "-x", Fixity::Prefix, 5, Associativity::Right, "negation"),
+    op(TokenType::Increment, "++x", Fixity::Prefix, 5, Associativity::Right, "pre-increment"),
+    op(TokenType::Decrement, "--x", Fixity::Prefix, 5, Associativity::Right, "pre-decrement"),
+    op(TokenType::Increment, "x++", Fixity::Postfix, 6, Associativity::Left, "post-increment"),
+    op(TokenType::Decrement, "x--", Fixity::Postfix, 6, Associativity::Left, "post-decrement"),
+    op(TokenType::LeftParen, "f(...)", Fixity::Postfix, 6, Associativity::Left, "call"),
+    op(TokenType::LeftBracket, "x[...]", Fixity::Postfix, 6, Associativity::Left, "index"),
+    op(TokenType::Dot, "x.name", Fixity::Postfix, 6, Associativity::Left, "member access"),
+];
+
+/// Look up an operator by (canonical) token and fixity
+pub fn lookup(token: &TokenType, fixity: Fixity) -> Option<&'static OperatorInfo> {
+    let token = token.canonical();
+    PRECEDENCE_TABLE.iter().find(|info| info.token == token && info.fixity == fixity)
+}
+
+impl OperatorInfo {
+    /// Pratt binding powers `(left, right)`. Left-associative operators bind
+    /// tighter on the right so `a - b - c` groups as `(a - b) - c`.
+    pub fn binding_power(&self) -> (u8, u8) {
+        let base = self.level * 2;
+        match self.associativity {
+            Associativity::Left | Associativity::NonAssociative => (base, base + 1),
+            Associativity::Right => (base + 1, base),
+        }
+    }
+}
+
+/// The precedence table as aligned text, highest precedence first
+pub fn precedence_table_text() -> String {
+    let mut text = String::new();
+    let _ = writeln!(text, "{:<6} {:<8} {:<8} {:<16} Meaning", "Level", "Operator", "Kind", "Associativity");
+    for info in PRECEDENCE_TABLE.iter().rev() {
+        let fixity = match info.fixity {
+            Fixity::Prefix => "prefix",
+            Fixity::Infix => "infix",
+            Fixity::Postfix => "postfix",
+        };
+        let associativity = match info.associativity {
+            Associativity::Left => "left",
+            Associativity::Right => "right",
+            Associativity::NonAssociative => "none",
+        };
+        let _ = writeln!(
+            text,
+            "{:<6} {:<8} {:<8} {:<16} {}",
+            info.level, info.symbol, fixity, associativity, info.description
+        );
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_precedence_table_text() {
+        let text = precedence_table_text();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), PRECEDENCE_TABLE.len() + 1);
+        assert_eq!(lines[0], "Level  Operator Kind     Associativity    Meaning");
+        assert_eq!(lines[1], "6      x.name   postfix  left             member access");
+        assert_eq!(lines.last(), Some(&"1      is       infix    none             equality"));
+    }
+}
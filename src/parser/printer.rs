@@ -0,0 +1,272 @@
+//! Indented tree view of the AST, for `--dump-ast`.
+//!
+//! Each node is one line: its kind, any inline details (names, operators,
+//! literal values) and the source position. Children are indented two spaces
+//! under their parent; labelled groups such as `condition` and `body` keep
+//! branches readable. Keyword spellings are English whatever the source
+//! language, since the AST is language-neutral.
+
+use std::fmt::Write;
+
+use crate::parser::ast::*;
+use crate::utils::tokens::Span;
+
+/// Render `program` as an indented tree
+pub fn ast_tree(program: &Program) -> String {
+    let mut printer = TreePrinter { text: String::new(), depth: 0 };
+    printer.line("Program", None);
+    printer.nested(|p| p.statements(&program.statements));
+    printer.text
+}
+
+struct TreePrinter {
+    text: String,
+    depth: usize,
+}
+
+impl TreePrinter {
+    fn line(&mut self, label: &str, span: Option<Span>) {
+        let indent = "  ".repeat(self.depth);
+        let _ = match span {
+            Some(span) => writeln!(self.text, "{}{} @{}", indent, label, span),
+            None => writeln!(self.text, "{}{}", indent, label),
+        };
+    }
+
+    fn nested(&mut self, body: impl FnOnce(&mut Self)) {
+        self.depth += 1;
+        body(self);
+        self.depth -= 1;
+    }
+
+    /// A label line with its children indented below it
+    fn group(&mut self, label: &str, body: impl FnOnce(&mut Self)) {
+        self.line(label, None);
+        self.nested(body);
+    }
+
+    fn statements(&mut self, statements: &[Stmt]) {
+        for stmt in statements {
+            self.statement(stmt);
+        }
+    }
+
+    fn statement(&mut self, stmt: &Stmt) {
+        let span = Some(stmt.span);
+        match &stmt.kind {
+            StmtKind::VarDecl { ty, name, initializer } => {
+                self.line(&format!("VarDecl {} {}", ty, name), span);
+                if let Some(initializer) = initializer {
+                    self.nested(|p| p.expression(initializer));
+                }
+            }
+            StmtKind::Assign { target, value } => {
+                self.line("Assign", span);
+                self.nested(|p| {
+                    p.group("target", |p| p.expression(target));
+                    p.group("value", |p| p.expression(value));
+                });
+            }
+            StmtKind::CompoundAssign { target, op, value } => {
+                self.line(&format!("Assign {}=", op.symbol()), span);
+                self.nested(|p| {
+                    p.group("target", |p| p.expression(target));
+                    p.group("value", |p| p.expression(value));
+                });
+            }
+            StmtKind::MultiAssign { targets, values } => {
+                self.line("Assign", span);
+                self.nested(|p| {
+                    p.group("targets", |p| targets.iter().for_each(|target| p.expression(target)));
+                    p.group("values", |p| values.iter().for_each(|value| p.expression(value)));
+                });
+            }
+            StmtKind::Print { values } => {
+                self.line("Print", span);
+                self.nested(|p| values.iter().for_each(|value| p.expression(value)));
+            }
+            StmtKind::If { branches, else_branch } => {
+                self.line("If", span);
+                self.nested(|p| {
+                    for (condition, body) in branches {
+                        p.group("condition", |p| p.expression(condition));
+                        p.group("then", |p| p.statements(body));
+                    }
+                    if let Some(body) = else_branch {
+                        p.group("else", |p| p.statements(body));
+                    }
+                });
+            }
+            StmtKind::While { condition, body } => {
+                self.line("While", span);
+                self.nested(|p| {
+                    p.group("condition", |p| p.expression(condition));
+                    p.group("body", |p| p.statements(body));
+                });
+            }
+            StmtKind::For { variable, value, iterable, body } => {
+                match value {
+                    Some(value) => self.line(&format!("For {}, {}", variable, value), span),
+                    None => self.line(&format!("For {}", variable), span),
+                }
+                self.nested(|p| {
+                    p.group("in", |p| p.expression(iterable));
+                    p.group("body", |p| p.statements(body));
+                });
+            }
+            StmtKind::Function(function) => self.function(function, span),
+            StmtKind::Stall(stall) => {
+                self.line(&format!("Stall {}", stall.name), span);
+                self.nested(|p| {
+                    for field in &stall.fields {
+                        p.line(&format!("Field {} {}", field.ty, field.name), Some(field.span));
+                        if let Some(initializer) = &field.initializer {
+                            p.nested(|p| p.expression(initializer));
+                        }
+                    }
+                    for function in &stall.functions {
+                        p.function(function, Some(function.span));
+                    }
+                });
+            }
+            StmtKind::Return(value) => {
+                self.line("Return", span);
+                if let Some(value) = value {
+                    self.nested(|p| p.expression(value));
+                }
+            }
+            StmtKind::Expression(expr) => {
+                self.line("Expression", span);
+                self.nested(|p| p.expression(expr));
+            }
+            StmtKind::Break => self.line("Break", span),
+            StmtKind::Continue => self.line("Continue", span),
+            StmtKind::Bring { path } => self.line(&format!("Bring {:?}", path), span),
+            StmtKind::Error => self.line("Error", span),
+        }
+    }
+
+    fn function(&mut self, function: &FunctionDecl, span: Option<Span>) {
+        let params: Vec<String> = function.params.iter()
+            .map(|param| format!("{} {}", param.ty, param.name))
+            .collect();
+        let returns = function.return_type.as_ref()
+            .map(|ty| format!(" -> {}", ty))
+            .unwrap_or_default();
+        self.line(&format!("Function {}({}){}", function.name, params.join(", "), returns), span);
+        self.nested(|p| p.statements(&function.body));
+    }
+
+    fn expression(&mut self, expr: &Expr) {
+        let span = Some(expr.span);
+        match &expr.kind {
+            ExprKind::Integer(value) => self.line(&format!("Integer {}", value), span),
+            ExprKind::Decimal(value) => self.line(&format!("Decimal {:?}", value), span),
+            ExprKind::Text(value) => self.line(&format!("Text {:?}", value), span),
+            ExprKind::Boolean(value) => self.line(&format!("Boolean {}", value), span),
+            ExprKind::Variable(name) => self.line(&format!("Variable {}", name), span),
+            ExprKind::CoopLiteral(elements) => {
+                self.line("Coop", span);
+                self.nested(|p| elements.iter().for_each(|element| p.expression(element)));
+            }
+            ExprKind::BarnMapLiteral(entries) => {
+                self.line("BarnMap", span);
+                self.nested(|p| {
+                    for (key, value) in entries {
+                        p.group("entry", |p| {
+                            p.expression(key);
+                            p.expression(value);
+                        });
+                    }
+                });
+            }
+            ExprKind::Range(args) => {
+                self.line("Range", span);
+                self.nested(|p| args.iter().for_each(|arg| p.expression(arg)));
+            }
+            ExprKind::Grouping(inner) => {
+                self.line("Grouping", span);
+                self.nested(|p| p.expression(inner));
+            }
+            ExprKind::Unary { op: UnaryOp::Negate, operand } => {
+                self.line("Unary -", span);
+                self.nested(|p| p.expression(operand));
+            }
+            ExprKind::Binary { op, left, right } => {
+                self.line(&format!("Binary {}", op.symbol()), span);
+                self.nested(|p| {
+                    p.expression(left);
+                    p.expression(right);
+                });
+            }
+            ExprKind::Update { op, prefix, target } => {
+                let label = if *prefix {
+                    format!("Update {}x", op.symbol())
+                } else {
+                    format!("Update x{}", op.symbol())
+                };
+                self.line(&label, span);
+                self.nested(|p| p.expression(target));
+            }
+            ExprKind::Call { callee, args, .. } => {
+                self.line("Call", span);
+                self.nested(|p| {
+                    p.expression(callee);
+                    if !args.is_empty() {
+                        p.group("args", |p| args.iter().for_each(|arg| p.expression(arg)));
+                    }
+                });
+            }
+            ExprKind::Index { object, index } => {
+                self.line("Index", span);
+                self.nested(|p| {
+                    p.expression(object);
+                    p.expression(index);
+                });
+            }
+            ExprKind::Member { object, name } => {
+                self.line(&format!("Member .{}", name), span);
+                self.nested(|p| p.expression(object));
+            }
+            ExprKind::Error => self.line("Error", span),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_source;
+    use crate::toml_config::language_config::{LanguageKeywordManager, MooConfig};
+
+    #[test]
+    fn test_ast_tree() {
+        let manager = LanguageKeywordManager::from_config(MooConfig::from_default());
+        let source = "coop 'num' counts = [1, 2]\nwhile (counts.count() < 3) {\n    counts.plant(-1)\n}\n";
+        let program = parse_source(source, &manager, "en").unwrap();
+        let expected = "\
+Program
+  VarDecl coop 'num' counts @1:1
+    Coop @1:21
+      Integer 1 @1:22
+      Integer 2 @1:25
+  While @2:1
+    condition
+      Grouping @2:7
+        Binary < @2:8
+          Call @2:8
+            Member .count @2:8
+              Variable counts @2:8
+          Integer 3 @2:25
+    body
+      Expression @3:5
+        Call @3:5
+          Member .plant @3:5
+            Variable counts @3:5
+          args
+            Unary - @3:18
+              Integer 1 @3:19
+";
+        assert_eq!(ast_tree(&program), expected);
+    }
+}
@@ -0,0 +1,16 @@
+pub mod barn_map;
+pub mod bytecode;
+pub mod codegen;
+pub mod coop;
+pub mod environment;
+pub mod errors;
+pub mod interpreter;
+pub mod numeric;
+pub mod object_file;
+mod operations;
+pub mod random;
+pub mod range;
+pub mod stall;
+pub mod stdlib;
+pub mod value;
+pub mod vm;
@@ -0,0 +1,153 @@
+//! `barn_map` values: maps that keep their entries in the order the keys
+//! were first put in.
+//!
+//! `moo`, `keys`, `values` and `for` loops all follow that order, so a
+//! program prints the same output every time it runs. Updating a key keeps
+//! its place; removing it and putting it back moves it to the end.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::runtime::value::Value;
+
+/// A key that can be looked up by hash. Keys that compare equal with `is`
+/// hash the same, so a whole `dec` uses the `num` form.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum HashKey {
+    Text(Rc<str>),
+    Num(i64),
+    Dec(u64),
+    Boolean(bool),
+}
+
+impl HashKey {
+    /// `None` for keys found by comparing entries instead: coops, maps and
+    /// functions, and `dec` NaN, which equals nothing
+    fn of(value: &Value) -> Option<HashKey> {
+        match value {
+            Value::Text(text) => Some(HashKey::Text(Rc::clone(text))),
+            Value::Num(number) => Some(HashKey::Num(*number)),
+            Value::Dec(number) if number.fract() == 0.0 && (i64::MIN as f64..i64::MAX as f64).contains(number) => {
+                Some(HashKey::Num(*number as i64))
+            }
+            Value::Dec(number) if !number.is_nan() => Some(HashKey::Dec(number.to_bits())),
+            Value::Boolean(value) => Some(HashKey::Boolean(*value)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BarnMap {
+    entries: Vec<(Value, Value)>,
+    /// Position in `entries` of each hashable key
+    positions: HashMap<HashKey, usize>,
+}
+
+impl BarnMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Entries in insertion order
+    pub fn entries(&self) -> &[(Value, Value)] {
+        &self.entries
+    }
+
+    fn position(&self, key: &Value) -> Option<usize> {
+        match HashKey::of(key) {
+            Some(hashed) => self.positions.get(&hashed).copied(),
+            None => self.entries.iter().position(|(existing, _)| existing == key),
+        }
+    }
+
+    pub fn get(&self, key: &Value) -> Option<&Value> {
+        self.position(key).map(|position| &self.entries[position].1)
+    }
+
+    pub fn contains(&self, key: &Value) -> bool {
+        self.position(key).is_some()
+    }
+
+    /// Insert or update an entry, keeping an existing key's position
+    pub fn put(&mut self, key: Value, value: Value) {
+        if let Some(position) = self.position(&key) {
+            self.entries[position].1 = value;
+            return;
+        }
+        if let Some(hashed) = HashKey::of(&key) {
+            self.positions.insert(hashed, self.entries.len());
+        }
+        self.entries.push((key, value));
+    }
+
+    /// Remove an entry, returning its value
+    pub fn remove(&mut self, key: &Value) -> Option<Value> {
+        let position = self.position(key)?;
+        let (key, value) = self.entries.remove(position);
+        if let Some(hashed) = HashKey::of(&key) {
+            self.positions.remove(&hashed);
+        }
+        for later in self.positions.values_mut().filter(|later| **later > position) {
+            *later -= 1;
+        }
+        Some(value)
+    }
+
+    pub fn keys(&self) -> Vec<Value> {
+        self.entries.iter().map(|(key, _)| key.clone()).collect()
+    }
+
+    pub fn values(&self) -> Vec<Value> {
+        self.entries.iter().map(|(_, value)| value.clone()).collect()
+    }
+}
+
+impl FromIterator<(Value, Value)> for BarnMap {
+    /// Later entries with the same key update earlier ones, as in
+    /// `{"cow": 1, "cow": 2}`
+    fn from_iter<I: IntoIterator<Item = (Value, Value)>>(entries: I) -> Self {
+        let mut map = BarnMap::new();
+        for (key, value) in entries {
+            map.put(key, value);
+        }
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insertion_order() {
+        let mut stock: BarnMap = [("pig", 2), ("cow", 5), ("hen", 9)].iter()
+            .map(|(key, value)| (Value::text(key), Value::Num(*value)))
+            .collect();
+        stock.put(Value::text("cow"), Value::Num(6));
+        assert_eq!(stock.remove(&Value::text("pig")), Some(Value::Num(2)));
+        stock.put(Value::text("pig"), Value::Num(1));
+        assert_eq!(stock.keys(), vec![Value::text("cow"), Value::text("hen"), Value::text("pig")]);
+        assert_eq!(stock.values(), vec![Value::Num(6), Value::Num(9), Value::Num(1)]);
+        assert_eq!(stock.get(&Value::text("hen")), Some(&Value::Num(9)));
+        assert_eq!(stock.remove(&Value::text("goat")), None);
+    }
+
+    #[test]
+    fn test_keys_match_like_is() {
+        let mut map = BarnMap::new();
+        map.put(Value::Num(2), Value::text("two"));
+        map.put(Value::coop(vec![Value::Num(1)]), Value::text("pen"));
+        assert_eq!(map.get(&Value::Dec(2.0)), Some(&Value::text("two")));
+        assert_eq!(map.get(&Value::coop(vec![Value::Num(1)])), Some(&Value::text("pen")));
+        assert!(!map.contains(&Value::Dec(f64::NAN)));
+    }
+}
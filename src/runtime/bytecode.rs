@@ -0,0 +1,470 @@
+//! Bytecode for the MooLang VM.
+//!
+//! Each `farmfunction`, and the top level of the program, compiles to a
+//! `FunctionProto` holding a `Chunk`: a byte string of instructions, the
+//! constants they refer to and a table mapping instructions back to source.
+//! An instruction is a one-byte `OpCode` followed by its operands, each a
+//! little-endian `u16`.
+//!
+//! The VM keeps a stack of values. Local variables live in the stack slots
+//! of their function's call, numbered from the first parameter. Variables a
+//! nested `farmfunction` uses are kept in shared cells instead, which the
+//! nested function's closure holds on to as upvalues. The farmfunctions of
+//! a stall take the instance as a hidden first parameter, `self`.
+
+use std::fmt::Write;
+use std::rc::Rc;
+
+use crate::parser::ast::{BinaryOp, TypeExpr};
+use crate::runtime::coop::ElementType;
+use crate::runtime::value::Value;
+use crate::semantic::types::{Builtin, Method};
+use crate::utils::tokens::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum OpCode {
+    /// `Constant(k)`: push constant `k`
+    Constant,
+    Nothing,
+    True,
+    False,
+    Pop,
+    /// `PopN(n)`: drop `n` values, e.g. a block's locals
+    PopN,
+    Dup,
+    /// Duplicate the top two values
+    Dup2,
+    /// `Bury(n)`: move the top value below the `n` values under it
+    Bury,
+    /// `GetLocal(slot)`
+    GetLocal,
+    /// `SetLocal(slot)`: store the top value, leaving it on the stack
+    SetLocal,
+    /// `NewCell(cell)`: give a captured variable a fresh cell
+    NewCell,
+    GetCell,
+    SetCell,
+    GetUpvalue,
+    SetUpvalue,
+    /// `DefineGlobal(global)`: pop the value of a top-level declaration
+    DefineGlobal,
+    GetGlobal,
+    SetGlobal,
+    /// Turn a `num` on top of the stack into a `dec`
+    Widen,
+    /// `CoopType(element)`: give the coop on top of the stack its declared
+    /// element type
+    CoopType,
+    Negate,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    IntegerDivide,
+    Modulo,
+    Less,
+    Greater,
+    Is,
+    IsNot,
+    Increment,
+    Decrement,
+    /// `Jump(offset)`: skip forward
+    Jump,
+    /// `JumpIfFalse(offset)`: pop a condition, skip forward if it is false
+    JumpIfFalse,
+    /// `Loop(offset)`: jump backward
+    Loop,
+    /// Replace the value to loop over with a coop of the values a `for` visits
+    Elements,
+    /// `ForIter(slot, offset)`: with the elements in `slot` and the position
+    /// in `slot + 1`, push the next element or skip forward when done
+    ForIter,
+    /// Replace a barn_map with a coop of its keys and values, alternating
+    Entries,
+    /// `ForEntry(slot, offset)`: as `ForIter`, pushing a key and its value
+    ForEntry,
+    /// `Coop(n)`: build a coop from the top `n` values
+    Coop,
+    /// `BarnMap(n)`: build a barn_map from the top `n` key/value pairs
+    BarnMap,
+    /// `Range(n)`: `barn(...)` with `n` bounds
+    Range,
+    GetIndex,
+    /// Store into `object[index]`, leaving the value on the stack
+    SetIndex,
+    /// `GetField(k)`: replace an instance with its field named by constant `k`
+    GetField,
+    /// `SetField(k)`: store into `object.name`, leaving the value on the stack
+    SetField,
+    /// `Call(argc)`: call the value below the arguments
+    Call,
+    /// `CallMethod(method, argc)`
+    CallMethod,
+    /// `CallBuiltin(builtin, argc)`
+    CallBuiltin,
+    /// `Invoke(k, argc, method)`: call the method named by constant `k` on the
+    /// value below the arguments, a stall's own method or else collection
+    /// method `method`, which is `NO_METHOD` when the name is not one
+    Invoke,
+    /// `Closure(k)`: create a function from constant `k`
+    Closure,
+    /// `Stall(k)`: create the stall described by constant `k` from its
+    /// `init` closure and its method closures, in order, on the stack
+    Stall,
+    Return,
+    /// `Print(n)`: `moo` the top `n` values
+    Print,
+}
+
+/// Every opcode, indexed by its byte
+const OPCODES: &[OpCode] = &[
+    OpCode::Constant, OpCode::Nothing, OpCode::True, OpCode::False, OpCode::Pop, OpCode::PopN,
+    OpCode::Dup, OpCode::Dup2, OpCode::Bury, OpCode::GetLocal, OpCode::SetLocal, OpCode::NewCell,
+    OpCode::GetCell, OpCode::SetCell, OpCode::GetUpvalue, OpCode::SetUpvalue, OpCode::DefineGlobal,
+    OpCode::GetGlobal, OpCode::SetGlobal, OpCode::Widen, OpCode::CoopType, OpCode::Negate, OpCode::Add, OpCode::Subtract,
+    OpCode::Multiply, OpCode::Divide, OpCode::IntegerDivide, OpCode::Modulo, OpCode::Less, OpCode::Greater,
+    OpCode::Is, OpCode::IsNot, OpCode::Increment, OpCode::Decrement, OpCode::Jump, OpCode::JumpIfFalse,
+    OpCode::Loop, OpCode::Elements, OpCode::ForIter, OpCode::Entries, OpCode::ForEntry, OpCode::Coop, OpCode::BarnMap, OpCode::Range,
+    OpCode::GetIndex, OpCode::SetIndex, OpCode::GetField, OpCode::SetField, OpCode::Call, OpCode::CallMethod,
+    OpCode::CallBuiltin, OpCode::Invoke, OpCode::Closure, OpCode::Stall, OpCode::Return, OpCode::Print,
+];
+
+/// Operand encodings for `CallMethod` and `Invoke`, indexed by operand
+pub const METHODS: &[Method] = &[
+    Method::Add, Method::Size, Method::Put, Method::Get, Method::Remove, Method::Contains, Method::RemoveAt,
+    Method::Insert, Method::IndexOf, Method::Sort, Method::Reverse, Method::Slice, Method::Keys, Method::Values,
+];
+
+/// `Invoke` method operand for a name that is no collection method
+pub const NO_METHOD: u16 = u16::MAX;
+
+/// Operand encodings for `CoopType`, indexed by operand
+pub const ELEMENT_TYPES: &[ElementType] = &[
+    ElementType::Text, ElementType::Num, ElementType::Dec, ElementType::Boolean, ElementType::Coop,
+    ElementType::BarnMap, ElementType::Range, ElementType::Stall,
+];
+
+/// Operand encodings for `CallBuiltin`, indexed by operand
+pub const BUILTINS: &[Builtin] = &[
+    Builtin::ToNum, Builtin::ToDec, Builtin::ToText, Builtin::Length, Builtin::Upper, Builtin::Split, Builtin::Join,
+    Builtin::Abs, Builtin::Min, Builtin::Max, Builtin::Sqrt, Builtin::Random, Builtin::Listen,
+];
+
+impl OpCode {
+    pub fn from_byte(byte: u8) -> Option<OpCode> {
+        OPCODES.get(byte as usize).copied()
+    }
+
+    /// Number of `u16` operands after the opcode
+    pub fn operands(&self) -> usize {
+        match self {
+            OpCode::Constant
+            | OpCode::PopN
+            | OpCode::Bury
+            | OpCode::GetLocal
+            | OpCode::SetLocal
+            | OpCode::NewCell
+            | OpCode::GetCell
+            | OpCode::SetCell
+            | OpCode::GetUpvalue
+            | OpCode::SetUpvalue
+            | OpCode::DefineGlobal
+            | OpCode::GetGlobal
+            | OpCode::SetGlobal
+            | OpCode::CoopType
+            | OpCode::Jump
+            | OpCode::JumpIfFalse
+            | OpCode::Loop
+            | OpCode::Coop
+            | OpCode::BarnMap
+            | OpCode::Range
+            | OpCode::GetField
+            | OpCode::SetField
+            | OpCode::Call
+            | OpCode::Closure
+            | OpCode::Stall
+            | OpCode::Print => 1,
+            OpCode::ForIter | OpCode::ForEntry | OpCode::CallMethod | OpCode::CallBuiltin => 2,
+            OpCode::Invoke => 3,
+            _ => 0,
+        }
+    }
+
+    pub fn binary(op: BinaryOp) -> OpCode {
+        match op {
+            BinaryOp::Add => OpCode::Add,
+            BinaryOp::Subtract => OpCode::Subtract,
+            BinaryOp::Multiply => OpCode::Multiply,
+            BinaryOp::Divide => OpCode::Divide,
+            BinaryOp::IntegerDivide => OpCode::IntegerDivide,
+            BinaryOp::Modulo => OpCode::Modulo,
+            BinaryOp::Less => OpCode::Less,
+            BinaryOp::Greater => OpCode::Greater,
+            BinaryOp::Is => OpCode::Is,
+            BinaryOp::IsNot => OpCode::IsNot,
+        }
+    }
+
+    /// The operator of a binary arithmetic or comparison opcode
+    pub fn binary_op(&self) -> Option<BinaryOp> {
+        let op = match self {
+            OpCode::Add => BinaryOp::Add,
+            OpCode::Subtract => BinaryOp::Subtract,
+            OpCode::Multiply => BinaryOp::Multiply,
+            OpCode::Divide => BinaryOp::Divide,
+            OpCode::IntegerDivide => BinaryOp::IntegerDivide,
+            OpCode::Modulo => BinaryOp::Modulo,
+            OpCode::Less => BinaryOp::Less,
+            OpCode::Greater => BinaryOp::Greater,
+            OpCode::Is => BinaryOp::Is,
+            OpCode::IsNot => BinaryOp::IsNot,
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    /// Name in disassembly, e.g. `GET_LOCAL`
+    pub fn name(&self) -> String {
+        let mut name = String::new();
+        for (i, c) in format!("{:?}", self).chars().enumerate() {
+            if c.is_uppercase() && i > 0 {
+                name.push('_');
+            }
+            name.push(c.to_ascii_uppercase());
+        }
+        name
+    }
+}
+
+/// A value known when the program is compiled
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    Num(i64),
+    Dec(f64),
+    Text(Rc<str>),
+    Function(Rc<FunctionProto>),
+    Stall(Rc<StallProto>),
+}
+
+impl Constant {
+    /// The value `OpCode::Constant` pushes; functions and stalls become
+    /// values through `OpCode::Closure` and `OpCode::Stall` instead
+    pub fn value(&self) -> Option<Value> {
+        match self {
+            Constant::Num(value) => Some(Value::Num(*value)),
+            Constant::Dec(value) => Some(Value::Dec(*value)),
+            Constant::Text(text) => Some(Value::Text(Rc::clone(text))),
+            Constant::Function(_) | Constant::Stall(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Constant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Constant::Function(function) => write!(f, "<farmfunction {}>", function.name),
+            Constant::Stall(stall) => write!(f, "<stall {}>", stall.name),
+            other => write!(f, "{}", other.value().map(|value| value.repr()).unwrap_or_default()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    pub constants: Vec<Constant>,
+    /// `(offset, span)` for each instruction whose source differs from the
+    /// instruction before it
+    pub spans: Vec<(usize, Span)>,
+}
+
+impl Chunk {
+    /// Append an instruction, returning its offset
+    pub fn write(&mut self, op: OpCode, operands: &[u16], span: Span) -> usize {
+        debug_assert_eq!(operands.len(), op.operands(), "{:?}", op);
+        let offset = self.code.len();
+        if self.spans.last().is_none_or(|(_, last)| *last != span) {
+            self.spans.push((offset, span));
+        }
+        self.code.push(op as u8);
+        for operand in operands {
+            self.code.extend_from_slice(&operand.to_le_bytes());
+        }
+        offset
+    }
+
+    pub fn read_u16(&self, offset: usize) -> u16 {
+        u16::from_le_bytes([self.code[offset], self.code[offset + 1]])
+    }
+
+    /// Overwrite the operand at `offset`, e.g. a jump once its target is known
+    pub fn patch(&mut self, offset: usize, value: u16) {
+        self.code[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// Index of `constant`, reusing an equal one already in the pool
+    pub fn add_constant(&mut self, constant: Constant) -> Option<u16> {
+        let index = match self.constants.iter().position(|existing| *existing == constant) {
+            Some(index) => index,
+            None => {
+                self.constants.push(constant);
+                self.constants.len() - 1
+            }
+        };
+        u16::try_from(index).ok()
+    }
+
+    /// Source of the instruction at `offset`
+    pub fn span_at(&self, offset: usize) -> Span {
+        let index = self.spans.partition_point(|(start, _)| *start <= offset);
+        index.checked_sub(1).map_or_else(Span::default, |index| self.spans[index].1)
+    }
+}
+
+/// Where a closure's upvalue comes from in the function that creates it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capture {
+    /// One of the creating function's own cells
+    Cell(u16),
+    /// One of the creating function's upvalues
+    Upvalue(u16),
+}
+
+/// A compiled `farmfunction`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionProto {
+    pub name: String,
+    pub arity: u16,
+    /// Cells for this function's own captured variables
+    pub cells: u16,
+    /// Most stack slots its locals take at once, parameters included
+    pub locals: u16,
+    pub captures: Vec<Capture>,
+    pub chunk: Chunk,
+}
+
+/// A compiled `stall`. Its `init` function and methods are closures
+/// created just before the `Stall` instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StallProto {
+    pub name: String,
+    pub fields: Vec<(String, TypeExpr)>,
+    pub methods: Vec<String>,
+}
+
+/// A whole compiled program
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledProgram {
+    /// The top-level statements
+    pub main: Rc<FunctionProto>,
+    /// Names of the top-level variables and functions, by global index
+    pub globals: Vec<String>,
+}
+
+/// Human-readable listing of every function in `program`, e.g.
+///
+/// ```text
+/// == <main> ==
+/// 0000    1:1  CONSTANT          0  10
+/// 0003      |  DEFINE_GLOBAL     0  count
+/// ```
+pub fn disassemble(program: &CompiledProgram) -> String {
+    let mut out = String::new();
+    let mut pending = vec![Rc::clone(&program.main)];
+    while let Some(function) = pending.pop() {
+        disassemble_function(&function, program, &mut out);
+        for constant in function.chunk.constants.iter().rev() {
+            if let Constant::Function(nested) = constant {
+                pending.push(Rc::clone(nested));
+            }
+        }
+    }
+    out
+}
+
+fn disassemble_function(function: &FunctionProto, program: &CompiledProgram, out: &mut String) {
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    let _ = writeln!(out, "== {} ==", function.name);
+    let chunk = &function.chunk;
+    let mut offset = 0;
+    let mut last_span = None;
+    while offset < chunk.code.len() {
+        let span = chunk.span_at(offset);
+        let position = if last_span == Some(span) { "|".to_string() } else { span.to_string() };
+        last_span = Some(span);
+
+        let Some(op) = OpCode::from_byte(chunk.code[offset]) else {
+            let _ = writeln!(out, "{:04}  {:>6}  <invalid opcode {}>", offset, position, chunk.code[offset]);
+            offset += 1;
+            continue;
+        };
+        let operands: Vec<u16> = (0..op.operands()).map(|i| chunk.read_u16(offset + 1 + 2 * i)).collect();
+        let next = offset + 1 + 2 * operands.len();
+        let detail = match (op, operands.as_slice()) {
+            (OpCode::Constant | OpCode::Closure | OpCode::Stall, [k]) => {
+                chunk.constants.get(*k as usize).map_or_else(String::new, |constant| constant.to_string())
+            }
+            (OpCode::GetField | OpCode::SetField, [k]) | (OpCode::Invoke, [k, _, _]) => match chunk.constants.get(*k as usize) {
+                Some(Constant::Text(name)) => name.to_string(),
+                _ => String::new(),
+            },
+            (OpCode::DefineGlobal | OpCode::GetGlobal | OpCode::SetGlobal, [global]) => {
+                program.globals.get(*global as usize).cloned().unwrap_or_default()
+            }
+            (OpCode::Jump | OpCode::JumpIfFalse, [jump]) => format!("-> {:04}", next + *jump as usize),
+            (OpCode::ForIter | OpCode::ForEntry, [_, jump]) => format!("-> {:04}", next + *jump as usize),
+            (OpCode::Loop, [jump]) => format!("-> {:04}", next.saturating_sub(*jump as usize)),
+            (OpCode::CoopType, [element]) => {
+                ELEMENT_TYPES.get(*element as usize).map_or_else(String::new, |element| format!("'{}'", element))
+            }
+            (OpCode::CallMethod, [method, _]) => METHODS.get(*method as usize).map_or("", |m| m.name()).to_string(),
+            (OpCode::CallBuiltin, [builtin, _]) => BUILTINS.get(*builtin as usize).map_or("", |b| b.name()).to_string(),
+            _ => String::new(),
+        };
+        let operands: Vec<String> = operands.iter().map(|operand| format!("{:>5}", operand)).collect();
+        let line = format!("{:04}  {:>6}  {:<14}{}  {}", offset, position, op.name(), operands.join(""), detail);
+        let _ = writeln!(out, "{}", line.trim_end());
+        offset = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_source;
+    use crate::runtime::codegen::compile;
+    use crate::toml_config::language_config::{LanguageKeywordManager, MooConfig};
+
+    #[test]
+    fn test_opcode_bytes() {
+        for (byte, op) in OPCODES.iter().enumerate() {
+            assert_eq!(*op as usize, byte, "{:?}", op);
+            assert_eq!(OpCode::from_byte(byte as u8), Some(*op));
+        }
+        assert_eq!(OpCode::from_byte(OPCODES.len() as u8), None);
+        assert_eq!(OpCode::DefineGlobal.name(), "DEFINE_GLOBAL");
+    }
+
+    #[test]
+    fn test_disassembly() {
+        let manager = LanguageKeywordManager::from_config(MooConfig::from_default());
+        let program = parse_source("num count = 2\nif (count > 1) {\n    moo \"many\"\n}", &manager, "en").unwrap();
+        let expected = "\
+== <main> ==
+0000    1:13  CONSTANT          0  2
+0003     1:1  DEFINE_GLOBAL     0  count
+0006     2:5  GET_GLOBAL        0  count
+0009    2:13  CONSTANT          1  1
+0012     2:5  GREATER
+0013     2:4  JUMP_IF_FALSE     6  -> 0022
+0016     3:9  CONSTANT          2  \"many\"
+0019     3:5  PRINT             1
+0022     2:1  NOTHING
+0023       |  RETURN
+";
+        assert_eq!(disassemble(&compile(&program).unwrap()), expected);
+    }
+}
@@ -0,0 +1,1095 @@
+//! Compiles a checked program to bytecode for the VM.
+
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::parser::ast::{
+    Expr, ExprKind, FunctionDecl, Program, StallDecl, Stmt, StmtKind, TypeExpr, UnaryOp, UpdateOp, SELF,
+};
+use crate::runtime::bytecode::{
+    Capture, Chunk, CompiledProgram, Constant, FunctionProto, OpCode, StallProto, BUILTINS, ELEMENT_TYPES, METHODS,
+    NO_METHOD,
+};
+use crate::runtime::coop::ElementType;
+use crate::runtime::errors::RuntimeError;
+use crate::runtime::operations::invalid;
+use crate::semantic::types::{Builtin, Method};
+use crate::utils::tokens::Span;
+
+/// Name of the function holding the top-level statements
+pub const MAIN: &str = "<main>";
+
+/// Compile `program`. Fails only for programs the resolver would reject,
+/// such as ones using undefined names, or functions too large for the
+/// bytecode's 16-bit operands.
+pub fn compile(program: &Program) -> Result<CompiledProgram, RuntimeError> {
+    let mut compiler = Compiler::default();
+    for stmt in &program.statements {
+        match &stmt.kind {
+            StmtKind::VarDecl { ty, name, .. } => compiler.add_global(name, *ty == TypeExpr::Dec),
+            StmtKind::Function(declaration) => compiler.add_global(&declaration.name, false),
+            StmtKind::Stall(declaration) => compiler.add_global(&declaration.name, false),
+            _ => {}
+        }
+    }
+    compiler.stall_methods = stall_method_names(&program.statements);
+
+    compiler.functions.push(FunctionState::new(MAIN, None, captured_names(&program.statements)));
+    compiler.block(&program.statements)?;
+    let end = program.statements.last().map_or_else(Span::default, |stmt| stmt.span);
+    compiler.emit(OpCode::Nothing, &[], end);
+    compiler.emit(OpCode::Return, &[], end);
+    let main = compiler.finish_function();
+
+    Ok(CompiledProgram {
+        main: Rc::new(main),
+        globals: compiler.globals.into_iter().map(|(name, _)| name).collect(),
+    })
+}
+
+/// Where a local variable lives
+#[derive(Debug, Clone, Copy)]
+enum Storage {
+    Slot(u16),
+    Cell(u16),
+}
+
+/// How to reach a variable from the function being compiled
+#[derive(Debug, Clone, Copy)]
+enum Access {
+    Local(u16),
+    Cell(u16),
+    Upvalue(u16),
+    Global(u16),
+}
+
+#[derive(Debug)]
+struct LocalVariable {
+    name: String,
+    storage: Storage,
+    /// Declared `dec`, so `num` values stored into it are widened
+    dec: bool,
+}
+
+/// A function being compiled
+struct FunctionState {
+    name: String,
+    arity: u16,
+    /// Innermost scope last
+    scopes: Vec<Vec<LocalVariable>>,
+    /// Stack slots currently holding locals
+    slots: u16,
+    /// Most slots held at once
+    locals: u16,
+    cells: u16,
+    /// Upvalues, with whether each holds a `dec`
+    captures: Vec<(Capture, bool)>,
+    /// Names that nested functions refer to; locals with these names are
+    /// stored in cells
+    captured: HashSet<String>,
+    return_type: Option<TypeExpr>,
+    kind: FunctionKind,
+    /// Loops being compiled, innermost last
+    loops: Vec<Loop>,
+    chunk: Chunk,
+}
+
+/// A loop being compiled, for `leave` and `skip`
+struct Loop {
+    /// Where `skip` jumps back to
+    start: usize,
+    /// Scopes open before the loop's variables; `leave` and `skip` pop the
+    /// locals of the scopes after these
+    scopes: usize,
+    /// Jumps from each `leave`, patched to the end of the loop
+    breaks: Vec<usize>,
+}
+
+/// What a compiled function is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FunctionKind {
+    Function,
+    /// A stall's method, taking `self` in slot 0
+    Method,
+    /// A stall's `init`, taking `self` in slot 0 and returning it
+    Constructor,
+}
+
+impl FunctionState {
+    fn new(name: &str, return_type: Option<&TypeExpr>, captured: HashSet<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            arity: 0,
+            scopes: vec![Vec::new()],
+            slots: 0,
+            locals: 0,
+            cells: 0,
+            captures: Vec::new(),
+            captured,
+            return_type: return_type.cloned(),
+            kind: FunctionKind::Function,
+            loops: Vec::new(),
+            chunk: Chunk::default(),
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<&LocalVariable> {
+        self.scopes.iter().rev().flat_map(|scope| scope.iter().rev()).find(|local| local.name == name)
+    }
+}
+
+#[derive(Default)]
+struct Compiler {
+    /// Innermost function last; the first is always `<main>`
+    functions: Vec<FunctionState>,
+    /// Top-level names, with whether each is declared `dec`
+    globals: Vec<(String, bool)>,
+    global_index: HashMap<String, u16>,
+    /// Names of every stall method in the program; calls to them are
+    /// resolved when they run, since the instance is not known before
+    stall_methods: HashSet<String>,
+}
+
+impl Compiler {
+    fn add_global(&mut self, name: &str, dec: bool) {
+        if !self.global_index.contains_key(name) {
+            self.global_index.insert(name.to_string(), self.globals.len() as u16);
+            self.globals.push((name.to_string(), dec));
+        }
+    }
+
+    fn current(&mut self) -> &mut FunctionState {
+        self.functions.last_mut().expect("compiling inside a function")
+    }
+
+    /// Whether declarations go to globals: the top level of `<main>`
+    fn at_top_level(&self) -> bool {
+        self.functions.len() == 1 && self.functions[0].scopes.len() == 1
+    }
+
+    fn too_large(&self, span: Span) -> RuntimeError {
+        let name = self.functions.last().map_or(MAIN, |function| function.name.as_str());
+        invalid(format!("`{}` is too large to compile", name), span)
+    }
+
+    fn finish_function(&mut self) -> FunctionProto {
+        let function = self.functions.pop().expect("compiling inside a function");
+        FunctionProto {
+            name: function.name,
+            arity: function.arity,
+            cells: function.cells,
+            locals: function.locals,
+            captures: function.captures.into_iter().map(|(capture, _)| capture).collect(),
+            chunk: function.chunk,
+        }
+    }
+
+    // ================================
+    // Emitting code
+    // ================================
+
+    fn emit(&mut self, op: OpCode, operands: &[u16], span: Span) -> usize {
+        self.current().chunk.write(op, operands, span)
+    }
+
+    fn constant(&mut self, constant: Constant, span: Span) -> Result<(), RuntimeError> {
+        let index = self.current().chunk.add_constant(constant).ok_or_else(|| self.too_large(span))?;
+        self.emit(OpCode::Constant, &[index], span);
+        Ok(())
+    }
+
+    /// Emit a forward jump to be patched later; returns the offset of its
+    /// distance, the last operand
+    fn jump(&mut self, op: OpCode, operands: &[u16], span: Span) -> usize {
+        self.emit(op, operands, span) + 2 * operands.len() - 1
+    }
+
+    /// Point the jump whose last operand ends at `operand + 2` here
+    fn patch_jump(&mut self, operand: usize, span: Span) -> Result<(), RuntimeError> {
+        let distance = self.current().chunk.code.len() - (operand + 2);
+        let distance = u16::try_from(distance).map_err(|_| self.too_large(span))?;
+        self.current().chunk.patch(operand, distance);
+        Ok(())
+    }
+
+    fn emit_loop(&mut self, start: usize, span: Span) -> Result<(), RuntimeError> {
+        let distance = self.current().chunk.code.len() + 3 - start;
+        let distance = u16::try_from(distance).map_err(|_| self.too_large(span))?;
+        self.emit(OpCode::Loop, &[distance], span);
+        Ok(())
+    }
+
+    fn count(&self, count: usize, span: Span) -> Result<u16, RuntimeError> {
+        u16::try_from(count).map_err(|_| self.too_large(span))
+    }
+
+    /// Index of the text constant `name`, e.g. a field name
+    fn name_constant(&mut self, name: &str, span: Span) -> Result<u16, RuntimeError> {
+        self.current().chunk.add_constant(Constant::Text(Rc::from(name))).ok_or_else(|| self.too_large(span))
+    }
+
+    // ================================
+    // Variables
+    // ================================
+
+    fn begin_scope(&mut self) {
+        self.current().scopes.push(Vec::new());
+    }
+
+    fn end_scope(&mut self, span: Span) {
+        let function = self.current();
+        let scope = function.scopes.pop().unwrap_or_default();
+        let slots = scope.iter().filter(|local| matches!(local.storage, Storage::Slot(_))).count() as u16;
+        function.slots -= slots;
+        if slots > 0 {
+            self.emit(OpCode::PopN, &[slots], span);
+        }
+    }
+
+    /// A local whose value is the top of the stack
+    fn declare_slot(&mut self, name: &str, dec: bool) {
+        let function = self.current();
+        let storage = Storage::Slot(function.slots);
+        function.slots += 1;
+        function.locals = function.locals.max(function.slots);
+        function.scopes.last_mut().expect("inside a scope").push(LocalVariable {
+            name: name.to_string(),
+            storage,
+            dec,
+        });
+    }
+
+    /// A local that nested functions use, stored in a fresh cell
+    fn declare_cell(&mut self, name: &str, dec: bool, span: Span) -> u16 {
+        let function = self.current();
+        let cell = function.cells;
+        function.cells += 1;
+        function.scopes.last_mut().expect("inside a scope").push(LocalVariable {
+            name: name.to_string(),
+            storage: Storage::Cell(cell),
+            dec,
+        });
+        self.emit(OpCode::NewCell, &[cell], span);
+        cell
+    }
+
+    fn is_captured(&mut self, name: &str) -> bool {
+        self.current().captured.contains(name)
+    }
+
+    /// Store the value on top of the stack into a new variable `name`
+    fn define(&mut self, name: &str, dec: bool, span: Span) {
+        if self.at_top_level() {
+            let global = self.global_index[name];
+            self.emit(OpCode::DefineGlobal, &[global], span);
+        } else if let Some(Storage::Cell(cell)) = self.current().lookup(name).map(|local| local.storage) {
+            // Captured variables get their cell when the block starts
+            self.emit(OpCode::SetCell, &[cell], span);
+            self.emit(OpCode::Pop, &[], span);
+        } else {
+            self.declare_slot(name, dec);
+        }
+    }
+
+    fn resolve(&mut self, name: &str) -> Option<(Access, bool)> {
+        let innermost = self.functions.len() - 1;
+        self.resolve_in(innermost, name).or_else(|| {
+            let global = *self.global_index.get(name)?;
+            Some((Access::Global(global), self.globals[global as usize].1))
+        })
+    }
+
+    /// Find `name` among the locals of `self.functions[function]` or the
+    /// functions enclosing it, capturing it as an upvalue if needed
+    fn resolve_in(&mut self, function: usize, name: &str) -> Option<(Access, bool)> {
+        if let Some(local) = self.functions[function].lookup(name) {
+            let access = match local.storage {
+                Storage::Slot(slot) => Access::Local(slot),
+                Storage::Cell(cell) => Access::Cell(cell),
+            };
+            return Some((access, local.dec));
+        }
+        if function == 0 {
+            return None;
+        }
+        let (capture, dec) = match self.resolve_in(function - 1, name)? {
+            (Access::Cell(cell), dec) => (Capture::Cell(cell), dec),
+            (Access::Upvalue(upvalue), dec) => (Capture::Upvalue(upvalue), dec),
+            // Locals nested functions use are always cells; see `captured_names`
+            _ => return None,
+        };
+        let captures = &mut self.functions[function].captures;
+        let index = match captures.iter().position(|(existing, _)| *existing == capture) {
+            Some(index) => index,
+            None => {
+                captures.push((capture, dec));
+                captures.len() - 1
+            }
+        };
+        Some((Access::Upvalue(index as u16), dec))
+    }
+
+    fn get(&mut self, access: Access, span: Span) {
+        match access {
+            Access::Local(slot) => self.emit(OpCode::GetLocal, &[slot], span),
+            Access::Cell(cell) => self.emit(OpCode::GetCell, &[cell], span),
+            Access::Upvalue(upvalue) => self.emit(OpCode::GetUpvalue, &[upvalue], span),
+            Access::Global(global) => self.emit(OpCode::GetGlobal, &[global], span),
+        };
+    }
+
+    /// Store the top of the stack, leaving it there
+    fn set(&mut self, access: Access, span: Span) {
+        match access {
+            Access::Local(slot) => self.emit(OpCode::SetLocal, &[slot], span),
+            Access::Cell(cell) => self.emit(OpCode::SetCell, &[cell], span),
+            Access::Upvalue(upvalue) => self.emit(OpCode::SetUpvalue, &[upvalue], span),
+            Access::Global(global) => self.emit(OpCode::SetGlobal, &[global], span),
+        };
+    }
+
+    fn variable(&mut self, name: &str, span: Span) -> Result<(Access, bool), RuntimeError> {
+        self.resolve(name).ok_or_else(|| RuntimeError::UndefinedName {
+            name: name.to_string(),
+            span,
+        })
+    }
+
+    // ================================
+    // Statements
+    // ================================
+
+    /// Compile a block's statements in the current scope. Captured variables
+    /// get their cells first and functions and stalls are created before any
+    /// other statement, so they can be used before the line defining them.
+    fn block(&mut self, statements: &[Stmt]) -> Result<(), RuntimeError> {
+        if !self.at_top_level() {
+            for stmt in statements {
+                let (name, dec) = match &stmt.kind {
+                    StmtKind::VarDecl { ty, name, .. } => (name, *ty == TypeExpr::Dec),
+                    StmtKind::Function(declaration) => (&declaration.name, false),
+                    StmtKind::Stall(declaration) => (&declaration.name, false),
+                    _ => continue,
+                };
+                if self.is_captured(name) {
+                    self.declare_cell(name, dec, stmt.span);
+                }
+            }
+        }
+        for stmt in statements {
+            match &stmt.kind {
+                StmtKind::Function(declaration) => {
+                    self.function(declaration, FunctionKind::Function)?;
+                    self.define(&declaration.name, false, declaration.span);
+                }
+                StmtKind::Stall(declaration) => {
+                    self.stall(declaration)?;
+                    self.define(&declaration.name, false, declaration.span);
+                }
+                _ => {}
+            }
+        }
+        for stmt in statements {
+            self.statement(stmt)?;
+        }
+        Ok(())
+    }
+
+    fn scoped_block(&mut self, statements: &[Stmt], span: Span) -> Result<(), RuntimeError> {
+        self.begin_scope();
+        self.block(statements)?;
+        self.end_scope(span);
+        Ok(())
+    }
+
+    fn statement(&mut self, stmt: &Stmt) -> Result<(), RuntimeError> {
+        let span = stmt.span;
+        match &stmt.kind {
+            StmtKind::VarDecl { ty, name, initializer } => {
+                let dec = *ty == TypeExpr::Dec;
+                match initializer {
+                    Some(initializer) => {
+                        self.expression(initializer)?;
+                        self.coerce(ty, span);
+                    }
+                    None => self.default_value(ty, span)?,
+                }
+                self.define(name, dec, span);
+            }
+            StmtKind::Assign { target, value } => self.assign(target, |compiler| compiler.expression(value), span)?,
+            StmtKind::CompoundAssign { target, op, value } => {
+                let op = OpCode::binary(*op);
+                match &unwrap_grouping(target).kind {
+                    ExprKind::Variable(name) => {
+                        let (access, dec) = self.variable(name, target.span)?;
+                        self.get(access, span);
+                        self.expression(value)?;
+                        self.emit(op, &[], span);
+                        if dec {
+                            self.emit(OpCode::Widen, &[], span);
+                        }
+                        self.set(access, span);
+                    }
+                    ExprKind::Index { object, index } => {
+                        self.expression(object)?;
+                        self.expression(index)?;
+                        self.emit(OpCode::Dup2, &[], span);
+                        self.emit(OpCode::GetIndex, &[], target.span);
+                        self.expression(value)?;
+                        self.emit(op, &[], span);
+                        self.emit(OpCode::SetIndex, &[], target.span);
+                    }
+                    ExprKind::Member { object, name } => {
+                        let field = self.name_constant(name, target.span)?;
+                        self.expression(object)?;
+                        self.emit(OpCode::Dup, &[], span);
+                        self.emit(OpCode::GetField, &[field], target.span);
+                        self.expression(value)?;
+                        self.emit(op, &[], span);
+                        self.emit(OpCode::SetField, &[field], target.span);
+                    }
+                    _ => return Err(invalid("only variables, elements and fields can be assigned", target.span)),
+                }
+                self.emit(OpCode::Pop, &[], span);
+            }
+            StmtKind::MultiAssign { targets, values } => {
+                // The values wait in hidden locals until every one is found
+                self.begin_scope();
+                let first = self.current().slots;
+                for value in values {
+                    self.expression(value)?;
+                    self.declare_slot("", false);
+                }
+                for (slot, target) in (first..).zip(targets) {
+                    self.assign(
+                        target,
+                        |compiler| {
+                            compiler.emit(OpCode::GetLocal, &[slot], span);
+                            Ok(())
+                        },
+                        span,
+                    )?;
+                }
+                self.end_scope(span);
+            }
+            StmtKind::Print { values } => {
+                for value in values {
+                    self.expression(value)?;
+                }
+                let count = self.count(values.len(), span)?;
+                self.emit(OpCode::Print, &[count], span);
+            }
+            StmtKind::If { branches, else_branch } => {
+                let mut ends = Vec::new();
+                for (i, (condition, body)) in branches.iter().enumerate() {
+                    self.expression(condition)?;
+                    let next = self.jump(OpCode::JumpIfFalse, &[0], condition.span);
+                    self.scoped_block(body, span)?;
+                    if i + 1 < branches.len() || else_branch.is_some() {
+                        ends.push(self.jump(OpCode::Jump, &[0], span));
+                    }
+                    self.patch_jump(next, span)?;
+                }
+                if let Some(body) = else_branch {
+                    self.scoped_block(body, span)?;
+                }
+                for end in ends {
+                    self.patch_jump(end, span)?;
+                }
+            }
+            StmtKind::While { condition, body } => {
+                let start = self.current().chunk.code.len();
+                self.expression(condition)?;
+                let exit = self.jump(OpCode::JumpIfFalse, &[0], condition.span);
+                self.begin_loop(start);
+                self.scoped_block(body, span)?;
+                self.emit_loop(start, span)?;
+                self.patch_jump(exit, span)?;
+                self.end_loop(span)?;
+            }
+            StmtKind::For { variable, value, iterable, body } => {
+                // Hidden locals: the elements to visit and the position. For
+                // `for key, value`, the elements are keys and values in turn.
+                let (setup, next) = match value {
+                    Some(_) => (OpCode::Entries, OpCode::ForEntry),
+                    None => (OpCode::Elements, OpCode::ForIter),
+                };
+                self.begin_scope();
+                self.expression(iterable)?;
+                self.emit(setup, &[], iterable.span);
+                let elements = self.current().slots;
+                self.declare_slot("", false);
+                self.constant(Constant::Num(0), span)?;
+                self.declare_slot("", false);
+
+                let start = self.current().chunk.code.len();
+                let exit = self.jump(next, &[elements, 0], iterable.span);
+                self.begin_loop(start);
+                self.begin_scope();
+                match value {
+                    None if self.is_captured(variable) => {
+                        let cell = self.declare_cell(variable, false, span);
+                        self.emit(OpCode::SetCell, &[cell], span);
+                        self.emit(OpCode::Pop, &[], span);
+                    }
+                    None => self.declare_slot(variable, false),
+                    Some(value) => self.loop_pair(variable, value, span),
+                }
+                self.scoped_block(body, span)?;
+                self.end_scope(span);
+                self.emit_loop(start, span)?;
+                self.patch_jump(exit, span)?;
+                self.end_loop(span)?;
+                self.end_scope(span);
+            }
+            // Compiled when the enclosing block started
+            StmtKind::Function(_) | StmtKind::Stall(_) => {}
+            StmtKind::Return(_) if self.current().kind == FunctionKind::Constructor => {
+                // The checker rejects constructors returning a value
+                self.emit(OpCode::GetLocal, &[0], span);
+                self.emit(OpCode::Return, &[], span);
+            }
+            StmtKind::Return(value) => {
+                match value {
+                    Some(value) => self.expression(value)?,
+                    None => {
+                        self.emit(OpCode::Nothing, &[], span);
+                    }
+                }
+                if let Some(ty) = self.current().return_type.clone() {
+                    self.coerce(&ty, span);
+                }
+                self.emit(OpCode::Return, &[], span);
+            }
+            StmtKind::Break | StmtKind::Continue => {
+                let function = self.current();
+                let Some(innermost) = function.loops.last() else {
+                    return Err(invalid("`leave` and `skip` can only be used inside a loop", span));
+                };
+                let start = innermost.start;
+                let slots = function.scopes[innermost.scopes..].iter()
+                    .flatten()
+                    .filter(|local| matches!(local.storage, Storage::Slot(_)))
+                    .count();
+                if slots > 0 {
+                    let slots = self.count(slots, span)?;
+                    self.emit(OpCode::PopN, &[slots], span);
+                }
+                if stmt.kind == StmtKind::Break {
+                    let jump = self.jump(OpCode::Jump, &[0], span);
+                    self.current().loops.last_mut().expect("checked above").breaks.push(jump);
+                } else {
+                    self.emit_loop(start, span)?;
+                }
+            }
+            StmtKind::Expression(expr) => {
+                self.expression(expr)?;
+                self.emit(OpCode::Pop, &[], span);
+            }
+            StmtKind::Bring { path } => return Err(invalid(format!("cannot run `{}` before it is loaded", path), span)),
+            StmtKind::Error => return Err(invalid("cannot run a statement with a syntax error", span)),
+        }
+        Ok(())
+    }
+
+    /// Start a loop whose next round begins at `start`
+    fn begin_loop(&mut self, start: usize) {
+        let function = self.current();
+        let scopes = function.scopes.len();
+        function.loops.push(Loop { start, scopes, breaks: Vec::new() });
+    }
+
+    /// Point the loop's `leave` jumps here
+    fn end_loop(&mut self, span: Span) -> Result<(), RuntimeError> {
+        let finished = self.current().loops.pop().expect("inside a loop");
+        for jump in finished.breaks {
+            self.patch_jump(jump, span)?;
+        }
+        Ok(())
+    }
+
+    /// Store into `target` the value `value` leaves on the stack
+    fn assign(
+        &mut self,
+        target: &Expr,
+        value: impl FnOnce(&mut Self) -> Result<(), RuntimeError>,
+        span: Span,
+    ) -> Result<(), RuntimeError> {
+        match &unwrap_grouping(target).kind {
+            ExprKind::Variable(name) => {
+                let (access, dec) = self.variable(name, target.span)?;
+                value(self)?;
+                if dec {
+                    self.emit(OpCode::Widen, &[], span);
+                }
+                self.set(access, span);
+            }
+            ExprKind::Index { object, index } => {
+                self.expression(object)?;
+                self.expression(index)?;
+                value(self)?;
+                self.emit(OpCode::SetIndex, &[], target.span);
+            }
+            ExprKind::Member { object, name } => {
+                let field = self.name_constant(name, target.span)?;
+                self.expression(object)?;
+                value(self)?;
+                self.emit(OpCode::SetField, &[field], target.span);
+            }
+            _ => return Err(invalid("only variables, elements and fields can be assigned", target.span)),
+        }
+        self.emit(OpCode::Pop, &[], span);
+        Ok(())
+    }
+
+    fn default_value(&mut self, ty: &TypeExpr, span: Span) -> Result<(), RuntimeError> {
+        match ty {
+            TypeExpr::Text => return self.constant(Constant::Text(Rc::from("")), span),
+            TypeExpr::Num => return self.constant(Constant::Num(0), span),
+            TypeExpr::Dec => return self.constant(Constant::Dec(0.0), span),
+            TypeExpr::Boolean => self.emit(OpCode::False, &[], span),
+            TypeExpr::Coop(_) => {
+                self.emit(OpCode::Coop, &[0], span);
+                self.coerce(ty, span);
+                return Ok(());
+            }
+            TypeExpr::BarnMap(_) => self.emit(OpCode::BarnMap, &[0], span),
+            TypeExpr::Range => {
+                self.constant(Constant::Num(0), span)?;
+                self.emit(OpCode::Range, &[1], span)
+            }
+            TypeExpr::Stall(_) => self.emit(OpCode::Nothing, &[], span),
+        };
+        Ok(())
+    }
+
+    /// Declare the key and value a `ForEntry` pushed. Either may be
+    /// captured, so both get slots, and captured ones are copied into cells.
+    fn loop_pair(&mut self, key: &str, value: &str, span: Span) {
+        let first = self.current().slots;
+        let captured = [self.is_captured(key), self.is_captured(value)];
+        for (name, captured) in [key, value].into_iter().zip(captured) {
+            self.declare_slot(if captured { "" } else { name }, false);
+        }
+        for (slot, (name, captured)) in [key, value].into_iter().zip(captured).enumerate() {
+            if captured {
+                let cell = self.declare_cell(name, false, span);
+                self.emit(OpCode::GetLocal, &[first + slot as u16], span);
+                self.emit(OpCode::SetCell, &[cell], span);
+                self.emit(OpCode::Pop, &[], span);
+            }
+        }
+    }
+
+    /// Fit the value on top of the stack to the declared type `ty`, as
+    /// `operations::coerce` does for the interpreter
+    fn coerce(&mut self, ty: &TypeExpr, span: Span) {
+        match ty {
+            TypeExpr::Dec => {
+                self.emit(OpCode::Widen, &[], span);
+            }
+            TypeExpr::Coop(Some(element)) => {
+                let element = ElementType::of(element);
+                let operand = ELEMENT_TYPES.iter().position(|known| *known == element)
+                    .expect("every element type has an operand");
+                self.emit(OpCode::CoopType, &[operand as u16], span);
+            }
+            _ => {}
+        }
+    }
+
+    /// Compile a nested `farmfunction` and emit the code creating its closure
+    fn function(&mut self, declaration: &FunctionDecl, kind: FunctionKind) -> Result<(), RuntimeError> {
+        let span = declaration.span;
+        let mut function = FunctionState::new(
+            &declaration.name,
+            declaration.return_type.as_ref(),
+            captured_names(&declaration.body),
+        );
+        let receiver = kind != FunctionKind::Function;
+        function.arity = self.count(declaration.params.len() + usize::from(receiver), span)?;
+        function.kind = kind;
+        self.functions.push(function);
+
+        if receiver {
+            self.declare_slot(SELF, false);
+            if self.is_captured(SELF) {
+                self.emit(OpCode::GetLocal, &[0], span);
+                let cell = self.declare_cell(SELF, false, span);
+                self.emit(OpCode::SetCell, &[cell], span);
+                self.emit(OpCode::Pop, &[], span);
+            }
+        }
+        for param in &declaration.params {
+            self.declare_slot(&param.name, param.ty == TypeExpr::Dec);
+        }
+        for (slot, param) in declaration.params.iter().enumerate() {
+            let coerced = coerces(&param.ty);
+            let slot = (slot + usize::from(receiver)) as u16;
+            if coerced || self.is_captured(&param.name) {
+                self.emit(OpCode::GetLocal, &[slot], param.span);
+            }
+            if coerced {
+                self.coerce(&param.ty, param.span);
+                self.emit(OpCode::SetLocal, &[slot], param.span);
+            }
+            if self.is_captured(&param.name) {
+                let cell = self.declare_cell(&param.name, param.ty == TypeExpr::Dec, param.span);
+                self.emit(OpCode::SetCell, &[cell], param.span);
+            }
+            if coerced || self.is_captured(&param.name) {
+                self.emit(OpCode::Pop, &[], param.span);
+            }
+        }
+        self.block(&declaration.body)?;
+        if kind == FunctionKind::Constructor {
+            self.emit(OpCode::GetLocal, &[0], span);
+        } else {
+            self.emit(OpCode::Nothing, &[], span);
+        }
+        self.emit(OpCode::Return, &[], span);
+
+        let proto = self.finish_function();
+        let index = self.current().chunk.add_constant(Constant::Function(Rc::new(proto)))
+            .ok_or_else(|| self.too_large(span))?;
+        self.emit(OpCode::Closure, &[index], span);
+        Ok(())
+    }
+
+    /// Compile a stall's `init` function and methods and emit the code
+    /// creating its constructor value
+    fn stall(&mut self, declaration: &StallDecl) -> Result<(), RuntimeError> {
+        let span = declaration.span;
+        self.function(&declaration.init(), FunctionKind::Constructor)?;
+        for method in declaration.methods() {
+            self.function(method, FunctionKind::Method)?;
+        }
+        let proto = StallProto {
+            name: declaration.name.clone(),
+            fields: declaration.fields.iter().map(|field| (field.name.clone(), field.ty.clone())).collect(),
+            methods: declaration.methods().map(|method| method.name.clone()).collect(),
+        };
+        let index = self.current().chunk.add_constant(Constant::Stall(Rc::new(proto)))
+            .ok_or_else(|| self.too_large(span))?;
+        self.emit(OpCode::Stall, &[index], span);
+        Ok(())
+    }
+
+    // ================================
+    // Expressions
+    // ================================
+
+    fn expression(&mut self, expr: &Expr) -> Result<(), RuntimeError> {
+        let span = expr.span;
+        match &expr.kind {
+            ExprKind::Integer(value) => self.constant(Constant::Num(*value), span)?,
+            ExprKind::Decimal(value) => self.constant(Constant::Dec(*value), span)?,
+            ExprKind::Text(text) => self.constant(Constant::Text(Rc::from(text.as_str())), span)?,
+            ExprKind::Boolean(true) => {
+                self.emit(OpCode::True, &[], span);
+            }
+            ExprKind::Boolean(false) => {
+                self.emit(OpCode::False, &[], span);
+            }
+            ExprKind::Variable(name) => {
+                let (access, _) = self.variable(name, span)?;
+                self.get(access, span);
+            }
+            ExprKind::CoopLiteral(elements) => {
+                for element in elements {
+                    self.expression(element)?;
+                }
+                let count = self.count(elements.len(), span)?;
+                self.emit(OpCode::Coop, &[count], span);
+            }
+            ExprKind::BarnMapLiteral(entries) => {
+                for (key, value) in entries {
+                    self.expression(key)?;
+                    self.expression(value)?;
+                }
+                let count = self.count(entries.len(), span)?;
+                self.emit(OpCode::BarnMap, &[count], span);
+            }
+            ExprKind::Range(args) => {
+                for arg in args {
+                    self.expression(arg)?;
+                }
+                let count = self.count(args.len(), span)?;
+                self.emit(OpCode::Range, &[count], span);
+            }
+            ExprKind::Grouping(inner) => self.expression(inner)?,
+            ExprKind::Unary { op: UnaryOp::Negate, operand } => {
+                self.expression(operand)?;
+                self.emit(OpCode::Negate, &[], span);
+            }
+            ExprKind::Binary { op, left, right } => {
+                self.expression(left)?;
+                self.expression(right)?;
+                self.emit(OpCode::binary(*op), &[], span);
+            }
+            ExprKind::Update { op, prefix, target } => self.update(*op, *prefix, target, span)?,
+            ExprKind::Call { callee, args, builtin } => self.call(callee, args, *builtin, span)?,
+            ExprKind::Index { object, index } => {
+                self.expression(object)?;
+                self.expression(index)?;
+                self.emit(OpCode::GetIndex, &[], span);
+            }
+            ExprKind::Member { object, name } => {
+                let field = self.name_constant(name, span)?;
+                self.expression(object)?;
+                self.emit(OpCode::GetField, &[field], span);
+            }
+            ExprKind::Error => return Err(invalid("cannot run an expression with a syntax error", span)),
+        }
+        Ok(())
+    }
+
+    /// `++x`, `x--`, `counts[i]++`, ... leaving the new value for prefix
+    /// updates and the old one for postfix updates
+    fn update(&mut self, op: UpdateOp, prefix: bool, target: &Expr, span: Span) -> Result<(), RuntimeError> {
+        let step = match op {
+            UpdateOp::Increment => OpCode::Increment,
+            UpdateOp::Decrement => OpCode::Decrement,
+        };
+        match &unwrap_grouping(target).kind {
+            ExprKind::Variable(name) => {
+                let (access, _) = self.variable(name, target.span)?;
+                self.get(access, span);
+                if !prefix {
+                    self.emit(OpCode::Dup, &[], span);
+                }
+                self.emit(step, &[], span);
+                self.set(access, span);
+                if !prefix {
+                    self.emit(OpCode::Pop, &[], span);
+                }
+            }
+            ExprKind::Index { object, index } => {
+                self.expression(object)?;
+                self.expression(index)?;
+                self.emit(OpCode::Dup2, &[], span);
+                self.emit(OpCode::GetIndex, &[], span);
+                if !prefix {
+                    // Keep the old value under the object and index
+                    self.emit(OpCode::Dup, &[], span);
+                    self.emit(OpCode::Bury, &[3], span);
+                }
+                self.emit(step, &[], span);
+                self.emit(OpCode::SetIndex, &[], span);
+                if !prefix {
+                    self.emit(OpCode::Pop, &[], span);
+                }
+            }
+            ExprKind::Member { object, name } => {
+                let field = self.name_constant(name, span)?;
+                self.expression(object)?;
+                self.emit(OpCode::Dup, &[], span);
+                self.emit(OpCode::GetField, &[field], span);
+                if !prefix {
+                    // Keep the old value under the object
+                    self.emit(OpCode::Dup, &[], span);
+                    self.emit(OpCode::Bury, &[2], span);
+                }
+                self.emit(step, &[], span);
+                self.emit(OpCode::SetField, &[field], span);
+                if !prefix {
+                    self.emit(OpCode::Pop, &[], span);
+                }
+            }
+            _ => return Err(invalid("only variables, elements and fields can be assigned", target.span)),
+        }
+        Ok(())
+    }
+
+    fn call(&mut self, callee: &Expr, args: &[Expr], builtin: Option<&str>, span: Span) -> Result<(), RuntimeError> {
+        let count = self.count(args.len(), span)?;
+        if let ExprKind::Member { object, name } = &callee.kind {
+            let method = builtin.and_then(Method::lookup)
+                .and_then(|method| METHODS.iter().position(|known| *known == method))
+                .map_or(NO_METHOD, |method| method as u16);
+            self.expression(object)?;
+            for arg in args {
+                self.expression(arg)?;
+            }
+            if method != NO_METHOD && !self.stall_methods.contains(name) {
+                self.emit(OpCode::CallMethod, &[method, count], span);
+            } else {
+                let name = self.name_constant(name, callee.span)?;
+                self.emit(OpCode::Invoke, &[name, count, method], span);
+            }
+            return Ok(());
+        }
+        if let ExprKind::Variable(name) = &callee.kind {
+            // A variable or function of the same name hides the builtin
+            let builtin = builtin.and_then(Builtin::lookup)
+                .filter(|_| self.resolve(name).is_none())
+                .and_then(|builtin| BUILTINS.iter().position(|known| *known == builtin));
+            if let Some(builtin) = builtin {
+                for arg in args {
+                    self.expression(arg)?;
+                }
+                self.emit(OpCode::CallBuiltin, &[builtin as u16, count], span);
+                return Ok(());
+            }
+        }
+        self.expression(callee)?;
+        for arg in args {
+            self.expression(arg)?;
+        }
+        self.emit(OpCode::Call, &[count], span);
+        Ok(())
+    }
+}
+
+/// Whether values stored where `ty` is declared need `coerce`
+fn coerces(ty: &TypeExpr) -> bool {
+    matches!(ty, TypeExpr::Dec | TypeExpr::Coop(Some(_)))
+}
+
+fn unwrap_grouping(expr: &Expr) -> &Expr {
+    match &expr.kind {
+        ExprKind::Grouping(inner) => unwrap_grouping(inner),
+        _ => expr,
+    }
+}
+
+/// Every name used inside the `farmfunction`s nested in `body`. This
+/// over-approximates the variables of `body` that need cells: a name a nested
+/// function declares itself is included too, which only costs a cell.
+fn captured_names(body: &[Stmt]) -> HashSet<String> {
+    let mut names = HashSet::new();
+    for stmt in body {
+        nested_function_names(stmt, &mut names);
+    }
+    names
+}
+
+fn nested_function_names(stmt: &Stmt, names: &mut HashSet<String>) {
+    match &stmt.kind {
+        StmtKind::Function(_) | StmtKind::Stall(_) => statement_names(stmt, names),
+        StmtKind::If { branches, else_branch } => {
+            for stmt in branches.iter().flat_map(|(_, body)| body).chain(else_branch.iter().flatten()) {
+                nested_function_names(stmt, names);
+            }
+        }
+        StmtKind::While { body, .. } | StmtKind::For { body, .. } => {
+            for stmt in body {
+                nested_function_names(stmt, names);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Names of the methods of every stall declared in `statements`, at any
+/// depth
+fn stall_method_names(statements: &[Stmt]) -> HashSet<String> {
+    fn collect(statements: &[Stmt], names: &mut HashSet<String>) {
+        for stmt in statements {
+            match &stmt.kind {
+                StmtKind::Stall(declaration) => {
+                    names.extend(declaration.methods().map(|method| method.name.clone()));
+                    for function in &declaration.functions {
+                        collect(&function.body, names);
+                    }
+                }
+                StmtKind::Function(declaration) => collect(&declaration.body, names),
+                StmtKind::If { branches, else_branch } => {
+                    for (_, body) in branches {
+                        collect(body, names);
+                    }
+                    if let Some(body) = else_branch {
+                        collect(body, names);
+                    }
+                }
+                StmtKind::While { body, .. } | StmtKind::For { body, .. } => collect(body, names),
+                _ => {}
+            }
+        }
+    }
+    let mut names = HashSet::new();
+    collect(statements, &mut names);
+    names
+}
+
+/// Every variable name `stmt` reads or writes, including in nested functions
+fn statement_names(stmt: &Stmt, names: &mut HashSet<String>) {
+    fn body(statements: &[Stmt], names: &mut HashSet<String>) {
+        for stmt in statements {
+            statement_names(stmt, names);
+        }
+    }
+    match &stmt.kind {
+        StmtKind::VarDecl { initializer, .. } => {
+            if let Some(initializer) = initializer {
+                expression_names(initializer, names);
+            }
+        }
+        StmtKind::Assign { target, value } | StmtKind::CompoundAssign { target, value, .. } => {
+            expression_names(target, names);
+            expression_names(value, names);
+        }
+        StmtKind::MultiAssign { targets, values } => {
+            targets.iter().chain(values).for_each(|expr| expression_names(expr, names));
+        }
+        StmtKind::Print { values } => values.iter().for_each(|value| expression_names(value, names)),
+        StmtKind::If { branches, else_branch } => {
+            for (condition, statements) in branches {
+                expression_names(condition, names);
+                body(statements, names);
+            }
+            if let Some(statements) = else_branch {
+                body(statements, names);
+            }
+        }
+        StmtKind::While { condition, body: statements } => {
+            expression_names(condition, names);
+            body(statements, names);
+        }
+        StmtKind::For { iterable, body: statements, .. } => {
+            expression_names(iterable, names);
+            body(statements, names);
+        }
+        StmtKind::Function(declaration) => body(&declaration.body, names),
+        StmtKind::Stall(declaration) => {
+            for initializer in declaration.fields.iter().filter_map(|field| field.initializer.as_ref()) {
+                expression_names(initializer, names);
+            }
+            for function in &declaration.functions {
+                body(&function.body, names);
+            }
+        }
+        StmtKind::Return(Some(value)) | StmtKind::Expression(value) => expression_names(value, names),
+        StmtKind::Return(None) | StmtKind::Break | StmtKind::Continue | StmtKind::Bring { .. } | StmtKind::Error => {}
+    }
+}
+
+fn expression_names(expr: &Expr, names: &mut HashSet<String>) {
+    match &expr.kind {
+        ExprKind::Variable(name) => {
+            names.insert(name.clone());
+        }
+        ExprKind::CoopLiteral(values) | ExprKind::Range(values) => {
+            values.iter().for_each(|value| expression_names(value, names))
+        }
+        ExprKind::BarnMapLiteral(entries) => {
+            for (key, value) in entries {
+                expression_names(key, names);
+                expression_names(value, names);
+            }
+        }
+        ExprKind::Grouping(inner) | ExprKind::Unary { operand: inner, .. } | ExprKind::Update { target: inner, .. } => {
+            expression_names(inner, names)
+        }
+        ExprKind::Member { object: inner, .. } => expression_names(inner, names),
+        ExprKind::Binary { left, right, .. } | ExprKind::Index { object: left, index: right } => {
+            expression_names(left, names);
+            expression_names(right, names);
+        }
+        ExprKind::Call { callee, args, .. } => {
+            expression_names(callee, names);
+            args.iter().for_each(|arg| expression_names(arg, names));
+        }
+        ExprKind::Integer(_) | ExprKind::Decimal(_) | ExprKind::Text(_) | ExprKind::Boolean(_) | ExprKind::Error => {}
+    }
+}
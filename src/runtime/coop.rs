@@ -0,0 +1,276 @@
+//! `coop` values: growable arrays whose elements are limited to the type
+//! they were declared with.
+//!
+//! A coop gets its element type when it is stored in a `coop 'num'`
+//! variable, parameter or return value. From then on adding, inserting or
+//! assigning a value of another type is a runtime error, even through a
+//! path the type checker could not follow, such as a coop inside a
+//! `coop 'coop'`. Coops that were never declared with an element type
+//! accept any value.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::parser::ast::TypeExpr;
+use crate::runtime::errors::RuntimeError;
+use crate::runtime::operations::invalid;
+use crate::runtime::value::Value;
+use crate::utils::tokens::Span;
+
+/// The declared type of a coop's elements
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElementType {
+    Text,
+    Num,
+    Dec,
+    Boolean,
+    Coop,
+    BarnMap,
+    Range,
+    /// Instances of any stall
+    Stall,
+}
+
+impl ElementType {
+    pub fn of(ty: &TypeExpr) -> ElementType {
+        match ty {
+            TypeExpr::Text => ElementType::Text,
+            TypeExpr::Num => ElementType::Num,
+            TypeExpr::Dec => ElementType::Dec,
+            TypeExpr::Boolean => ElementType::Boolean,
+            TypeExpr::Coop(_) => ElementType::Coop,
+            TypeExpr::BarnMap(_) => ElementType::BarnMap,
+            TypeExpr::Range => ElementType::Range,
+            TypeExpr::Stall(_) => ElementType::Stall,
+        }
+    }
+
+    /// Whether an element of this type can hold `value`. A `dec` coop also
+    /// holds `num` values.
+    pub fn accepts(&self, value: &Value) -> bool {
+        matches!(
+            (self, value),
+            (ElementType::Text, Value::Text(_))
+                | (ElementType::Num, Value::Num(_))
+                | (ElementType::Dec, Value::Num(_) | Value::Dec(_))
+                | (ElementType::Boolean, Value::Boolean(_))
+                | (ElementType::Coop, Value::Coop(_))
+                | (ElementType::BarnMap, Value::BarnMap(_))
+                | (ElementType::Range, Value::Range(_))
+                | (ElementType::Stall, Value::Instance(_))
+        )
+    }
+}
+
+impl fmt::Display for ElementType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ElementType::Text => "text",
+            ElementType::Num => "num",
+            ElementType::Dec => "dec",
+            ElementType::Boolean => "boolean",
+            ElementType::Coop => "coop",
+            ElementType::BarnMap => "barn_map",
+            ElementType::Range => "barn",
+            ElementType::Stall => "stall",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Coop {
+    /// `None` until the coop is stored somewhere declared `coop 'type'`
+    pub element: Option<ElementType>,
+    pub elements: Vec<Value>,
+}
+
+/// Position of a valid index into a sequence of `length` elements. With
+/// `end_allowed`, `length` itself is valid too, as for `insert` and `slice`.
+pub(crate) fn position(index: &Value, length: usize, end_allowed: bool, span: Span) -> Result<usize, RuntimeError> {
+    let limit = if end_allowed { length + 1 } else { length };
+    match index {
+        Value::Num(index) if *index < 0 => Err(RuntimeError::NegativeIndex { index: *index, span }),
+        Value::Num(index) if (*index as u64) < limit as u64 => Ok(*index as usize),
+        Value::Num(index) => Err(RuntimeError::IndexOutOfRange { index: *index, length, span }),
+        other => Err(invalid(format!("a coop index must be `num`, found `{}`", other.type_name()), span)),
+    }
+}
+
+/// Order of two elements for `sort`: numbers by value, text alphabetically,
+/// `false` before `true`. `None` for values that cannot be compared.
+fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Num(a), Value::Num(b)) => Some(a.cmp(b)),
+        (Value::Num(_) | Value::Dec(_), Value::Num(_) | Value::Dec(_)) => Some(a.as_dec()?.total_cmp(&b.as_dec()?)),
+        (Value::Text(a), Value::Text(b)) => Some(a.cmp(b)),
+        (Value::Boolean(a), Value::Boolean(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+impl Coop {
+    pub fn new(elements: Vec<Value>) -> Self {
+        Self { element: None, elements }
+    }
+
+    pub fn typed(element: ElementType, elements: Vec<Value>) -> Self {
+        Self { element: Some(element), elements }
+    }
+
+    /// The coop's type as written in a declaration, e.g. `coop 'num'`
+    pub fn type_name(&self) -> String {
+        match self.element {
+            Some(element) => format!("coop '{}'", element),
+            None => "coop".to_string(),
+        }
+    }
+
+    fn check(&self, value: &Value, span: Span) -> Result<(), RuntimeError> {
+        match self.element {
+            Some(element) if !element.accepts(value) => Err(RuntimeError::ElementTypeMismatch {
+                value: value.repr(),
+                container: self.type_name(),
+                span,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Give an untyped coop the element type it was declared with, checking
+    /// the elements it already holds. A coop keeps the first type it gets.
+    pub fn declare(&mut self, element: ElementType, span: Span) -> Result<(), RuntimeError> {
+        if self.element.is_some() {
+            return Ok(());
+        }
+        if let Some(value) = self.elements.iter().find(|value| !element.accepts(value)) {
+            return Err(RuntimeError::ElementTypeMismatch {
+                value: value.repr(),
+                container: format!("coop '{}'", element),
+                span,
+            });
+        }
+        self.element = Some(element);
+        Ok(())
+    }
+
+    pub fn get(&self, index: &Value, span: Span) -> Result<Value, RuntimeError> {
+        Ok(self.elements[position(index, self.elements.len(), false, span)?].clone())
+    }
+
+    /// `animals[index] = value`. An element that holds a `dec` stays a `dec`.
+    pub fn set(&mut self, index: &Value, value: Value, span: Span) -> Result<(), RuntimeError> {
+        self.check(&value, span)?;
+        let slot = position(index, self.elements.len(), false, span)?;
+        self.elements[slot] = match (&self.elements[slot], value) {
+            (Value::Dec(_), Value::Num(number)) => Value::Dec(number as f64),
+            (_, value) => value,
+        };
+        Ok(())
+    }
+
+    /// `add` / `plant`
+    pub fn push(&mut self, value: Value, span: Span) -> Result<(), RuntimeError> {
+        self.check(&value, span)?;
+        self.elements.push(value);
+        Ok(())
+    }
+
+    /// `insert(index, value)`: `index` may be the length, to append
+    pub fn insert(&mut self, index: &Value, value: Value, span: Span) -> Result<(), RuntimeError> {
+        self.check(&value, span)?;
+        let slot = position(index, self.elements.len(), true, span)?;
+        self.elements.insert(slot, value);
+        Ok(())
+    }
+
+    /// `remove_at(index)`, returning the removed element
+    pub fn remove_at(&mut self, index: &Value, span: Span) -> Result<Value, RuntimeError> {
+        let slot = position(index, self.elements.len(), false, span)?;
+        Ok(self.elements.remove(slot))
+    }
+
+    /// Position of the first element equal to `value`
+    pub fn index_of(&self, value: &Value) -> Option<usize> {
+        self.elements.iter().position(|element| element == value)
+    }
+
+    /// `sort()`: numbers, texts or booleans in ascending order
+    pub fn sort(&mut self, span: Span) -> Result<(), RuntimeError> {
+        if let Some(value) = self.elements.iter().find(|value| compare(value, value).is_none()) {
+            return Err(invalid(format!("cannot sort a coop holding `{}` values", value.type_name()), span));
+        }
+        if let Some(pair) = self.elements.windows(2).find(|pair| compare(&pair[0], &pair[1]).is_none()) {
+            return Err(invalid(
+                format!("cannot sort a coop mixing `{}` and `{}`", pair[0].type_name(), pair[1].type_name()),
+                span,
+            ));
+        }
+        self.elements.sort_by(|a, b| compare(a, b).unwrap_or(Ordering::Equal));
+        Ok(())
+    }
+
+    /// `slice(start, end)`: a new coop of the elements from `start` up to,
+    /// but not including, `end`
+    pub fn slice(&self, start: &Value, end: &Value, span: Span) -> Result<Coop, RuntimeError> {
+        let length = self.elements.len();
+        let (first, last) = (position(start, length, true, span)?, position(end, length, true, span)?);
+        if first > last {
+            return Err(invalid(format!("slice start {} is after its end {}", first, last), span));
+        }
+        Ok(Coop {
+            element: self.element,
+            elements: self.elements[first..last].to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbers(values: &[i64]) -> Coop {
+        Coop::typed(ElementType::Num, values.iter().map(|value| Value::Num(*value)).collect())
+    }
+
+    #[test]
+    fn test_element_type_is_enforced() {
+        let span = Span::new(3, 1, 6);
+        let mut counts = numbers(&[5, 10]);
+        assert_eq!(
+            counts.push(Value::text("cow"), span),
+            Err(RuntimeError::ElementTypeMismatch {
+                value: "\"cow\"".to_string(),
+                container: "coop 'num'".to_string(),
+                span,
+            })
+        );
+        assert!(counts.insert(&Value::Num(2), Value::Num(3), span).is_ok());
+        assert_eq!(counts.elements, vec![Value::Num(5), Value::Num(10), Value::Num(3)]);
+
+        let mut mixed = Coop::new(vec![Value::Num(1), Value::text("cow")]);
+        assert!(mixed.declare(ElementType::Num, span).is_err());
+        assert!(mixed.declare(ElementType::Text, span).is_err());
+        assert_eq!(mixed.element, None);
+    }
+
+    #[test]
+    fn test_indexes() {
+        let span = Span::new(1, 1, 1);
+        let mut counts = numbers(&[3, 1, 2]);
+        assert_eq!(counts.get(&Value::Num(-1), span), Err(RuntimeError::NegativeIndex { index: -1, span }));
+        assert_eq!(
+            counts.insert(&Value::Num(4), Value::Num(0), span),
+            Err(RuntimeError::IndexOutOfRange { index: 4, length: 3, span })
+        );
+        assert_eq!(counts.remove_at(&Value::Num(0), span), Ok(Value::Num(3)));
+        assert_eq!(counts.slice(&Value::Num(0), &Value::Num(2), span).unwrap().elements.len(), 2);
+        assert!(counts.slice(&Value::Num(2), &Value::Num(1), span).is_err());
+
+        let mut mixed = Coop::new(vec![Value::Num(1), Value::text("cow")]);
+        assert!(mixed.sort(span).is_err());
+        let mut decs = Coop::new(vec![Value::Dec(2.5), Value::Num(1), Value::Num(2)]);
+        decs.sort(span).unwrap();
+        assert_eq!(decs.elements, vec![Value::Num(1), Value::Num(2), Value::Dec(2.5)]);
+    }
+}
@@ -0,0 +1,145 @@
+use std::fmt;
+
+use crate::utils::diagnostics::Diagnostic;
+use crate::utils::tokens::Span;
+
+/// # RuntimeError
+/// Errors that stop a running program. Each points at the expression that
+/// failed.
+/// ## Currently 12 variants
+/// - IndexOutOfRange { index, length, span }: `animals[5]` on a shorter coop
+/// - NegativeIndex { index, span }: `animals[-1]`; positions start at 0
+/// - ElementTypeMismatch { value, container, span }: e.g. adding `"cow"` to a `coop 'num'`
+/// - MissingKey { key, span }: `inventory["pig"]` without a `"pig"` entry
+/// - DivisionByZero { span }: `/`, `//` or `%` by zero
+/// - Overflow { span }: a `num` result outside the 64-bit range
+/// - ConversionFailed { value, target, span }: e.g. `to_num("cow")`
+/// - UndefinedName { name, span }: a variable or function that was never declared
+/// - InvalidOperation { message, span }: an operation the value does not
+///   support, for programs the type checker could not fully check
+/// - RecursionLimit { span }: too many nested `farmfunction` calls
+/// - Output { message, kind }: `moo` could not write its output
+/// - Input { message, span }: `listen` could not read a line
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeError {
+    IndexOutOfRange { index: i64, length: usize, span: Span },
+    NegativeIndex { index: i64, span: Span },
+    ElementTypeMismatch { value: String, container: String, span: Span },
+    MissingKey { key: String, span: Span },
+    DivisionByZero { span: Span },
+    Overflow { span: Span },
+    ConversionFailed { value: String, target: String, span: Span },
+    UndefinedName { name: String, span: Span },
+    InvalidOperation { message: String, span: Span },
+    RecursionLimit { span: Span },
+    Output { message: String, kind: std::io::ErrorKind },
+    Input { message: String, span: Span },
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeError::IndexOutOfRange { index, length, span } => write!(
+                f,
+                "Index {} is out of range for a coop of {} element(s) at {}",
+                index, length, span
+            ),
+            RuntimeError::NegativeIndex { index, span } => write!(
+                f,
+                "Index {} is negative at {}; coop positions start at 0",
+                index, span
+            ),
+            RuntimeError::ElementTypeMismatch { value, container, span } => {
+                write!(f, "A `{}` cannot hold {} at {}", container, value, span)
+            }
+            RuntimeError::MissingKey { key, span } => write!(f, "Key {} is not in the barn_map at {}", key, span),
+            RuntimeError::DivisionByZero { span } => write!(f, "Division by zero at {}", span),
+            RuntimeError::Overflow { span } => write!(f, "Number too large for `num` at {}", span),
+            RuntimeError::ConversionFailed { value, target, span } => {
+                write!(f, "Cannot convert {} to `{}` at {}", value, target, span)
+            }
+            RuntimeError::UndefinedName { name, span } => write!(f, "`{}` is not defined at {}", name, span),
+            RuntimeError::InvalidOperation { message, span } => write!(f, "{} at {}", message, span),
+            RuntimeError::RecursionLimit { span } => write!(
+                f,
+                "Too many nested farmfunction calls at {}; check that the recursion stops",
+                span
+            ),
+            RuntimeError::Output { message, .. } => write!(f, "Could not write output: {}", message),
+            RuntimeError::Input { message, span } => write!(f, "Could not read input at {}: {}", span, message),
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+impl RuntimeError {
+    /// Where the error happened, if it points at source code
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            RuntimeError::Output { .. } => None,
+            RuntimeError::IndexOutOfRange { span, .. }
+            | RuntimeError::NegativeIndex { span, .. }
+            | RuntimeError::ElementTypeMismatch { span, .. }
+            | RuntimeError::MissingKey { span, .. }
+            | RuntimeError::DivisionByZero { span }
+            | RuntimeError::Overflow { span }
+            | RuntimeError::ConversionFailed { span, .. }
+            | RuntimeError::UndefinedName { span, .. }
+            | RuntimeError::InvalidOperation { span, .. }
+            | RuntimeError::Input { span, .. }
+            | RuntimeError::RecursionLimit { span } => Some(*span),
+        }
+    }
+
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let span = self.span().unwrap_or_default();
+        Diagnostic::error_at(self.to_string(), span)
+    }
+}
+
+/// # ObjectFileError
+/// Reasons a compiled `.moob` file cannot be loaded.
+/// ## Currently 6 variants
+/// - NotAnObjectFile: the file does not start with the `.moob` magic bytes
+/// - UnsupportedFormat { found, supported }: a bytecode layout this loader does not read
+/// - IncompatibleVersion { found, expected }: compiled for another MooLang version
+/// - ChecksumMismatch { found, expected }: the contents were changed or damaged
+/// - Truncated: the file ends in the middle of its contents
+/// - Corrupt { message }: the contents decode but do not form a valid program
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjectFileError {
+    NotAnObjectFile,
+    UnsupportedFormat { found: u16, supported: u16 },
+    IncompatibleVersion { found: String, expected: String },
+    ChecksumMismatch { found: u32, expected: u32 },
+    Truncated,
+    Corrupt { message: String },
+}
+
+impl fmt::Display for ObjectFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjectFileError::NotAnObjectFile => write!(f, "Not a compiled MooLang (.moob) file"),
+            ObjectFileError::UnsupportedFormat { found, supported } => write!(
+                f,
+                "Bytecode format {} is not supported (this compiler reads format {}); recompile the program",
+                found, supported
+            ),
+            ObjectFileError::IncompatibleVersion { found, expected } => write!(
+                f,
+                "Compiled for MooLang {} but this is MooLang {}; recompile the program from its source",
+                found, expected
+            ),
+            ObjectFileError::ChecksumMismatch { found, expected } => write!(
+                f,
+                "Checksum {:08x} does not match the recorded {:08x}; the file is damaged",
+                found, expected
+            ),
+            ObjectFileError::Truncated => write!(f, "The file ends unexpectedly; it is incomplete"),
+            ObjectFileError::Corrupt { message } => write!(f, "Invalid bytecode: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ObjectFileError {}
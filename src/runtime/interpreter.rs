@@ -0,0 +1,546 @@
+//! Tree-walking interpreter: runs a checked program straight from its AST.
+
+use std::cell::RefCell;
+use std::io::{BufRead, Write};
+use std::rc::Rc;
+
+use crate::parser::ast::{Expr, ExprKind, FunctionDecl, Program, StallDecl, Stmt, StmtKind, UnaryOp, SELF};
+use crate::runtime::environment::Environment;
+use crate::runtime::barn_map::BarnMap;
+use crate::runtime::errors::RuntimeError;
+use crate::runtime::operations::{
+    binary, call_method, coerce, default_value, element, elements, entries, get_field, invalid, negate, no_method,
+    set_element, set_field, update,
+};
+use crate::runtime::range::Range;
+use crate::runtime::stall::{Instance, Stall};
+use crate::runtime::stdlib::Stdlib;
+use crate::runtime::value::{Function, Value};
+use crate::semantic::types::{Builtin, Method};
+use crate::utils::tokens::Span;
+
+/// Nested `farmfunction` calls allowed before the program is stopped, so
+/// runaway recursion ends with an error instead of a crash
+pub const MAX_CALL_DEPTH: usize = 1000;
+
+/// What the enclosing code should do after a statement
+enum Flow {
+    Next,
+    /// `leave` the innermost loop
+    Break,
+    /// `skip` to the innermost loop's next round
+    Continue,
+    Return(Value),
+}
+
+/// Something that can be assigned to or updated with `++` / `--`
+enum Place {
+    Variable(String),
+    /// `object[index]`
+    Element { object: Value, index: Value },
+    /// `object.name`
+    Field { object: Value, name: String },
+}
+
+pub struct Interpreter<'a> {
+    /// Where `moo` writes
+    out: &'a mut dyn Write,
+    globals: Rc<RefCell<Environment>>,
+    depth: usize,
+    stdlib: Stdlib<'a>,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(out: &'a mut dyn Write) -> Self {
+        Self {
+            out,
+            globals: Environment::global(),
+            depth: 0,
+            stdlib: Stdlib::new(),
+        }
+    }
+
+    /// Read `listen` lines from `input` instead of standard input
+    pub fn with_input(mut self, input: impl BufRead + 'a) -> Self {
+        self.stdlib = self.stdlib.with_input(input);
+        self
+    }
+
+    /// Seed `random`, so the program prints the same output on every run
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.stdlib = self.stdlib.with_seed(seed);
+        self
+    }
+
+    /// Run every top-level statement, stopping at the first runtime error
+    pub fn run(&mut self, program: &Program) -> Result<(), RuntimeError> {
+        let globals = Rc::clone(&self.globals);
+        self.block(&program.statements, &globals)?;
+        Ok(())
+    }
+
+    // ================================
+    // Statements
+    // ================================
+
+    /// Run `statements` in `env`. Functions and stalls are declared first so
+    /// they can be used before the line that defines them.
+    fn block(&mut self, statements: &[Stmt], env: &Rc<RefCell<Environment>>) -> Result<Flow, RuntimeError> {
+        for stmt in statements {
+            match &stmt.kind {
+                StmtKind::Function(declaration) => {
+                    env.borrow_mut().define(&declaration.name, function(declaration.clone(), env));
+                }
+                StmtKind::Stall(declaration) => {
+                    env.borrow_mut().define(&declaration.name, stall(declaration, env));
+                }
+                _ => {}
+            }
+        }
+        for stmt in statements {
+            match self.execute(stmt, env)? {
+                Flow::Next => {}
+                flow => return Ok(flow),
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    fn execute(&mut self, stmt: &Stmt, env: &Rc<RefCell<Environment>>) -> Result<Flow, RuntimeError> {
+        match &stmt.kind {
+            StmtKind::VarDecl { ty, name, initializer } => {
+                let value = match initializer {
+                    Some(initializer) => coerce(ty, self.evaluate(initializer, env)?, stmt.span)?,
+                    None => default_value(ty),
+                };
+                env.borrow_mut().define(name, value);
+            }
+            StmtKind::Assign { target, value } => {
+                let value = self.evaluate(value, env)?;
+                let place = self.place(target, env)?;
+                self.write_place(place, value, env, target.span)?;
+            }
+            StmtKind::CompoundAssign { target, op, value } => {
+                let place = self.place(target, env)?;
+                let old = self.read_place(&place, env, target.span)?;
+                let value = self.evaluate(value, env)?;
+                let new = binary(*op, &old, &value, stmt.span)?;
+                self.write_place(place, new, env, target.span)?;
+            }
+            StmtKind::MultiAssign { targets, values } => {
+                let values = self.arguments(values, env)?;
+                for (target, value) in targets.iter().zip(values) {
+                    let place = self.place(target, env)?;
+                    self.write_place(place, value, env, target.span)?;
+                }
+            }
+            StmtKind::Print { values } => {
+                let mut line = Vec::with_capacity(values.len());
+                for value in values {
+                    line.push(self.evaluate(value, env)?.to_string());
+                }
+                writeln!(self.out, "{}", line.join(" ")).map_err(|error| RuntimeError::Output {
+                    message: error.to_string(),
+                    kind: error.kind(),
+                })?;
+            }
+            StmtKind::If { branches, else_branch } => {
+                for (condition, body) in branches {
+                    if self.condition(condition, env)? {
+                        return self.block(body, &Environment::child(env));
+                    }
+                }
+                if let Some(body) = else_branch {
+                    return self.block(body, &Environment::child(env));
+                }
+            }
+            StmtKind::While { condition, body } => {
+                while self.condition(condition, env)? {
+                    match self.block(body, &Environment::child(env))? {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        Flow::Next | Flow::Continue => {}
+                    }
+                }
+            }
+            StmtKind::For { variable, value: None, iterable, body } => {
+                let items = self.evaluate(iterable, env)?;
+                for item in elements(&items, iterable.span)? {
+                    let scope = Environment::child(env);
+                    scope.borrow_mut().define(variable, item);
+                    match self.block(body, &scope)? {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        Flow::Next | Flow::Continue => {}
+                    }
+                }
+            }
+            StmtKind::For { variable, value: Some(value_name), iterable, body } => {
+                let map = self.evaluate(iterable, env)?;
+                for (key, value) in entries(&map, iterable.span)? {
+                    let scope = Environment::child(env);
+                    scope.borrow_mut().define(variable, key);
+                    scope.borrow_mut().define(value_name, value);
+                    match self.block(body, &scope)? {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        Flow::Next | Flow::Continue => {}
+                    }
+                }
+            }
+            // Declared when the enclosing block started
+            StmtKind::Function(_) | StmtKind::Stall(_) => {}
+            StmtKind::Return(value) => {
+                let value = match value {
+                    Some(value) => self.evaluate(value, env)?,
+                    None => Value::Nothing,
+                };
+                return Ok(Flow::Return(value));
+            }
+            StmtKind::Break => return Ok(Flow::Break),
+            StmtKind::Continue => return Ok(Flow::Continue),
+            StmtKind::Expression(expr) => {
+                self.evaluate(expr, env)?;
+            }
+            StmtKind::Bring { path } => {
+                return Err(invalid(format!("cannot run `{}` before it is loaded", path), stmt.span))
+            }
+            StmtKind::Error => return Err(invalid("cannot run a statement with a syntax error", stmt.span)),
+        }
+        Ok(Flow::Next)
+    }
+
+    fn condition(&mut self, condition: &Expr, env: &Rc<RefCell<Environment>>) -> Result<bool, RuntimeError> {
+        match self.evaluate(condition, env)? {
+            Value::Boolean(value) => Ok(value),
+            other => Err(invalid(
+                format!("condition must be `boolean`, found `{}`", other.type_name()),
+                condition.span,
+            )),
+        }
+    }
+
+    // ================================
+    // Expressions
+    // ================================
+
+    fn evaluate(&mut self, expr: &Expr, env: &Rc<RefCell<Environment>>) -> Result<Value, RuntimeError> {
+        match &expr.kind {
+            ExprKind::Integer(value) => Ok(Value::Num(*value)),
+            ExprKind::Decimal(value) => Ok(Value::Dec(*value)),
+            ExprKind::Text(text) => Ok(Value::text(text)),
+            ExprKind::Boolean(value) => Ok(Value::Boolean(*value)),
+            ExprKind::Variable(name) => env.borrow().get(name).ok_or_else(|| RuntimeError::UndefinedName {
+                name: name.clone(),
+                span: expr.span,
+            }),
+            ExprKind::CoopLiteral(elements) => {
+                let mut values = Vec::with_capacity(elements.len());
+                for element in elements {
+                    values.push(self.evaluate(element, env)?);
+                }
+                Ok(Value::coop(values))
+            }
+            ExprKind::BarnMapLiteral(entries) => {
+                let mut map = BarnMap::new();
+                for (key, value) in entries {
+                    let key = self.evaluate(key, env)?;
+                    let value = self.evaluate(value, env)?;
+                    map.put(key, value);
+                }
+                Ok(Value::barn_map(map))
+            }
+            ExprKind::Range(args) => {
+                let bounds = self.arguments(args, env)?;
+                Range::new(&bounds, expr.span).map(Value::Range)
+            }
+            ExprKind::Grouping(inner) => self.evaluate(inner, env),
+            ExprKind::Unary { op: UnaryOp::Negate, operand } => negate(&self.evaluate(operand, env)?, expr.span),
+            ExprKind::Binary { op, left, right } => {
+                let left = self.evaluate(left, env)?;
+                let right = self.evaluate(right, env)?;
+                binary(*op, &left, &right, expr.span)
+            }
+            ExprKind::Update { op, prefix, target } => {
+                let place = self.place(target, env)?;
+                let old = self.read_place(&place, env, expr.span)?;
+                let new = update(*op, &old, expr.span)?;
+                self.write_place(place, new.clone(), env, expr.span)?;
+                Ok(if *prefix { new } else { old })
+            }
+            ExprKind::Call { callee, args, builtin } => self.call(callee, args, *builtin, env, expr.span),
+            ExprKind::Index { object, index } => {
+                let object = self.evaluate(object, env)?;
+                let index = self.evaluate(index, env)?;
+                element(&object, &index, expr.span)
+            }
+            ExprKind::Member { object, name } => get_field(&self.evaluate(object, env)?, name, expr.span),
+            ExprKind::Error => Err(invalid("cannot run an expression with a syntax error", expr.span)),
+        }
+    }
+
+    fn arguments(&mut self, args: &[Expr], env: &Rc<RefCell<Environment>>) -> Result<Vec<Value>, RuntimeError> {
+        let mut values = Vec::with_capacity(args.len());
+        for arg in args {
+            values.push(self.evaluate(arg, env)?);
+        }
+        Ok(values)
+    }
+
+    fn call(
+        &mut self,
+        callee: &Expr,
+        args: &[Expr],
+        builtin: Option<&str>,
+        env: &Rc<RefCell<Environment>>,
+        span: Span,
+    ) -> Result<Value, RuntimeError> {
+        if let ExprKind::Member { object, name } = &callee.kind {
+            let object = self.evaluate(object, env)?;
+            let stall_method = match &object {
+                Value::Instance(instance) => instance.borrow().stall.method(name).cloned(),
+                _ => None,
+            };
+            if let Some(Value::Function(method)) = stall_method {
+                let args = self.arguments(args, env)?;
+                return self.call_function(&method, args, Some(object), span);
+            }
+            let method = builtin.and_then(Method::lookup).ok_or_else(|| no_method(&object, name, span))?;
+            let args = self.arguments(args, env)?;
+            return call_method(&object, method, &args, span);
+        }
+        if let ExprKind::Variable(name) = &callee.kind {
+            // A variable or function of the same name hides the builtin
+            let builtin = builtin.and_then(Builtin::lookup).filter(|_| env.borrow().get(name).is_none());
+            if let Some(builtin) = builtin {
+                let args = self.arguments(args, env)?;
+                return self.stdlib.call(builtin, &args, span);
+            }
+        }
+        match self.evaluate(callee, env)? {
+            Value::Function(function) => {
+                let args = self.arguments(args, env)?;
+                self.call_function(&function, args, None, span)
+            }
+            Value::Stall(stall) => {
+                let args = self.arguments(args, env)?;
+                let instance = Value::Instance(Rc::new(RefCell::new(Instance::new(Rc::clone(&stall)))));
+                if let Value::Function(init) = &stall.init {
+                    self.call_function(init, args, Some(instance.clone()), span)?;
+                }
+                Ok(instance)
+            }
+            other => Err(invalid(format!("`{}` cannot be called", other.type_name()), callee.span)),
+        }
+    }
+
+    /// Call `function`; methods of a stall also get the instance they were
+    /// called on as `receiver`
+    fn call_function(
+        &mut self,
+        function: &Function,
+        args: Vec<Value>,
+        receiver: Option<Value>,
+        span: Span,
+    ) -> Result<Value, RuntimeError> {
+        let declaration = &function.declaration;
+        if args.len() != declaration.params.len() {
+            return Err(invalid(
+                format!(
+                    "`{}` takes {} argument(s) but {} were given",
+                    declaration.name,
+                    declaration.params.len(),
+                    args.len()
+                ),
+                span,
+            ));
+        }
+        if self.depth >= MAX_CALL_DEPTH {
+            return Err(RuntimeError::RecursionLimit { span });
+        }
+
+        let scope = Environment::child(&function.closure);
+        if let Some(receiver) = receiver {
+            scope.borrow_mut().define(SELF, receiver);
+        }
+        for (param, arg) in declaration.params.iter().zip(args) {
+            let arg = coerce(&param.ty, arg, param.span)?;
+            scope.borrow_mut().define(&param.name, arg);
+        }
+        self.depth += 1;
+        let flow = self.block(&declaration.body, &scope);
+        self.depth -= 1;
+
+        let value = match flow? {
+            Flow::Return(value) => value,
+            // The parser keeps `leave` and `skip` inside loops
+            Flow::Next | Flow::Break | Flow::Continue => Value::Nothing,
+        };
+        match &declaration.return_type {
+            Some(ty) => coerce(ty, value, span),
+            None => Ok(value),
+        }
+    }
+
+    // ================================
+    // Assignment targets
+    // ================================
+
+    fn place(&mut self, target: &Expr, env: &Rc<RefCell<Environment>>) -> Result<Place, RuntimeError> {
+        match &target.kind {
+            ExprKind::Variable(name) => Ok(Place::Variable(name.clone())),
+            ExprKind::Index { object, index } => Ok(Place::Element {
+                object: self.evaluate(object, env)?,
+                index: self.evaluate(index, env)?,
+            }),
+            ExprKind::Member { object, name } => Ok(Place::Field {
+                object: self.evaluate(object, env)?,
+                name: name.clone(),
+            }),
+            ExprKind::Grouping(inner) => self.place(inner, env),
+            _ => Err(invalid("only variables, elements and fields can be assigned", target.span)),
+        }
+    }
+
+    fn read_place(&self, place: &Place, env: &Rc<RefCell<Environment>>, span: Span) -> Result<Value, RuntimeError> {
+        match place {
+            Place::Variable(name) => env.borrow().get(name).ok_or_else(|| RuntimeError::UndefinedName {
+                name: name.clone(),
+                span,
+            }),
+            Place::Element { object, index } => element(object, index, span),
+            Place::Field { object, name } => get_field(object, name, span),
+        }
+    }
+
+    fn write_place(
+        &self,
+        place: Place,
+        value: Value,
+        env: &Rc<RefCell<Environment>>,
+        span: Span,
+    ) -> Result<(), RuntimeError> {
+        match place {
+            Place::Variable(name) => {
+                if env.borrow_mut().assign(&name, value) {
+                    Ok(())
+                } else {
+                    Err(RuntimeError::UndefinedName { name, span })
+                }
+            }
+            Place::Element { object, index } => set_element(&object, index, value, span),
+            Place::Field { object, name } => set_field(&object, &name, value, span),
+        }
+    }
+}
+
+/// A `farmfunction` value closing over `env`
+fn function(declaration: FunctionDecl, env: &Rc<RefCell<Environment>>) -> Value {
+    Value::Function(Rc::new(Function {
+        declaration: Rc::new(declaration),
+        closure: Rc::clone(env),
+    }))
+}
+
+/// The constructor value of a stall declared in `env`
+fn stall(declaration: &StallDecl, env: &Rc<RefCell<Environment>>) -> Value {
+    let stall = Stall {
+        name: declaration.name.clone(),
+        fields: declaration.fields.iter().map(|field| (field.name.clone(), field.ty.clone())).collect(),
+        methods: declaration.methods()
+            .map(|method| (method.name.clone(), function(method.clone(), env)))
+            .collect(),
+        init: function(declaration.init(), env),
+    };
+    Value::Stall(Rc::new(stall))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_source;
+    use crate::toml_config::language_config::{LanguageKeywordManager, MooConfig};
+
+    fn run(source: &str) -> Result<String, RuntimeError> {
+        let manager = LanguageKeywordManager::from_config(MooConfig::from_default());
+        let program = parse_source(source, &manager, "en").unwrap();
+        let mut out = Vec::new();
+        Interpreter::new(&mut out).run(&program)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_output_and_arithmetic() {
+        let source = r#"
+num count = 3
+dec price = 2
+moo "Count is:", count, price
+moo 7 / 2, 7 // 2, -7 // 2, -7 % 2, 7.5 // 2, count * price
+moo count is 3, count is not 3, 1 is 1.0, "a" + "b"
+coop 'text' animals = ["cow", "pig"]
+barn_map 'text':'num' inventory = {"cow": 5}
+inventory["pig"] = 2
+inventory.put("cow", 6)
+moo animals, inventory, inventory.collect(), to_num(2.9), to_text(1.5) + "!"
+"#;
+        assert_eq!(
+            run(source).unwrap(),
+            "Count is: 3 2.0\n3.5 3 -4 1 3.0 6.0\ntrue false true ab\n[\"cow\", \"pig\"] {\"cow\": 6, \"pig\": 2} 2 2 1.5!\n"
+        );
+    }
+
+    #[test]
+    fn test_control_flow_and_functions() {
+        let source = r#"
+farmfunction 'num' factorial(num n) {
+    if (n < 2) {
+        return 1
+    }
+    return n * factorial(n - 1)
+}
+farmfunction addAnimal(coop 'text' animals, text animal) {
+    animals.plant(animal)
+}
+coop 'text' animals = ["cow"]
+addAnimal(animals, "pig")
+num total = 0
+for index in barn(animals.collect()) {
+    moo index, animals[index]
+    total++
+}
+for value in barn(10, 0, -4) {
+    moo value
+}
+while (total < 5) {
+    total = total + 2
+}
+moo factorial(10), total
+"#;
+        assert_eq!(run(source).unwrap(), "0 cow\n1 pig\n10\n6\n2\n3628800 6\n");
+    }
+
+    #[test]
+    fn test_runtime_errors_point_at_the_failing_expression() {
+        let errors = [
+            ("coop 'num' xs = [1, 2]\nmoo xs[2]", "Index 2 is out of range for a coop of 2 element(s) at 2:5"),
+            ("barn_map 'text':'num' m = {}\nmoo m[\"pig\"]", "Key \"pig\" is not in the barn_map at 2:5"),
+            ("num zero = 0\nmoo 1 // zero", "Division by zero at 2:5"),
+            ("moo to_num(\"cow\")", "Cannot convert \"cow\" to `num` at 1:5"),
+        ];
+        for (source, message) in errors {
+            assert_eq!(run(source).unwrap_err().to_string(), message, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_runaway_recursion_is_stopped() {
+        let source = "farmfunction forever(num n) {\n    forever(n + 1)\n}\nforever(0)";
+        let error = std::thread::Builder::new()
+            .stack_size(256 * 1024 * 1024)
+            .spawn(move || run(source).unwrap_err())
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(error, RuntimeError::RecursionLimit { span: Span::new(2, 5, 14) });
+    }
+}
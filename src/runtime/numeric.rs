@@ -0,0 +1,98 @@
+//! Arithmetic rules shared by every way of running MooLang.
+//!
+//! `//` is floored division: the quotient rounds toward negative infinity, so
+//! `-7 // 2` is `-4`. `%` is the matching floored remainder, which takes the
+//! sign of the divisor: `-7 % 2` is `1`. Together they always satisfy
+//! `a == (a // b) * b + a % b`. Both work on `dec` too; `7.5 // 2` is `3.0`.
+//!
+//! Every function returns `None` where the result is undefined (dividing by
+//! zero) or does not fit in a `num`.
+
+/// `a // b` for `num`
+pub fn floor_div(a: i64, b: i64) -> Option<i64> {
+    let quotient = a.checked_div(b)?;
+    if (a % b != 0) && ((a < 0) != (b < 0)) {
+        quotient.checked_sub(1)
+    } else {
+        Some(quotient)
+    }
+}
+
+/// `a % b` for `num`
+pub fn floor_mod(a: i64, b: i64) -> Option<i64> {
+    let remainder = a.checked_rem(b)?;
+    if remainder != 0 && ((remainder < 0) != (b < 0)) {
+        Some(remainder + b)
+    } else {
+        Some(remainder)
+    }
+}
+
+/// `a // b` for `dec`
+pub fn floor_div_dec(a: f64, b: f64) -> Option<f64> {
+    (b != 0.0).then(|| (a / b).floor())
+}
+
+/// `a % b` for `dec`
+pub fn floor_mod_dec(a: f64, b: f64) -> Option<f64> {
+    (b != 0.0).then(|| a - b * (a / b).floor())
+}
+
+/// `a / b`, which always produces a `dec`
+pub fn divide(a: f64, b: f64) -> Option<f64> {
+    (b != 0.0).then(|| a / b)
+}
+
+/// `to_num` of a `dec`: rounds toward zero
+pub fn dec_to_num(value: f64) -> Option<i64> {
+    let truncated = value.trunc();
+    // i64::MAX is not exactly representable; 2^63 is the first value past it
+    (truncated.is_finite() && truncated >= i64::MIN as f64 && truncated < 9_223_372_036_854_775_808.0)
+        .then_some(truncated as i64)
+}
+
+/// `to_num` of a `text`: whole numbers, optionally signed, surrounding
+/// spaces ignored. `"2.5"` is rejected; convert it with `to_dec` first.
+pub fn parse_num(text: &str) -> Option<i64> {
+    text.trim().parse().ok()
+}
+
+/// `to_dec` of a `text`
+pub fn parse_dec(text: &str) -> Option<f64> {
+    text.trim().parse().ok().filter(|value: &f64| value.is_finite())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_floored_division_and_remainder() {
+        let cases = [(7, 2, 3, 1), (-7, 2, -4, 1), (7, -2, -4, -1), (-7, -2, 3, -1), (6, 3, 2, 0), (-6, 3, -2, 0)];
+        for (a, b, quotient, remainder) in cases {
+            assert_eq!(floor_div(a, b), Some(quotient), "{} // {}", a, b);
+            assert_eq!(floor_mod(a, b), Some(remainder), "{} % {}", a, b);
+            assert_eq!(a, quotient * b + remainder);
+        }
+        assert_eq!(floor_div(1, 0), None);
+        assert_eq!(floor_mod(1, 0), None);
+        assert_eq!(floor_div(i64::MIN, -1), None);
+
+        assert_eq!(floor_div_dec(7.5, 2.0), Some(3.0));
+        assert_eq!(floor_div_dec(-7.5, 2.0), Some(-4.0));
+        assert_eq!(floor_mod_dec(-7.5, 2.0), Some(0.5));
+        assert_eq!(floor_div_dec(1.0, 0.0), None);
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(dec_to_num(2.9), Some(2));
+        assert_eq!(dec_to_num(-2.9), Some(-2));
+        assert_eq!(dec_to_num(f64::NAN), None);
+        assert_eq!(dec_to_num(1e19), None);
+        assert_eq!(parse_num(" 42 "), Some(42));
+        assert_eq!(parse_num("2.5"), None);
+        assert_eq!(parse_dec("2.5"), Some(2.5));
+        assert_eq!(parse_dec("inf"), None);
+    }
+}
@@ -0,0 +1,563 @@
+//! Compiled `.moob` object files.
+//!
+//! A `.moob` file holds a `CompiledProgram` so that it can be run without
+//! its source. It starts with a header:
+//!
+//! ```text
+//! magic     4 bytes   "MOOB"
+//! format    u16       layout of the rest of the file, `FORMAT_VERSION`
+//! version   string    `LanguageSettings.version` of the compiling configuration
+//! language  string    keyword pack the source was written in, e.g. `en+es`
+//! checksum  u32       CRC-32 of the payload
+//! ```
+//!
+//! The payload that follows is the global names and then the main function.
+//! A function is its name, arity, cell count, local count, captures, code,
+//! constants and line table, with nested functions stored inside the
+//! constants. Each line table entry names the brought file it points into,
+//! or is empty for the compiled file itself. A stall constant is its name,
+//! its fields with their types and its method names. Integers are
+//! little-endian and strings are a `u32` byte length followed by UTF-8.
+//!
+//! Loading rejects files for another format or MooLang version, checks the
+//! checksum and verifies the indices the VM trusts the compiler to get
+//! right: constants, cells, upvalues, globals, local slots and jumps, which
+//! must land on an instruction. A damaged file is an error instead of a
+//! crash.
+
+use std::rc::Rc;
+
+use crate::parser::ast::TypeExpr;
+use crate::runtime::bytecode::{
+    Capture, Chunk, CompiledProgram, Constant, FunctionProto, OpCode, StallProto, BUILTINS, ELEMENT_TYPES, METHODS,
+    NO_METHOD,
+};
+use crate::runtime::errors::ObjectFileError;
+use crate::utils::tokens::{file_name, Span};
+
+pub const EXTENSION: &str = "moob";
+pub const MAGIC: &[u8; 4] = b"MOOB";
+/// Bumped whenever the payload layout or the instruction set changes
+pub const FORMAT_VERSION: u16 = 9;
+
+/// What a `.moob` file records about how it was compiled
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectHeader {
+    pub format: u16,
+    pub version: String,
+    pub language: String,
+    pub checksum: u32,
+}
+
+/// Serialise `program`, compiled under language `version` from source in
+/// the keyword pack `language`
+pub fn write_object(program: &CompiledProgram, version: &str, language: &str) -> Vec<u8> {
+    let mut payload = Writer::default();
+    payload.u32(program.globals.len() as u32);
+    for global in &program.globals {
+        payload.string(global);
+    }
+    payload.function(&program.main);
+
+    let mut out = Writer::default();
+    out.bytes.extend_from_slice(MAGIC);
+    out.u16(FORMAT_VERSION);
+    out.string(version);
+    out.string(language);
+    out.u32(crc32(&payload.bytes));
+    out.bytes.extend_from_slice(&payload.bytes);
+    out.bytes
+}
+
+/// Only the header of a `.moob` file
+pub fn read_header(bytes: &[u8]) -> Result<ObjectHeader, ObjectFileError> {
+    Reader::new(bytes).header()
+}
+
+/// Load a `.moob` file for a loader running language `version`
+pub fn read_object(bytes: &[u8], version: &str) -> Result<(ObjectHeader, CompiledProgram), ObjectFileError> {
+    let mut reader = Reader::new(bytes);
+    let header = reader.header()?;
+    if !compatible(&header.version, version) {
+        return Err(ObjectFileError::IncompatibleVersion {
+            found: header.version,
+            expected: version.to_string(),
+        });
+    }
+    let checksum = crc32(&bytes[reader.position..]);
+    if checksum != header.checksum {
+        return Err(ObjectFileError::ChecksumMismatch { found: checksum, expected: header.checksum });
+    }
+
+    let globals = (0..reader.u32()?).map(|_| reader.string()).collect::<Result<Vec<_>, _>>()?;
+    let main = reader.function()?;
+    if reader.position != bytes.len() {
+        return Err(corrupt("unexpected data after the program"));
+    }
+    verify(&main, None, globals.len())?;
+    Ok((header, CompiledProgram { main, globals }))
+}
+
+/// A file compiled under `found` runs under `current` when both have the
+/// same major version and `found` is not newer. Versions that are not
+/// `major.minor.patch` must match exactly.
+fn compatible(found: &str, current: &str) -> bool {
+    fn parse(version: &str) -> Option<Vec<u64>> {
+        let parts: Vec<u64> = version.split('.').map(|part| part.parse().ok()).collect::<Option<_>>()?;
+        (parts.len() == 3).then_some(parts)
+    }
+    match (parse(found), parse(current)) {
+        (Some(found), Some(current)) => found[0] == current[0] && found <= current,
+        _ => found == current,
+    }
+}
+
+fn corrupt(message: impl Into<String>) -> ObjectFileError {
+    ObjectFileError::Corrupt { message: message.into() }
+}
+
+/// CRC-32 (IEEE), as used by zip and PNG
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+const CAPTURE_CELL: u8 = 0;
+const CAPTURE_UPVALUE: u8 = 1;
+const CONSTANT_NUM: u8 = 0;
+const CONSTANT_DEC: u8 = 1;
+const CONSTANT_TEXT: u8 = 2;
+const CONSTANT_FUNCTION: u8 = 3;
+const CONSTANT_STALL: u8 = 4;
+const TYPE_TEXT: u8 = 0;
+const TYPE_NUM: u8 = 1;
+const TYPE_DEC: u8 = 2;
+const TYPE_BOOLEAN: u8 = 3;
+const TYPE_COOP: u8 = 4;
+const TYPE_TYPED_COOP: u8 = 5;
+const TYPE_BARN_MAP: u8 = 6;
+const TYPE_TYPED_BARN_MAP: u8 = 7;
+const TYPE_RANGE: u8 = 8;
+const TYPE_STALL: u8 = 9;
+
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn string(&mut self, value: &str) {
+        self.u32(value.len() as u32);
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    fn function(&mut self, function: &FunctionProto) {
+        self.string(&function.name);
+        self.u16(function.arity);
+        self.u16(function.cells);
+        self.u16(function.locals);
+        self.u32(function.captures.len() as u32);
+        for capture in &function.captures {
+            match capture {
+                Capture::Cell(cell) => {
+                    self.bytes.push(CAPTURE_CELL);
+                    self.u16(*cell);
+                }
+                Capture::Upvalue(upvalue) => {
+                    self.bytes.push(CAPTURE_UPVALUE);
+                    self.u16(*upvalue);
+                }
+            }
+        }
+        self.chunk(&function.chunk);
+    }
+
+    fn stall(&mut self, stall: &StallProto) {
+        self.string(&stall.name);
+        self.u32(stall.fields.len() as u32);
+        for (name, ty) in &stall.fields {
+            self.string(name);
+            self.type_expr(ty);
+        }
+        self.u32(stall.methods.len() as u32);
+        for method in &stall.methods {
+            self.string(method);
+        }
+    }
+
+    fn type_expr(&mut self, ty: &TypeExpr) {
+        match ty {
+            TypeExpr::Text => self.bytes.push(TYPE_TEXT),
+            TypeExpr::Num => self.bytes.push(TYPE_NUM),
+            TypeExpr::Dec => self.bytes.push(TYPE_DEC),
+            TypeExpr::Boolean => self.bytes.push(TYPE_BOOLEAN),
+            TypeExpr::Coop(None) => self.bytes.push(TYPE_COOP),
+            TypeExpr::Coop(Some(element)) => {
+                self.bytes.push(TYPE_TYPED_COOP);
+                self.type_expr(element);
+            }
+            TypeExpr::BarnMap(None) => self.bytes.push(TYPE_BARN_MAP),
+            TypeExpr::BarnMap(Some((key, value))) => {
+                self.bytes.push(TYPE_TYPED_BARN_MAP);
+                self.type_expr(key);
+                self.type_expr(value);
+            }
+            TypeExpr::Range => self.bytes.push(TYPE_RANGE),
+            TypeExpr::Stall(name) => {
+                self.bytes.push(TYPE_STALL);
+                self.string(name);
+            }
+        }
+    }
+
+    fn chunk(&mut self, chunk: &Chunk) {
+        self.u32(chunk.code.len() as u32);
+        self.bytes.extend_from_slice(&chunk.code);
+        self.u32(chunk.constants.len() as u32);
+        for constant in &chunk.constants {
+            match constant {
+                Constant::Num(value) => {
+                    self.bytes.push(CONSTANT_NUM);
+                    self.bytes.extend_from_slice(&value.to_le_bytes());
+                }
+                Constant::Dec(value) => {
+                    self.bytes.push(CONSTANT_DEC);
+                    self.bytes.extend_from_slice(&value.to_bits().to_le_bytes());
+                }
+                Constant::Text(text) => {
+                    self.bytes.push(CONSTANT_TEXT);
+                    self.string(text);
+                }
+                Constant::Function(function) => {
+                    self.bytes.push(CONSTANT_FUNCTION);
+                    self.function(function);
+                }
+                Constant::Stall(stall) => {
+                    self.bytes.push(CONSTANT_STALL);
+                    self.stall(stall);
+                }
+            }
+        }
+        self.u32(chunk.spans.len() as u32);
+        for (offset, span) in &chunk.spans {
+            self.u32(*offset as u32);
+            self.u32(span.line as u32);
+            self.u32(span.column as u32);
+            self.u32(span.length as u32);
+            self.string(span.file.unwrap_or(""));
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], ObjectFileError> {
+        let end = self.position.checked_add(length).filter(|end| *end <= self.bytes.len());
+        let end = end.ok_or(ObjectFileError::Truncated)?;
+        let taken = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ObjectFileError> {
+        Ok(self.take(N)?.try_into().expect("took N bytes"))
+    }
+
+    fn u8(&mut self) -> Result<u8, ObjectFileError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, ObjectFileError> {
+        self.array().map(u16::from_le_bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, ObjectFileError> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn string(&mut self) -> Result<String, ObjectFileError> {
+        let length = self.u32()? as usize;
+        String::from_utf8(self.take(length)?.to_vec()).map_err(|_| corrupt("a name or text is not valid UTF-8"))
+    }
+
+    fn header(&mut self) -> Result<ObjectHeader, ObjectFileError> {
+        if self.bytes.get(..MAGIC.len()) != Some(MAGIC.as_slice()) {
+            return Err(ObjectFileError::NotAnObjectFile);
+        }
+        self.position = MAGIC.len();
+        let format = self.u16()?;
+        if format != FORMAT_VERSION {
+            return Err(ObjectFileError::UnsupportedFormat { found: format, supported: FORMAT_VERSION });
+        }
+        Ok(ObjectHeader {
+            format,
+            version: self.string()?,
+            language: self.string()?,
+            checksum: self.u32()?,
+        })
+    }
+
+    fn function(&mut self) -> Result<Rc<FunctionProto>, ObjectFileError> {
+        let name = self.string()?;
+        let arity = self.u16()?;
+        let cells = self.u16()?;
+        let locals = self.u16()?;
+        let captures = (0..self.u32()?)
+            .map(|_| match (self.u8()?, self.u16()?) {
+                (CAPTURE_CELL, cell) => Ok(Capture::Cell(cell)),
+                (CAPTURE_UPVALUE, upvalue) => Ok(Capture::Upvalue(upvalue)),
+                (tag, _) => Err(corrupt(format!("unknown capture kind {}", tag))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let chunk = self.chunk()?;
+        Ok(Rc::new(FunctionProto { name, arity, cells, locals, captures, chunk }))
+    }
+
+    fn stall(&mut self) -> Result<Rc<StallProto>, ObjectFileError> {
+        let name = self.string()?;
+        let fields = (0..self.u32()?)
+            .map(|_| Ok((self.string()?, self.type_expr()?)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let methods = (0..self.u32()?).map(|_| self.string()).collect::<Result<Vec<_>, _>>()?;
+        Ok(Rc::new(StallProto { name, fields, methods }))
+    }
+
+    fn type_expr(&mut self) -> Result<TypeExpr, ObjectFileError> {
+        let ty = match self.u8()? {
+            TYPE_TEXT => TypeExpr::Text,
+            TYPE_NUM => TypeExpr::Num,
+            TYPE_DEC => TypeExpr::Dec,
+            TYPE_BOOLEAN => TypeExpr::Boolean,
+            TYPE_COOP => TypeExpr::Coop(None),
+            TYPE_TYPED_COOP => TypeExpr::Coop(Some(Box::new(self.type_expr()?))),
+            TYPE_BARN_MAP => TypeExpr::BarnMap(None),
+            TYPE_TYPED_BARN_MAP => TypeExpr::BarnMap(Some((Box::new(self.type_expr()?), Box::new(self.type_expr()?)))),
+            TYPE_RANGE => TypeExpr::Range,
+            TYPE_STALL => TypeExpr::Stall(self.string()?),
+            tag => return Err(corrupt(format!("unknown field type {}", tag))),
+        };
+        Ok(ty)
+    }
+
+    fn chunk(&mut self) -> Result<Chunk, ObjectFileError> {
+        let length = self.u32()? as usize;
+        let code = self.take(length)?.to_vec();
+        let constants = (0..self.u32()?)
+            .map(|_| match self.u8()? {
+                CONSTANT_NUM => Ok(Constant::Num(i64::from_le_bytes(self.array()?))),
+                CONSTANT_DEC => Ok(Constant::Dec(f64::from_bits(u64::from_le_bytes(self.array()?)))),
+                CONSTANT_TEXT => Ok(Constant::Text(Rc::from(self.string()?))),
+                CONSTANT_FUNCTION => Ok(Constant::Function(self.function()?)),
+                CONSTANT_STALL => Ok(Constant::Stall(self.stall()?)),
+                tag => Err(corrupt(format!("unknown constant kind {}", tag))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let spans = (0..self.u32()?)
+            .map(|_| {
+                let offset = self.u32()? as usize;
+                let span = Span::new(self.u32()? as usize, self.u32()? as usize, self.u32()? as usize);
+                let file = self.string()?;
+                Ok((offset, span.in_file((!file.is_empty()).then(|| file_name(&file)))))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Chunk { code, constants, spans })
+    }
+}
+
+/// Check every operand of `function` against the tables it indexes.
+/// `parent` is the function whose `Closure` instruction creates it.
+fn verify(function: &FunctionProto, parent: Option<&FunctionProto>, globals: usize) -> Result<(), ObjectFileError> {
+    let name = &function.name;
+    for capture in &function.captures {
+        let valid = match (capture, parent) {
+            (Capture::Cell(cell), Some(parent)) => *cell < parent.cells,
+            (Capture::Upvalue(upvalue), Some(parent)) => (*upvalue as usize) < parent.captures.len(),
+            (_, None) => false,
+        };
+        if !valid {
+            return Err(corrupt(format!("`{}` captures a variable that does not exist", name)));
+        }
+    }
+
+    let chunk = &function.chunk;
+    if !chunk.spans.windows(2).all(|pair| pair[0].0 < pair[1].0) {
+        return Err(corrupt(format!("the line table of `{}` is out of order", name)));
+    }
+    if function.arity > function.locals {
+        return Err(corrupt(format!("`{}` has more parameters than locals", name)));
+    }
+    // Instruction starts, and the jumps to check against them once all are known
+    let mut starts = vec![false; chunk.code.len()];
+    let mut jumps = Vec::new();
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        starts[offset] = true;
+        let op = OpCode::from_byte(chunk.code[offset])
+            .ok_or_else(|| corrupt(format!("unknown opcode {} in `{}` at {:04}", chunk.code[offset], name, offset)))?;
+        let next = offset + 1 + 2 * op.operands();
+        if next > chunk.code.len() {
+            return Err(corrupt(format!("`{}` ends in the middle of an instruction", name)));
+        }
+        let operands: Vec<usize> = (0..op.operands()).map(|i| chunk.read_u16(offset + 1 + 2 * i) as usize).collect();
+        let valid = match (op, operands.as_slice()) {
+            (OpCode::Constant, [k]) => chunk.constants.get(*k).is_some_and(|c| c.value().is_some()),
+            (OpCode::Closure, [k]) => match chunk.constants.get(*k) {
+                Some(Constant::Function(nested)) => {
+                    verify(nested, Some(function), globals)?;
+                    true
+                }
+                _ => false,
+            },
+            (OpCode::Stall, [k]) => matches!(chunk.constants.get(*k), Some(Constant::Stall(_))),
+            (OpCode::GetField | OpCode::SetField, [k]) => matches!(chunk.constants.get(*k), Some(Constant::Text(_))),
+            (OpCode::Invoke, [k, _, method]) => {
+                matches!(chunk.constants.get(*k), Some(Constant::Text(_)))
+                    && (*method < METHODS.len() || *method == NO_METHOD as usize)
+            }
+            (OpCode::GetLocal | OpCode::SetLocal, [slot]) => *slot < function.locals as usize,
+            (OpCode::NewCell | OpCode::GetCell | OpCode::SetCell, [cell]) => *cell < function.cells as usize,
+            (OpCode::GetUpvalue | OpCode::SetUpvalue, [upvalue]) => *upvalue < function.captures.len(),
+            (OpCode::DefineGlobal | OpCode::GetGlobal | OpCode::SetGlobal, [global]) => *global < globals,
+            (OpCode::Jump | OpCode::JumpIfFalse, [jump]) | (OpCode::ForIter | OpCode::ForEntry, [_, jump]) => {
+                jumps.push((offset, next + jump));
+                true
+            }
+            (OpCode::Loop, [jump]) => {
+                jumps.push((offset, next.wrapping_sub(*jump)));
+                true
+            }
+            (OpCode::CallMethod, [method, _]) => *method < METHODS.len(),
+            (OpCode::CallBuiltin, [builtin, _]) => *builtin < BUILTINS.len(),
+            (OpCode::CoopType, [element]) => *element < ELEMENT_TYPES.len(),
+            _ => true,
+        };
+        if !valid {
+            return Err(corrupt(format!("bad operand for {} in `{}` at {:04}", op.name(), name, offset)));
+        }
+        offset = next;
+    }
+    if let Some((offset, _)) = jumps.iter().find(|(_, target)| !starts.get(*target).copied().unwrap_or(false)) {
+        return Err(corrupt(format!("a jump in `{}` at {:04} does not land on an instruction", name, offset)));
+    }
+    if chunk.code.last().and_then(|byte| OpCode::from_byte(*byte)) != Some(OpCode::Return) {
+        return Err(corrupt(format!("`{}` does not end with a return", name)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_source;
+    use crate::runtime::codegen::compile;
+    use crate::toml_config::language_config::{LanguageKeywordManager, MooConfig};
+
+    fn compiled(source: &str) -> CompiledProgram {
+        let manager = LanguageKeywordManager::from_config(MooConfig::from_default());
+        compile(&parse_source(source, &manager, "en").unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let program = compiled(
+            "num base = 2\nfarmfunction 'num' adder(num step) {\n    farmfunction 'num' add(num x) {\n        return x + step + base\n    }\n    return add(1)\n}\nmoo adder(3), 1.5, \"moo\"",
+        );
+        let bytes = write_object(&program, "1.0.0", "en");
+        let (header, loaded) = read_object(&bytes, "1.0.0").unwrap();
+        assert_eq!(header.version, "1.0.0");
+        assert_eq!(header.language, "en");
+        assert_eq!(loaded, program);
+
+        // Spans of brought files keep their file
+        let mut program = compiled("moo 1");
+        Rc::get_mut(&mut program.main).unwrap().chunk.spans[0].1.file = Some("lib/tools.moo");
+        let (_, loaded) = read_object(&write_object(&program, "1.0.0", "en"), "1.0.0").unwrap();
+        assert_eq!(loaded.main.chunk.spans[0].1.file, Some("lib/tools.moo"));
+    }
+
+    /// Offset of the first `op` in `chunk`
+    fn find(chunk: &Chunk, op: OpCode) -> usize {
+        let mut offset = 0;
+        loop {
+            let found = OpCode::from_byte(chunk.code[offset]).unwrap();
+            if found == op {
+                return offset;
+            }
+            offset += 1 + 2 * found.operands();
+        }
+    }
+
+    #[test]
+    fn test_rejects_bad_operands() {
+        let load = |program: &CompiledProgram| read_object(&write_object(program, "1.0.0", "en"), "1.0.0");
+
+        let mut program = compiled("farmfunction f(num a) {\n    moo a\n}\nf(1)");
+        let main = Rc::get_mut(&mut program.main).unwrap();
+        let function = main.chunk.constants.iter_mut().find_map(|constant| match constant {
+            Constant::Function(function) => Some(function),
+            _ => None,
+        });
+        let function = Rc::get_mut(function.unwrap()).unwrap();
+        assert_eq!(function.locals, 1);
+        let slot = find(&function.chunk, OpCode::GetLocal) + 1;
+        function.chunk.code[slot] = 1;
+        assert_eq!(load(&program), Err(corrupt("bad operand for GET_LOCAL in `f` at 0000".to_string())));
+
+        let mut program = compiled("if (true) {\n    moo 1\n}");
+        let main = Rc::get_mut(&mut program.main).unwrap();
+        let jump = find(&main.chunk, OpCode::JumpIfFalse);
+        let constant = find(&main.chunk, OpCode::Constant);
+        // Into the middle of the `Constant` instruction
+        main.chunk.code[jump + 1] = (constant + 1 - (jump + 3)) as u8;
+        assert_eq!(
+            load(&program),
+            Err(corrupt(format!("a jump in `<main>` at {:04} does not land on an instruction", jump)))
+        );
+    }
+
+    #[test]
+    fn test_rejects_incompatible_files() {
+        let bytes = write_object(&compiled("moo 1"), "1.2.0", "en");
+        assert!(read_object(&bytes, "1.3.0").is_ok());
+        assert_eq!(
+            read_object(&bytes, "2.0.0"),
+            Err(ObjectFileError::IncompatibleVersion { found: "1.2.0".to_string(), expected: "2.0.0".to_string() })
+        );
+        assert!(matches!(read_object(&bytes, "1.1.0"), Err(ObjectFileError::IncompatibleVersion { .. })));
+        assert_eq!(read_object(b"moo 1", "1.2.0"), Err(ObjectFileError::NotAnObjectFile));
+        assert_eq!(read_object(&bytes[..8], "1.2.0"), Err(ObjectFileError::Truncated));
+
+        let mut damaged = bytes.clone();
+        *damaged.last_mut().unwrap() ^= 0xFF;
+        assert!(matches!(read_object(&damaged, "1.2.0"), Err(ObjectFileError::ChecksumMismatch { .. })));
+
+        let mut future = bytes;
+        future[4] = 99;
+        assert_eq!(
+            read_object(&future, "1.2.0"),
+            Err(ObjectFileError::UnsupportedFormat { found: 99, supported: FORMAT_VERSION })
+        );
+    }
+}
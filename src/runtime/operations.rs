@@ -0,0 +1,328 @@
+//! Operations on runtime values, shared by the tree-walking interpreter and
+//! the VM so both run every program the same way.
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::parser::ast::{BinaryOp, TypeExpr, UpdateOp};
+use crate::runtime::barn_map::BarnMap;
+use crate::runtime::coop::{position, Coop, ElementType};
+use crate::runtime::errors::RuntimeError;
+use crate::runtime::numeric;
+use crate::runtime::range::{Range, RangeIter};
+use crate::runtime::value::Value;
+use crate::semantic::types::{Builtin, Method};
+use crate::utils::tokens::Span;
+
+pub(crate) fn invalid(message: impl Into<String>, span: Span) -> RuntimeError {
+    RuntimeError::InvalidOperation {
+        message: message.into(),
+        span,
+    }
+}
+
+/// Value of a declared variable that has no initializer
+pub(crate) fn default_value(ty: &TypeExpr) -> Value {
+    match ty {
+        TypeExpr::Text => Value::text(""),
+        TypeExpr::Num => Value::Num(0),
+        TypeExpr::Dec => Value::Dec(0.0),
+        TypeExpr::Boolean => Value::Boolean(false),
+        TypeExpr::Coop(Some(element)) => Value::from_coop(Coop::typed(ElementType::of(element), Vec::new())),
+        TypeExpr::Coop(None) => Value::coop(Vec::new()),
+        TypeExpr::BarnMap(_) => Value::barn_map(BarnMap::new()),
+        TypeExpr::Range => Value::Range(Range { start: 0, end: 0, step: 1 }),
+        TypeExpr::Stall(_) => Value::Nothing,
+    }
+}
+
+/// Widen a `num` stored where a `dec` is expected
+pub(crate) fn widen(value: Value) -> Value {
+    match value {
+        Value::Num(number) => Value::Dec(number as f64),
+        value => value,
+    }
+}
+
+/// Fit a value stored where `ty` is declared: widen a `num` to `dec` and
+/// give a coop the declared element type
+pub(crate) fn coerce(ty: &TypeExpr, value: Value, span: Span) -> Result<Value, RuntimeError> {
+    match (ty, value) {
+        (TypeExpr::Dec, value) => Ok(widen(value)),
+        (TypeExpr::Coop(Some(element)), value) => {
+            declare_element(&value, ElementType::of(element), span)?;
+            Ok(value)
+        }
+        (_, value) => Ok(value),
+    }
+}
+
+/// Give `value`, if it is a coop, the element type `element`
+pub(crate) fn declare_element(value: &Value, element: ElementType, span: Span) -> Result<(), RuntimeError> {
+    match value {
+        Value::Coop(coop) => coop.borrow_mut().declare(element, span),
+        _ => Ok(()),
+    }
+}
+
+pub(crate) fn negate(value: &Value, span: Span) -> Result<Value, RuntimeError> {
+    match value {
+        Value::Num(value) => value.checked_neg().map(Value::Num).ok_or(RuntimeError::Overflow { span }),
+        Value::Dec(value) => Ok(Value::Dec(-value)),
+        other => Err(invalid(format!("cannot negate `{}`", other.type_name()), span)),
+    }
+}
+
+/// The new value for `++` / `--`
+pub(crate) fn update(op: UpdateOp, value: &Value, span: Span) -> Result<Value, RuntimeError> {
+    let updated = match (op, value) {
+        (UpdateOp::Increment, Value::Num(value)) => value.checked_add(1).map(Value::Num),
+        (UpdateOp::Decrement, Value::Num(value)) => value.checked_sub(1).map(Value::Num),
+        (UpdateOp::Increment, Value::Dec(value)) => Some(Value::Dec(value + 1.0)),
+        (UpdateOp::Decrement, Value::Dec(value)) => Some(Value::Dec(value - 1.0)),
+        (op, other) => {
+            return Err(invalid(
+                format!("cannot apply `{}` to `{}`", op.symbol(), other.type_name()),
+                span,
+            ))
+        }
+    };
+    updated.ok_or(RuntimeError::Overflow { span })
+}
+
+pub(crate) fn binary(op: BinaryOp, left: &Value, right: &Value, span: Span) -> Result<Value, RuntimeError> {
+    match (op, left, right) {
+        (BinaryOp::Is, ..) => Ok(Value::Boolean(left == right)),
+        (BinaryOp::IsNot, ..) => Ok(Value::Boolean(left != right)),
+        (BinaryOp::Add, Value::Text(a), Value::Text(b)) => Ok(Value::text(&format!("{}{}", a, b))),
+        (_, Value::Num(a), Value::Num(b)) => num_arithmetic(op, *a, *b, span),
+        (_, Value::Num(_) | Value::Dec(_), Value::Num(_) | Value::Dec(_)) => {
+            let (a, b) = (left.as_dec().unwrap_or_default(), right.as_dec().unwrap_or_default());
+            Ok(dec_arithmetic(op, a, b).ok_or(RuntimeError::DivisionByZero { span })?)
+        }
+        _ => Err(invalid(
+            format!("cannot apply `{}` to `{}` and `{}`", op.symbol(), left.type_name(), right.type_name()),
+            span,
+        )),
+    }
+}
+
+fn num_arithmetic(op: BinaryOp, a: i64, b: i64, span: Span) -> Result<Value, RuntimeError> {
+    let result = match op {
+        BinaryOp::Add => a.checked_add(b),
+        BinaryOp::Subtract => a.checked_sub(b),
+        BinaryOp::Multiply => a.checked_mul(b),
+        BinaryOp::IntegerDivide | BinaryOp::Modulo if b == 0 => return Err(RuntimeError::DivisionByZero { span }),
+        BinaryOp::IntegerDivide => numeric::floor_div(a, b),
+        BinaryOp::Modulo => numeric::floor_mod(a, b),
+        _ => return dec_arithmetic(op, a as f64, b as f64).ok_or(RuntimeError::DivisionByZero { span }),
+    };
+    result.map(Value::Num).ok_or(RuntimeError::Overflow { span })
+}
+
+/// Arithmetic and comparisons on `dec`; `None` when dividing by zero
+fn dec_arithmetic(op: BinaryOp, a: f64, b: f64) -> Option<Value> {
+    let value = match op {
+        BinaryOp::Add => a + b,
+        BinaryOp::Subtract => a - b,
+        BinaryOp::Multiply => a * b,
+        BinaryOp::Divide => numeric::divide(a, b)?,
+        BinaryOp::IntegerDivide => numeric::floor_div_dec(a, b)?,
+        BinaryOp::Modulo => numeric::floor_mod_dec(a, b)?,
+        BinaryOp::Less => return Some(Value::Boolean(a < b)),
+        BinaryOp::Greater => return Some(Value::Boolean(a > b)),
+        BinaryOp::Is => return Some(Value::Boolean(a == b)),
+        BinaryOp::IsNot => return Some(Value::Boolean(a != b)),
+    };
+    Some(Value::Dec(value))
+}
+
+/// The values a `for` loop visits
+pub(crate) enum Elements {
+    /// A copy of coop elements, barn_map keys or the characters of a text,
+    /// so changing the collection inside the loop does not change the loop
+    Values(std::vec::IntoIter<Value>),
+    /// The numbers of a range, computed one at a time
+    Range(RangeIter),
+}
+
+impl Iterator for Elements {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        match self {
+            Elements::Values(values) => values.next(),
+            Elements::Range(numbers) => numbers.next().map(Value::Num),
+        }
+    }
+}
+
+pub(crate) fn elements(value: &Value, span: Span) -> Result<Elements, RuntimeError> {
+    let values = match value {
+        Value::Coop(coop) => coop.borrow().elements.clone(),
+        Value::BarnMap(map) => map.borrow().keys(),
+        Value::Text(text) => text.graphemes(true).map(Value::text).collect(),
+        Value::Range(range) => return Ok(Elements::Range(range.iter())),
+        other => return Err(invalid(format!("cannot loop over `{}`", other.type_name()), span)),
+    };
+    Ok(Elements::Values(values.into_iter()))
+}
+
+/// The key/value pairs `for key, value in map` visits
+pub(crate) fn entries(value: &Value, span: Span) -> Result<Vec<(Value, Value)>, RuntimeError> {
+    match value {
+        Value::BarnMap(map) => Ok(map.borrow().entries().to_vec()),
+        other => Err(invalid(
+            format!("`for key, value` loops over a `barn_map`, not `{}`", other.type_name()),
+            span,
+        )),
+    }
+}
+
+/// `object[index]`
+pub(crate) fn element(object: &Value, index: &Value, span: Span) -> Result<Value, RuntimeError> {
+    match object {
+        Value::Coop(coop) => coop.borrow().get(index, span),
+        Value::BarnMap(map) => map.borrow().get(index)
+            .cloned()
+            .ok_or_else(|| RuntimeError::MissingKey { key: index.repr(), span }),
+        Value::Text(text) => {
+            let characters: Vec<&str> = text.graphemes(true).collect();
+            Ok(Value::text(characters[position(index, characters.len(), false, span)?]))
+        }
+        Value::Range(range) => {
+            let position = position(index, range.len(), false, span)?;
+            Ok(Value::Num(range.get(position).expect("position is in range")))
+        }
+        other => Err(invalid(format!("cannot index into `{}`", other.type_name()), span)),
+    }
+}
+
+/// `object[index] = value`
+pub(crate) fn set_element(object: &Value, index: Value, value: Value, span: Span) -> Result<(), RuntimeError> {
+    match object {
+        Value::Coop(coop) => coop.borrow_mut().set(&index, value, span),
+        Value::BarnMap(map) => {
+            map.borrow_mut().put(index, value);
+            Ok(())
+        }
+        other => Err(invalid(format!("elements of `{}` cannot be assigned", other.type_name()), span)),
+    }
+}
+
+/// `object.name`
+pub(crate) fn get_field(object: &Value, name: &str, span: Span) -> Result<Value, RuntimeError> {
+    match object {
+        Value::Instance(instance) => instance.borrow().get(name, span),
+        _ if Method::lookup(name).is_some() => Err(invalid(
+            format!("`{}` is a method and must be called, e.g. `{}()`", name, name),
+            span,
+        )),
+        other => Err(invalid(format!("`{}` has no field `{}`", other.type_name(), name), span)),
+    }
+}
+
+/// `object.name = value`
+pub(crate) fn set_field(object: &Value, name: &str, value: Value, span: Span) -> Result<(), RuntimeError> {
+    match object {
+        Value::Instance(instance) => instance.borrow_mut().set(name, value, span),
+        other => Err(invalid(format!("`{}` has no field `{}`", other.type_name(), name), span)),
+    }
+}
+
+/// Error for calling `object.name(...)` when `object` has no such method
+pub(crate) fn no_method(object: &Value, name: &str, span: Span) -> RuntimeError {
+    let owner = match object {
+        Value::Instance(instance) => instance.borrow().stall.name.clone(),
+        other => other.type_name().to_string(),
+    };
+    invalid(format!("`{}` has no method `{}`", owner, name), span)
+}
+
+pub(crate) fn call_method(object: &Value, method: Method, args: &[Value], span: Span) -> Result<Value, RuntimeError> {
+    match (method, object, args) {
+        (Method::Add, Value::Coop(coop), [value]) => {
+            coop.borrow_mut().push(value.clone(), span)?;
+            Ok(Value::Nothing)
+        }
+        (Method::Size, Value::Coop(coop), []) => Ok(Value::Num(coop.borrow().elements.len() as i64)),
+        (Method::Size, Value::BarnMap(map), []) => Ok(Value::Num(map.borrow().len() as i64)),
+        (Method::Size, Value::Text(text), []) => Ok(Value::Num(text.graphemes(true).count() as i64)),
+        (Method::Size, Value::Range(range), []) => {
+            i64::try_from(range.len()).map(Value::Num).map_err(|_| RuntimeError::Overflow { span })
+        }
+        (Method::Get, Value::Coop(_) | Value::BarnMap(_) | Value::Range(_), [index]) => element(object, index, span),
+        (Method::Remove, Value::Coop(coop), [value]) => {
+            let found = coop.borrow().index_of(value);
+            if let Some(position) = found {
+                coop.borrow_mut().elements.remove(position);
+            }
+            Ok(Value::Nothing)
+        }
+        (Method::Remove, Value::BarnMap(map), [key]) => {
+            map.borrow_mut().remove(key);
+            Ok(Value::Nothing)
+        }
+        (Method::Contains, Value::Coop(coop), [value]) => Ok(Value::Boolean(coop.borrow().index_of(value).is_some())),
+        (Method::Contains, Value::Range(range), [value]) => {
+            Ok(Value::Boolean(matches!(value, Value::Num(number) if range.contains(*number))))
+        }
+        (Method::Contains, Value::BarnMap(map), [key]) => Ok(Value::Boolean(map.borrow().contains(key))),
+        (Method::Put, Value::BarnMap(map), [key, value]) => {
+            map.borrow_mut().put(key.clone(), value.clone());
+            Ok(Value::Nothing)
+        }
+        (Method::Keys, Value::BarnMap(map), []) => Ok(Value::coop(map.borrow().keys())),
+        (Method::Values, Value::BarnMap(map), []) => Ok(Value::coop(map.borrow().values())),
+        (Method::RemoveAt, Value::Coop(coop), [index]) => coop.borrow_mut().remove_at(index, span),
+        (Method::Insert, Value::Coop(coop), [index, value]) => {
+            coop.borrow_mut().insert(index, value.clone(), span)?;
+            Ok(Value::Nothing)
+        }
+        (Method::IndexOf, Value::Coop(coop), [value]) => {
+            Ok(Value::Num(coop.borrow().index_of(value).map_or(-1, |position| position as i64)))
+        }
+        (Method::Sort, Value::Coop(coop), []) => {
+            coop.borrow_mut().sort(span)?;
+            Ok(Value::Nothing)
+        }
+        (Method::Reverse, Value::Coop(coop), []) => {
+            coop.borrow_mut().elements.reverse();
+            Ok(Value::Nothing)
+        }
+        (Method::Slice, Value::Coop(coop), [start, end]) => Ok(Value::from_coop(coop.borrow().slice(start, end, span)?)),
+        _ => Err(invalid(
+            format!(
+                "cannot call `{}` on `{}` with {} argument(s)",
+                method.name(),
+                object.type_name(),
+                args.len()
+            ),
+            span,
+        )),
+    }
+}
+
+/// `to_num`, `to_dec` and `to_text`
+pub(crate) fn convert(builtin: Builtin, args: &[Value], span: Span) -> Result<Value, RuntimeError> {
+    let [value] = args else {
+        return Err(invalid(
+            format!("`{}` takes 1 argument but {} were given", builtin.name(), args.len()),
+            span,
+        ));
+    };
+    let converted = match (builtin, value) {
+        (Builtin::ToNum, Value::Num(number)) => Some(Value::Num(*number)),
+        (Builtin::ToNum, Value::Dec(number)) => numeric::dec_to_num(*number).map(Value::Num),
+        (Builtin::ToNum, Value::Text(text)) => numeric::parse_num(text).map(Value::Num),
+        (Builtin::ToDec, Value::Num(number)) => Some(Value::Dec(*number as f64)),
+        (Builtin::ToDec, Value::Dec(number)) => Some(Value::Dec(*number)),
+        (Builtin::ToDec, Value::Text(text)) => numeric::parse_dec(text).map(Value::Dec),
+        (Builtin::ToText, value) => Some(Value::text(&value.to_string())),
+        _ => None,
+    };
+    converted.ok_or_else(|| RuntimeError::ConversionFailed {
+        value: value.repr(),
+        target: builtin.returns(&[]).to_string(),
+        span,
+    })
+}
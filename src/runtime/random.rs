@@ -0,0 +1,64 @@
+//! The pseudo-random numbers behind `random`.
+//!
+//! SplitMix64: a 64-bit state and a few shifts and multiplications, with no
+//! platform-specific parts, so a seed gives the same numbers on every
+//! machine.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Seeded from the clock, so each run draws different numbers
+    pub fn from_time() -> Self {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_nanos());
+        Self::new(nanos as u64)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number from `low` to `high`, both included; `None` when `low` is
+    /// greater than `high`
+    pub fn between(&mut self, low: i64, high: i64) -> Option<i64> {
+        if low > high {
+            return None;
+        }
+        let count = (high as i128 - low as i128 + 1) as u128;
+        let offset = (self.next_u64() as u128 * count) >> 64;
+        Some((low as i128 + offset as i128) as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeds_repeat() {
+        let draws = |seed| {
+            let mut random = Random::new(seed);
+            (0..20).map(|_| random.between(1, 6).unwrap()).collect::<Vec<_>>()
+        };
+        assert_eq!(draws(42), draws(42));
+        assert_ne!(draws(42), draws(43));
+        assert!(draws(7).iter().all(|roll| (1..=6).contains(roll)));
+
+        let mut random = Random::new(0);
+        assert_eq!(random.between(5, 5), Some(5));
+        assert_eq!(random.between(6, 5), None);
+        assert!(random.between(i64::MIN, i64::MAX).is_some());
+    }
+}
@@ -0,0 +1,138 @@
+//! `barn` ranges: the numbers from a start up to, but not including, an
+//! end, counting by a step.
+//!
+//! A range only stores its three bounds. Looping over `barn(1000000000)`
+//! produces one number at a time instead of building a coop of them first,
+//! and a range can be stored, passed and indexed like any other value.
+
+use std::fmt;
+
+use crate::runtime::errors::RuntimeError;
+use crate::runtime::operations::invalid;
+use crate::runtime::value::Value;
+use crate::utils::tokens::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Range {
+    pub start: i64,
+    pub end: i64,
+    pub step: i64,
+}
+
+impl Range {
+    /// `barn(end)`, `barn(start, end)` or `barn(start, end, step)`
+    pub fn new(bounds: &[Value], span: Span) -> Result<Range, RuntimeError> {
+        let mut numbers = Vec::with_capacity(bounds.len());
+        for bound in bounds {
+            match bound {
+                Value::Num(number) => numbers.push(*number),
+                other => {
+                    return Err(invalid(
+                        format!("barn bounds must be `num`, found `{}`", other.type_name()),
+                        span,
+                    ))
+                }
+            }
+        }
+        let (start, end, step) = match *numbers {
+            [end] => (0, end, 1),
+            [start, end] => (start, end, 1),
+            [start, end, step] => (start, end, step),
+            _ => {
+                return Err(invalid(
+                    format!("barn takes 1 to 3 arguments but {} were given", bounds.len()),
+                    span,
+                ))
+            }
+        };
+        if step == 0 {
+            return Err(invalid("barn step cannot be zero", span));
+        }
+        Ok(Range { start, end, step })
+    }
+
+    /// How many numbers the range holds
+    pub fn len(&self) -> usize {
+        let (start, end, step) = (self.start as i128, self.end as i128, self.step as i128);
+        let distance = if step > 0 { end - start } else { start - end };
+        if distance <= 0 {
+            return 0;
+        }
+        let count = (distance + step.abs() - 1) / step.abs();
+        usize::try_from(count).unwrap_or(usize::MAX)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number at `position`, counting from 0
+    pub fn get(&self, position: usize) -> Option<i64> {
+        if position >= self.len() {
+            return None;
+        }
+        i64::try_from(self.start as i128 + position as i128 * self.step as i128).ok()
+    }
+
+    pub fn contains(&self, number: i64) -> bool {
+        let (offset, step) = (number as i128 - self.start as i128, self.step as i128);
+        offset % step == 0 && (0..self.len() as i128).contains(&(offset / step))
+    }
+
+    pub fn iter(&self) -> RangeIter {
+        RangeIter { range: *self, position: 0 }
+    }
+}
+
+impl fmt::Display for Range {
+    /// As written in source code, e.g. `barn(0, 10, 2)`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.step {
+            1 => write!(f, "barn({}, {})", self.start, self.end),
+            step => write!(f, "barn({}, {}, {})", self.start, self.end, step),
+        }
+    }
+}
+
+/// The numbers of a range, computed as they are visited
+#[derive(Debug, Clone)]
+pub struct RangeIter {
+    range: Range,
+    position: usize,
+}
+
+impl Iterator for RangeIter {
+    type Item = i64;
+
+    fn next(&mut self) -> Option<i64> {
+        let number = self.range.get(self.position)?;
+        self.position += 1;
+        Some(number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(bounds: &[i64]) -> Range {
+        let bounds: Vec<Value> = bounds.iter().map(|bound| Value::Num(*bound)).collect();
+        Range::new(&bounds, Span::default()).unwrap()
+    }
+
+    #[test]
+    fn test_bounds_and_steps() {
+        assert_eq!(range(&[4]).iter().collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+        assert_eq!(range(&[2, 9, 3]).iter().collect::<Vec<_>>(), vec![2, 5, 8]);
+        assert_eq!(range(&[5, 0, -2]).iter().collect::<Vec<_>>(), vec![5, 3, 1]);
+        assert!(range(&[5, 0]).is_empty());
+        assert_eq!(range(&[i64::MIN, i64::MAX]).len(), usize::MAX);
+        assert_eq!(range(&[i64::MAX - 1, i64::MAX, 5]).iter().collect::<Vec<_>>(), vec![i64::MAX - 1]);
+
+        let evens = range(&[0, 10, 2]);
+        assert!(evens.contains(4) && !evens.contains(5) && !evens.contains(10) && !evens.contains(-2));
+        assert_eq!(evens.to_string(), "barn(0, 10, 2)");
+        assert!(Range::new(&[Value::Num(1), Value::Num(2), Value::Num(0)], Span::default()).is_err());
+        assert!(Range::new(&[Value::text("ten")], Span::default()).is_err());
+    }
+}
@@ -0,0 +1,87 @@
+//! `stall` values: records with named fields and methods.
+//!
+//! Declaring a stall creates a constructor value; calling it creates an
+//! instance with every field set to its type's default, runs the field
+//! initializers and then the constructor's body with `self` bound to the
+//! new instance. Instances are shared like coops: passing one to a
+//! function and changing a field there changes the caller's instance.
+
+use std::fmt;
+use std::rc::Rc;
+
+use crate::parser::ast::TypeExpr;
+use crate::runtime::errors::RuntimeError;
+use crate::runtime::operations::{coerce, default_value, invalid};
+use crate::runtime::value::Value;
+use crate::utils::tokens::Span;
+
+/// A declared stall, shared by all of its instances
+#[derive(Debug)]
+pub struct Stall {
+    pub name: String,
+    pub fields: Vec<(String, TypeExpr)>,
+    /// Methods by name, as `farmfunction` values that take `self` first
+    pub methods: Vec<(String, Value)>,
+    /// Runs the field initializers and the constructor's body on a new
+    /// instance
+    pub init: Value,
+}
+
+impl Stall {
+    pub fn field(&self, name: &str) -> Option<usize> {
+        self.fields.iter().position(|(field, _)| field == name)
+    }
+
+    pub fn method(&self, name: &str) -> Option<&Value> {
+        self.methods.iter().find(|(method, _)| method == name).map(|(_, method)| method)
+    }
+}
+
+#[derive(Debug)]
+pub struct Instance {
+    pub stall: Rc<Stall>,
+    /// Field values, in the order the stall declares them
+    pub fields: Vec<Value>,
+}
+
+impl Instance {
+    /// An instance with every field set to its type's default
+    pub fn new(stall: Rc<Stall>) -> Self {
+        let fields = stall.fields.iter().map(|(_, ty)| default_value(ty)).collect();
+        Self { stall, fields }
+    }
+
+    pub fn get(&self, name: &str, span: Span) -> Result<Value, RuntimeError> {
+        match self.stall.field(name) {
+            Some(index) => Ok(self.fields[index].clone()),
+            None if self.stall.method(name).is_some() => Err(invalid(
+                format!("`{}` is a method and must be called, e.g. `{}()`", name, name),
+                span,
+            )),
+            None => Err(invalid(format!("`{}` has no field `{}`", self.stall.name, name), span)),
+        }
+    }
+
+    /// Set a field, fitting the value to the field's declared type
+    pub fn set(&mut self, name: &str, value: Value, span: Span) -> Result<(), RuntimeError> {
+        let Some(index) = self.stall.field(name) else {
+            return Err(invalid(format!("`{}` has no field `{}`", self.stall.name, name), span));
+        };
+        self.fields[index] = coerce(&self.stall.fields[index].1, value, span)?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for Instance {
+    /// `Cow {name: "Daisy", age: 3}`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {{", self.stall.name)?;
+        for (i, ((name, _), value)) in self.stall.fields.iter().zip(&self.fields).enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}: {}", name, value.repr())?;
+        }
+        write!(f, "}}")
+    }
+}
@@ -0,0 +1,116 @@
+//! The standard library: builtins called by name, such as `upper("cow")`,
+//! `max(a, b)` or `listen()`.
+//!
+//! Types live in `semantic::types::Builtin`; the names a program calls them
+//! by come from its keyword pack's `[builtins]` table through
+//! `BuiltinNameSet`. This module runs them. A `Stdlib` holds the state builtins keep between calls: where
+//! `listen` reads from and the numbers `random` draws.
+
+use std::io::{self, BufRead};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::runtime::coop::{Coop, ElementType};
+use crate::runtime::errors::RuntimeError;
+use crate::runtime::operations::{convert, invalid};
+use crate::runtime::random::Random;
+use crate::runtime::value::Value;
+use crate::semantic::types::Builtin;
+use crate::utils::tokens::Span;
+
+pub struct Stdlib<'a> {
+    /// Where `listen` reads lines
+    input: Box<dyn BufRead + 'a>,
+    random: Random,
+}
+
+impl Default for Stdlib<'_> {
+    /// Reads standard input and seeds `random` from the clock
+    fn default() -> Self {
+        Self {
+            input: Box::new(io::stdin().lock()),
+            random: Random::from_time(),
+        }
+    }
+}
+
+impl<'a> Stdlib<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_input(mut self, input: impl BufRead + 'a) -> Self {
+        self.input = Box::new(input);
+        self
+    }
+
+    /// Draw `random` numbers from `seed`, so every run with the same seed
+    /// draws the same numbers
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.random = Random::new(seed);
+        self
+    }
+
+    pub fn call(&mut self, builtin: Builtin, args: &[Value], span: Span) -> Result<Value, RuntimeError> {
+        if builtin.is_conversion() {
+            return convert(builtin, args, span);
+        }
+        let expected = builtin.params().len();
+        if args.len() != expected {
+            let plural = if expected == 1 { "" } else { "s" };
+            return Err(invalid(
+                format!("`{}` takes {} argument{} but {} were given", builtin.name(), expected, plural, args.len()),
+                span,
+            ));
+        }
+
+        match (builtin, args) {
+            (Builtin::Length, [Value::Text(text)]) => Ok(Value::Num(text.graphemes(true).count() as i64)),
+            (Builtin::Upper, [Value::Text(text)]) => Ok(Value::text(&text.to_uppercase())),
+            (Builtin::Split, [Value::Text(text), Value::Text(separator)]) => {
+                let pieces = if separator.is_empty() {
+                    text.graphemes(true).map(Value::text).collect()
+                } else {
+                    text.split(&**separator).map(Value::text).collect()
+                };
+                Ok(Value::from_coop(Coop::typed(ElementType::Text, pieces)))
+            }
+            (Builtin::Join, [Value::Coop(coop), Value::Text(separator)]) => {
+                let pieces: Vec<String> = coop.borrow().elements.iter().map(Value::to_string).collect();
+                Ok(Value::text(&pieces.join(separator)))
+            }
+            (Builtin::Abs, [Value::Num(number)]) => number.checked_abs().map(Value::Num).ok_or(RuntimeError::Overflow { span }),
+            (Builtin::Abs, [Value::Dec(number)]) => Ok(Value::Dec(number.abs())),
+            (Builtin::Min, [Value::Num(a), Value::Num(b)]) => Ok(Value::Num(*a.min(b))),
+            (Builtin::Max, [Value::Num(a), Value::Num(b)]) => Ok(Value::Num(*a.max(b))),
+            (Builtin::Min | Builtin::Max, [a, b]) if a.as_dec().is_some() && b.as_dec().is_some() => {
+                let (a, b) = (a.as_dec().unwrap_or_default(), b.as_dec().unwrap_or_default());
+                Ok(Value::Dec(if builtin == Builtin::Min { a.min(b) } else { a.max(b) }))
+            }
+            (Builtin::Sqrt, [number]) if number.as_dec().is_some_and(|number| number < 0.0) => {
+                Err(invalid(format!("`sqrt` cannot take a negative number, found {}", number), span))
+            }
+            (Builtin::Sqrt, [number]) if number.as_dec().is_some() => {
+                Ok(Value::Dec(number.as_dec().unwrap_or_default().sqrt()))
+            }
+            (Builtin::Random, [Value::Num(low), Value::Num(high)]) => self.random.between(*low, *high)
+                .map(Value::Num)
+                .ok_or_else(|| invalid(format!("`random` needs low <= high, found {} and {}", low, high), span)),
+            (Builtin::Listen, []) => self.listen(span),
+            _ => {
+                let found: Vec<&str> = args.iter().map(Value::type_name).collect();
+                Err(invalid(format!("`{}` cannot take `{}`", builtin.name(), found.join("`, `")), span))
+            }
+        }
+    }
+
+    fn listen(&mut self, span: Span) -> Result<Value, RuntimeError> {
+        let mut line = String::new();
+        self.input.read_line(&mut line).map_err(|error| RuntimeError::Input {
+            message: error.to_string(),
+            span,
+        })?;
+        let line = line.strip_suffix('\n').unwrap_or(&line);
+        Ok(Value::text(line.strip_suffix('\r').unwrap_or(line)))
+    }
+}
@@ -0,0 +1,166 @@
+//! Runtime values.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::parser::ast::FunctionDecl;
+use crate::runtime::barn_map::BarnMap;
+use crate::runtime::coop::Coop;
+use crate::runtime::environment::Environment;
+use crate::runtime::range::Range;
+use crate::runtime::stall::{Instance, Stall};
+use crate::runtime::vm::Closure;
+
+/// A MooLang value. `coop`, `barn_map` and stall instance values are shared:
+/// assigning one to another variable or passing it to a function does not
+/// copy it, so `animals.plant("cow")` inside a function changes the
+/// caller's coop.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Text(Rc<str>),
+    Num(i64),
+    Dec(f64),
+    Boolean(bool),
+    Coop(Rc<RefCell<Coop>>),
+    BarnMap(Rc<RefCell<BarnMap>>),
+    /// `barn(start, end, step)`
+    Range(Range),
+    Function(Rc<Function>),
+    /// A `farmfunction` compiled for the VM
+    Closure(Rc<Closure>),
+    /// A declared stall; calling it creates an instance
+    Stall(Rc<Stall>),
+    Instance(Rc<RefCell<Instance>>),
+    /// Result of calls that produce no value
+    Nothing,
+}
+
+/// A `farmfunction` together with the scope it was declared in, so nested
+/// functions can read and update their enclosing function's variables
+#[derive(Debug)]
+pub struct Function {
+    pub declaration: Rc<FunctionDecl>,
+    pub closure: Rc<RefCell<Environment>>,
+}
+
+impl Value {
+    pub fn text(text: &str) -> Value {
+        Value::Text(Rc::from(text))
+    }
+
+    /// A coop without a declared element type
+    pub fn coop(elements: Vec<Value>) -> Value {
+        Value::from_coop(Coop::new(elements))
+    }
+
+    pub fn from_coop(coop: Coop) -> Value {
+        Value::Coop(Rc::new(RefCell::new(coop)))
+    }
+
+    pub fn barn_map(map: BarnMap) -> Value {
+        Value::BarnMap(Rc::new(RefCell::new(map)))
+    }
+
+    /// Name of the value's type, as used in error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Text(_) => "text",
+            Value::Num(_) => "num",
+            Value::Dec(_) => "dec",
+            Value::Boolean(_) => "boolean",
+            Value::Coop(_) => "coop",
+            Value::BarnMap(_) => "barn_map",
+            Value::Range(_) => "barn",
+            Value::Function(_) | Value::Closure(_) => "farmfunction",
+            Value::Stall(_) => "stall constructor",
+            Value::Instance(_) => "stall",
+            Value::Nothing => "nothing",
+        }
+    }
+
+    /// The value as a `dec`, widening a `num`
+    pub fn as_dec(&self) -> Option<f64> {
+        match self {
+            Value::Num(value) => Some(*value as f64),
+            Value::Dec(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The value as written in source code. Unlike `moo` output, text is
+    /// quoted, so collections print unambiguously: `["1", 1]`.
+    pub fn repr(&self) -> String {
+        match self {
+            Value::Text(text) => format!("{:?}", text),
+            other => other.to_string(),
+        }
+    }
+}
+
+impl PartialEq for Value {
+    /// `is` / `es`: structural equality, with `num` and `dec` compared by value
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Text(a), Value::Text(b)) => a == b,
+            (Value::Num(a), Value::Num(b)) => a == b,
+            (Value::Boolean(a), Value::Boolean(b)) => a == b,
+            (Value::Num(_) | Value::Dec(_), Value::Num(_) | Value::Dec(_)) => self.as_dec() == other.as_dec(),
+            (Value::Coop(a), Value::Coop(b)) => Rc::ptr_eq(a, b) || a.borrow().elements == b.borrow().elements,
+            (Value::BarnMap(a), Value::BarnMap(b)) => {
+                if Rc::ptr_eq(a, b) {
+                    return true;
+                }
+                let (a, b) = (a.borrow(), b.borrow());
+                a.len() == b.len() && a.entries().iter().all(|(key, value)| b.get(key) == Some(value))
+            }
+            (Value::Range(a), Value::Range(b)) => a.len() == b.len() && a.iter().take(2).eq(b.iter().take(2)),
+            (Value::Function(a), Value::Function(b)) => Rc::ptr_eq(a, b),
+            (Value::Closure(a), Value::Closure(b)) => Rc::ptr_eq(a, b),
+            (Value::Stall(a), Value::Stall(b)) => Rc::ptr_eq(a, b),
+            // Two instances are the same value only if they are the same instance
+            (Value::Instance(a), Value::Instance(b)) => Rc::ptr_eq(a, b),
+            (Value::Nothing, Value::Nothing) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    /// How `moo` prints a value
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Text(text) => write!(f, "{}", text),
+            Value::Num(value) => write!(f, "{}", value),
+            Value::Dec(value) if value.is_finite() && value.fract() == 0.0 => write!(f, "{:.1}", value),
+            Value::Dec(value) => write!(f, "{}", value),
+            Value::Boolean(value) => write!(f, "{}", value),
+            Value::Coop(coop) => {
+                write!(f, "[")?;
+                for (i, element) in coop.borrow().elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", element.repr())?;
+                }
+                write!(f, "]")
+            }
+            Value::BarnMap(map) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in map.borrow().entries().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", key.repr(), value.repr())?;
+                }
+                write!(f, "}}")
+            }
+            Value::Range(range) => write!(f, "{}", range),
+            Value::Function(function) => write!(f, "<farmfunction {}>", function.declaration.name),
+            Value::Closure(closure) => write!(f, "<farmfunction {}>", closure.proto.name),
+            Value::Stall(stall) => write!(f, "<stall {}>", stall.name),
+            Value::Instance(instance) => write!(f, "{}", instance.borrow()),
+            Value::Nothing => write!(f, "nothing"),
+        }
+    }
+}
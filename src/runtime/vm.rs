@@ -0,0 +1,878 @@
+//! Stack-based virtual machine running compiled bytecode.
+
+use std::cell::RefCell;
+use std::io::{BufRead, Write};
+use std::rc::Rc;
+
+use crate::parser::ast::{BinaryOp, UpdateOp};
+use crate::runtime::barn_map::BarnMap;
+use crate::runtime::bytecode::{
+    Capture, CompiledProgram, Constant, FunctionProto, OpCode, BUILTINS, ELEMENT_TYPES, METHODS,
+};
+use crate::runtime::errors::RuntimeError;
+use crate::runtime::interpreter::MAX_CALL_DEPTH;
+use crate::runtime::numeric;
+use crate::runtime::operations::{
+    binary, call_method, declare_element, element, elements, entries, get_field, invalid, negate, no_method,
+    set_element, set_field, update, widen,
+};
+use crate::runtime::range::Range;
+use crate::runtime::stall::{Instance, Stall};
+use crate::runtime::stdlib::Stdlib;
+use crate::runtime::value::Value;
+use crate::utils::tokens::Span;
+
+/// A compiled `farmfunction` together with the captured variables it uses
+#[derive(Debug)]
+pub struct Closure {
+    pub proto: Rc<FunctionProto>,
+    pub upvalues: Vec<Rc<RefCell<Value>>>,
+}
+
+/// One running function call
+struct Frame {
+    closure: Rc<Closure>,
+    /// Next instruction, saved while the frame calls another function
+    ip: usize,
+    /// Stack index of the first parameter
+    base: usize,
+    cells: Vec<Rc<RefCell<Value>>>,
+}
+
+pub struct Vm<'a> {
+    /// Where `moo` writes
+    out: &'a mut dyn Write,
+    stack: Vec<Value>,
+    frames: Vec<Frame>,
+    globals: Vec<Option<Value>>,
+    stdlib: Stdlib<'a>,
+}
+
+impl<'a> Vm<'a> {
+    pub fn new(out: &'a mut dyn Write) -> Self {
+        Self {
+            out,
+            stack: Vec::new(),
+            frames: Vec::new(),
+            globals: Vec::new(),
+            stdlib: Stdlib::new(),
+        }
+    }
+
+    /// Read `listen` lines from `input` instead of standard input
+    pub fn with_input(mut self, input: impl BufRead + 'a) -> Self {
+        self.stdlib = self.stdlib.with_input(input);
+        self
+    }
+
+    /// Seed `random`, so the program prints the same output on every run
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.stdlib = self.stdlib.with_seed(seed);
+        self
+    }
+
+    /// Run `program` from its first top-level statement, stopping at the
+    /// first runtime error
+    pub fn run(&mut self, program: &CompiledProgram) -> Result<(), RuntimeError> {
+        self.stack.clear();
+        self.frames.clear();
+        self.globals = vec![None; program.globals.len()];
+        let main = Rc::new(Closure {
+            proto: Rc::clone(&program.main),
+            upvalues: Vec::new(),
+        });
+        self.stack.push(Value::Closure(Rc::clone(&main)));
+        self.push_frame(main, 0, Span::default())?;
+        self.execute(program)
+    }
+
+    fn push_frame(&mut self, closure: Rc<Closure>, argc: usize, span: Span) -> Result<(), RuntimeError> {
+        let proto = &closure.proto;
+        if argc != proto.arity as usize {
+            return Err(invalid(
+                format!("`{}` takes {} argument(s) but {} were given", proto.name, proto.arity, argc),
+                span,
+            ));
+        }
+        if self.frames.len() > MAX_CALL_DEPTH {
+            return Err(RuntimeError::RecursionLimit { span });
+        }
+        let cells = (0..proto.cells).map(|_| Rc::new(RefCell::new(Value::Nothing))).collect();
+        self.frames.push(Frame {
+            closure,
+            ip: 0,
+            base: self.stack.len() - argc,
+            cells,
+        });
+        Ok(())
+    }
+
+    /// Push the frame of a stall's farmfunction, whose instance is already
+    /// on the stack below the `argc` arguments. Arity errors leave out the
+    /// hidden `self` parameter.
+    fn push_method_frame(&mut self, closure: Rc<Closure>, argc: usize, span: Span) -> Result<(), RuntimeError> {
+        let params = closure.proto.arity as usize - 1;
+        if argc != params {
+            return Err(invalid(
+                format!("`{}` takes {} argument(s) but {} were given", closure.proto.name, params, argc),
+                span,
+            ));
+        }
+        self.push_frame(closure, argc + 1, span)
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("bytecode keeps the stack balanced")
+    }
+
+    fn peek(&self) -> &Value {
+        self.stack.last().expect("bytecode keeps the stack balanced")
+    }
+
+    /// The top `count` values, in the order they were pushed
+    fn pop_many(&mut self, count: usize) -> Vec<Value> {
+        self.stack.split_off(self.stack.len() - count)
+    }
+
+    fn execute(&mut self, program: &CompiledProgram) -> Result<(), RuntimeError> {
+        let mut frame = self.frames.len() - 1;
+        let mut closure = Rc::clone(&self.frames[frame].closure);
+        let mut base = self.frames[frame].base;
+        let mut ip = 0;
+
+        loop {
+            let chunk = &closure.proto.chunk;
+            let start = ip;
+            let op = OpCode::from_byte(chunk.code[ip])
+                .ok_or_else(|| invalid("invalid bytecode", chunk.span_at(start)))?;
+            let operand = |index: usize| chunk.read_u16(start + 1 + 2 * index) as usize;
+            ip += 1 + 2 * op.operands();
+
+            match op {
+                OpCode::Constant => {
+                    let value = chunk.constants[operand(0)].value()
+                        .ok_or_else(|| invalid("invalid bytecode", chunk.span_at(start)))?;
+                    self.stack.push(value);
+                }
+                OpCode::Nothing => self.stack.push(Value::Nothing),
+                OpCode::True => self.stack.push(Value::Boolean(true)),
+                OpCode::False => self.stack.push(Value::Boolean(false)),
+                OpCode::Pop => {
+                    self.pop();
+                }
+                OpCode::PopN => {
+                    let length = self.stack.len() - operand(0);
+                    self.stack.truncate(length);
+                }
+                OpCode::Dup => self.stack.push(self.peek().clone()),
+                OpCode::Dup2 => {
+                    let length = self.stack.len();
+                    self.stack.extend_from_within(length - 2..);
+                }
+                OpCode::Bury => {
+                    let value = self.pop();
+                    let position = self.stack.len() - operand(0);
+                    self.stack.insert(position, value);
+                }
+                OpCode::GetLocal => self.stack.push(self.stack[base + operand(0)].clone()),
+                OpCode::SetLocal => self.stack[base + operand(0)] = self.peek().clone(),
+                OpCode::NewCell => self.frames[frame].cells[operand(0)] = Rc::new(RefCell::new(Value::Nothing)),
+                OpCode::GetCell => {
+                    let value = self.frames[frame].cells[operand(0)].borrow().clone();
+                    self.stack.push(value);
+                }
+                OpCode::SetCell => *self.frames[frame].cells[operand(0)].borrow_mut() = self.peek().clone(),
+                OpCode::GetUpvalue => {
+                    let value = closure.upvalues[operand(0)].borrow().clone();
+                    self.stack.push(value);
+                }
+                OpCode::SetUpvalue => *closure.upvalues[operand(0)].borrow_mut() = self.peek().clone(),
+                OpCode::DefineGlobal => {
+                    let value = self.pop();
+                    self.globals[operand(0)] = Some(value);
+                }
+                OpCode::GetGlobal | OpCode::SetGlobal => {
+                    let global = operand(0);
+                    let Some(slot) = self.globals[global].as_mut() else {
+                        return Err(RuntimeError::UndefinedName {
+                            name: program.globals[global].clone(),
+                            span: chunk.span_at(start),
+                        });
+                    };
+                    if op == OpCode::GetGlobal {
+                        let value = slot.clone();
+                        self.stack.push(value);
+                    } else {
+                        *slot = self.stack.last().expect("bytecode keeps the stack balanced").clone();
+                    }
+                }
+                OpCode::Widen => {
+                    let value = self.pop();
+                    self.stack.push(widen(value));
+                }
+                OpCode::CoopType => {
+                    let element = ELEMENT_TYPES.get(operand(0)).copied()
+                        .ok_or_else(|| invalid("invalid bytecode", chunk.span_at(start)))?;
+                    declare_element(self.peek(), element, chunk.span_at(start))?;
+                }
+                OpCode::Negate => {
+                    let value = negate(self.peek(), chunk.span_at(start))?;
+                    *self.stack.last_mut().expect("bytecode keeps the stack balanced") = value;
+                }
+                OpCode::Add
+                | OpCode::Subtract
+                | OpCode::Multiply
+                | OpCode::Divide
+                | OpCode::IntegerDivide
+                | OpCode::Modulo
+                | OpCode::Less
+                | OpCode::Greater
+                | OpCode::Is
+                | OpCode::IsNot => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    let op = op.binary_op().expect("binary opcode");
+                    let value = match fast_binary(op, &left, &right) {
+                        Some(value) => value,
+                        None => binary(op, &left, &right, chunk.span_at(start))?,
+                    };
+                    self.stack.push(value);
+                }
+                OpCode::Increment | OpCode::Decrement => {
+                    let step = if op == OpCode::Increment { UpdateOp::Increment } else { UpdateOp::Decrement };
+                    let value = update(step, self.peek(), chunk.span_at(start))?;
+                    *self.stack.last_mut().expect("bytecode keeps the stack balanced") = value;
+                }
+                OpCode::Jump => ip += operand(0),
+                OpCode::JumpIfFalse => match self.pop() {
+                    Value::Boolean(true) => {}
+                    Value::Boolean(false) => ip += operand(0),
+                    other => {
+                        return Err(invalid(
+                            format!("condition must be `boolean`, found `{}`", other.type_name()),
+                            chunk.span_at(start),
+                        ))
+                    }
+                },
+                OpCode::Loop => ip -= operand(0),
+                OpCode::Elements => {
+                    // A range stays as it is and `ForIter` computes each number
+                    let value = self.pop();
+                    let items = match value {
+                        Value::Range(_) => value,
+                        _ => Value::coop(elements(&value, chunk.span_at(start))?.collect()),
+                    };
+                    self.stack.push(items);
+                }
+                OpCode::ForIter => {
+                    let slot = base + operand(0);
+                    let Value::Num(position) = self.stack[slot + 1] else {
+                        return Err(invalid("invalid bytecode", chunk.span_at(start)));
+                    };
+                    let next = match &self.stack[slot] {
+                        Value::Coop(items) => items.borrow().elements.get(position as usize).cloned(),
+                        Value::Range(range) => range.get(position as usize).map(Value::Num),
+                        _ => return Err(invalid("invalid bytecode", chunk.span_at(start))),
+                    };
+                    match next {
+                        Some(item) => {
+                            self.stack[slot + 1] = Value::Num(position + 1);
+                            self.stack.push(item);
+                        }
+                        None => ip += operand(1),
+                    }
+                }
+                OpCode::Entries => {
+                    let map = self.pop();
+                    let entries = entries(&map, chunk.span_at(start))?;
+                    let values = entries.into_iter().flat_map(|(key, value)| [key, value]).collect();
+                    self.stack.push(Value::coop(values));
+                }
+                OpCode::ForEntry => {
+                    let slot = base + operand(0);
+                    let (Value::Coop(items), Value::Num(position)) = (&self.stack[slot], &self.stack[slot + 1]) else {
+                        return Err(invalid("invalid bytecode", chunk.span_at(start)));
+                    };
+                    let position = *position as usize;
+                    let next = items.borrow().elements.get(position..position + 2).map(<[Value]>::to_vec);
+                    match next {
+                        Some(pair) => {
+                            self.stack[slot + 1] = Value::Num(position as i64 + 2);
+                            self.stack.extend(pair);
+                        }
+                        None => ip += operand(1),
+                    }
+                }
+                OpCode::Coop => {
+                    let values = self.pop_many(operand(0));
+                    self.stack.push(Value::coop(values));
+                }
+                OpCode::BarnMap => {
+                    let mut values = self.pop_many(2 * operand(0)).into_iter();
+                    let mut map = BarnMap::new();
+                    while let (Some(key), Some(value)) = (values.next(), values.next()) {
+                        map.put(key, value);
+                    }
+                    self.stack.push(Value::barn_map(map));
+                }
+                OpCode::Range => {
+                    let bounds = self.pop_many(operand(0));
+                    self.stack.push(Value::Range(Range::new(&bounds, chunk.span_at(start))?));
+                }
+                OpCode::GetIndex => {
+                    let index = self.pop();
+                    let object = self.pop();
+                    self.stack.push(element(&object, &index, chunk.span_at(start))?);
+                }
+                OpCode::SetIndex => {
+                    let value = self.pop();
+                    let index = self.pop();
+                    let object = self.pop();
+                    set_element(&object, index, value.clone(), chunk.span_at(start))?;
+                    self.stack.push(value);
+                }
+                OpCode::GetField | OpCode::SetField => {
+                    let Some(Constant::Text(name)) = chunk.constants.get(operand(0)) else {
+                        return Err(invalid("invalid bytecode", chunk.span_at(start)));
+                    };
+                    if op == OpCode::GetField {
+                        let object = self.pop();
+                        self.stack.push(get_field(&object, name, chunk.span_at(start))?);
+                    } else {
+                        let value = self.pop();
+                        let object = self.pop();
+                        set_field(&object, name, value.clone(), chunk.span_at(start))?;
+                        self.stack.push(value);
+                    }
+                }
+                OpCode::Call => {
+                    let argc = operand(0);
+                    let span = chunk.span_at(start);
+                    let position = self.stack.len() - argc - 1;
+                    let callee = match self.stack[position].clone() {
+                        Value::Closure(callee) => {
+                            self.frames[frame].ip = ip;
+                            self.push_frame(Rc::clone(&callee), argc, span)?;
+                            callee
+                        }
+                        Value::Stall(stall) => {
+                            // Call `init` with a new instance as `self`; it returns the instance
+                            let Value::Closure(init) = &stall.init else {
+                                return Err(invalid("invalid bytecode", span));
+                            };
+                            let instance = Instance::new(Rc::clone(&stall));
+                            self.stack[position] = Value::Closure(Rc::clone(init));
+                            self.stack.insert(position + 1, Value::Instance(Rc::new(RefCell::new(instance))));
+                            self.frames[frame].ip = ip;
+                            self.push_method_frame(Rc::clone(init), argc, span)?;
+                            Rc::clone(init)
+                        }
+                        other => return Err(invalid(format!("`{}` cannot be called", other.type_name()), span)),
+                    };
+                    frame += 1;
+                    closure = callee;
+                    base = self.frames[frame].base;
+                    ip = 0;
+                }
+                OpCode::CallMethod => {
+                    let method = METHODS.get(operand(0)).copied()
+                        .ok_or_else(|| invalid("invalid bytecode", chunk.span_at(start)))?;
+                    let args = self.pop_many(operand(1));
+                    let object = self.pop();
+                    self.stack.push(call_method(&object, method, &args, chunk.span_at(start))?);
+                }
+                OpCode::CallBuiltin => {
+                    let builtin = BUILTINS.get(operand(0)).copied()
+                        .ok_or_else(|| invalid("invalid bytecode", chunk.span_at(start)))?;
+                    let args = self.pop_many(operand(1));
+                    self.stack.push(self.stdlib.call(builtin, &args, chunk.span_at(start))?);
+                }
+                OpCode::Invoke => {
+                    let Some(Constant::Text(name)) = chunk.constants.get(operand(0)) else {
+                        return Err(invalid("invalid bytecode", chunk.span_at(start)));
+                    };
+                    let argc = operand(1);
+                    let span = chunk.span_at(start);
+                    let position = self.stack.len() - argc - 1;
+                    let stall_method = match &self.stack[position] {
+                        Value::Instance(instance) => instance.borrow().stall.method(name).cloned(),
+                        _ => None,
+                    };
+                    if let Some(Value::Closure(method)) = stall_method {
+                        // The method sits below the instance, which becomes `self`
+                        self.stack.insert(position, Value::Closure(Rc::clone(&method)));
+                        self.frames[frame].ip = ip;
+                        self.push_method_frame(Rc::clone(&method), argc, span)?;
+                        frame += 1;
+                        closure = method;
+                        base = self.frames[frame].base;
+                        ip = 0;
+                        continue;
+                    }
+                    let args = self.pop_many(argc);
+                    let object = self.pop();
+                    let method = METHODS.get(operand(2)).copied().ok_or_else(|| no_method(&object, name, span))?;
+                    self.stack.push(call_method(&object, method, &args, span)?);
+                }
+                OpCode::Closure => {
+                    let Constant::Function(proto) = &chunk.constants[operand(0)] else {
+                        return Err(invalid("invalid bytecode", chunk.span_at(start)));
+                    };
+                    let upvalues = proto.captures.iter()
+                        .map(|capture| match capture {
+                            Capture::Cell(cell) => Rc::clone(&self.frames[frame].cells[*cell as usize]),
+                            Capture::Upvalue(upvalue) => Rc::clone(&closure.upvalues[*upvalue as usize]),
+                        })
+                        .collect();
+                    let proto = Rc::clone(proto);
+                    self.stack.push(Value::Closure(Rc::new(Closure { proto, upvalues })));
+                }
+                OpCode::Stall => {
+                    let Constant::Stall(proto) = &chunk.constants[operand(0)] else {
+                        return Err(invalid("invalid bytecode", chunk.span_at(start)));
+                    };
+                    let mut closures = self.pop_many(proto.methods.len() + 1).into_iter();
+                    let init = closures.next().expect("init is on the stack");
+                    let stall = Stall {
+                        name: proto.name.clone(),
+                        fields: proto.fields.clone(),
+                        methods: proto.methods.iter().cloned().zip(closures).collect(),
+                        init,
+                    };
+                    self.stack.push(Value::Stall(Rc::new(stall)));
+                }
+                OpCode::Return => {
+                    let result = self.pop();
+                    let finished = self.frames.pop().expect("a frame is running");
+                    // Drop the arguments, locals and the called function
+                    self.stack.truncate(finished.base - 1);
+                    if self.frames.is_empty() {
+                        return Ok(());
+                    }
+                    self.stack.push(result);
+                    frame -= 1;
+                    closure = Rc::clone(&self.frames[frame].closure);
+                    base = self.frames[frame].base;
+                    ip = self.frames[frame].ip;
+                }
+                OpCode::Print => {
+                    let values = self.pop_many(operand(0));
+                    let line: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+                    writeln!(self.out, "{}", line.join(" ")).map_err(|error| RuntimeError::Output {
+                        message: error.to_string(),
+                        kind: error.kind(),
+                    })?;
+                }
+            }
+        }
+    }
+}
+
+/// `num` arithmetic and comparisons without the error handling of
+/// `operations::binary`; `None` when the slow path is needed
+fn fast_binary(op: BinaryOp, left: &Value, right: &Value) -> Option<Value> {
+    let (Value::Num(a), Value::Num(b)) = (left, right) else {
+        return None;
+    };
+    let value = match op {
+        BinaryOp::Add => Value::Num(a.checked_add(*b)?),
+        BinaryOp::Subtract => Value::Num(a.checked_sub(*b)?),
+        BinaryOp::Multiply => Value::Num(a.checked_mul(*b)?),
+        BinaryOp::IntegerDivide => Value::Num(numeric::floor_div(*a, *b)?),
+        BinaryOp::Modulo => Value::Num(numeric::floor_mod(*a, *b)?),
+        BinaryOp::Less => Value::Boolean(a < b),
+        BinaryOp::Greater => Value::Boolean(a > b),
+        BinaryOp::Is => Value::Boolean(a == b),
+        BinaryOp::IsNot => Value::Boolean(a != b),
+        _ => return None,
+    };
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_source;
+    use crate::runtime::codegen::compile;
+    use crate::runtime::interpreter::Interpreter;
+    use crate::toml_config::language_config::{LanguageKeywordManager, MooConfig};
+
+    /// Run `source` on both engines, checking they agree
+    fn run(source: &str) -> Result<String, RuntimeError> {
+        let manager = LanguageKeywordManager::from_config(MooConfig::from_default());
+        let program = parse_source(source, &manager, "en").unwrap();
+
+        let mut expected = Vec::new();
+        let expected_result = Interpreter::new(&mut expected).run(&program);
+        let mut out = Vec::new();
+        let result = Vm::new(&mut out).run(&compile(&program)?);
+        assert_eq!(result, expected_result, "{}", source);
+        assert_eq!(out, expected, "{}", source);
+        result.map(|()| String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_runs_like_the_interpreter() {
+        let source = r#"
+num count = 3
+dec price = 2
+coop 'dec' prices = [1.5, 2]
+prices[1] = 4
+moo "Count is:", count, price, prices, 7 / 2, -7 // 2, -7 % 2, count is 3
+barn_map 'text':'num' inventory = {"cow": 5}
+inventory["pig"] = 2
+inventory["cow"]++
+moo inventory, inventory.collect(), to_num(2.9), to_text(1.5) + "!"
+farmfunction 'num' fib(num n) {
+    if (n < 2) {
+        return n
+    }
+    return fib(n - 1) + fib(n - 2)
+}
+num total = 0
+for index in barn(10) {
+    total = total + fib(index)
+}
+for letter in "moo" {
+    moo letter
+}
+while (total > 40) {
+    total--
+}
+moo total, fib(20)
+"#;
+        assert_eq!(
+            run(source).unwrap(),
+            "Count is: 3 2.0 [1.5, 4] 3.5 -4 1 true\n{\"cow\": 6, \"pig\": 2} 2 2 1.5!\nm\no\no\n40 6765\n"
+        );
+    }
+
+    #[test]
+    fn test_stalls() {
+        let source = r#"
+stall Cow {
+    text name
+    num age = 1
+    dec weight = 2
+    coop 'text' friends = []
+    farmfunction Cow(text name) {
+        self.name = name
+    }
+    farmfunction 'num' birthday(num years) {
+        self.age = self.age + years
+        self.weight++
+        return self.age
+    }
+    farmfunction befriend(Cow other) {
+        farmfunction add() {
+            self.friends.plant(other.name)
+        }
+        add()
+    }
+}
+Cow daisy = Cow("Daisy")
+Cow bella = Cow("Bella")
+moo daisy.birthday(2), daisy.age, daisy.weight, bella.age++, ++bella.age
+daisy.befriend(bella)
+moo daisy, daisy is bella, Cow
+"#;
+        assert_eq!(
+            run(source).unwrap(),
+            "3 3 3.0 1 3\nCow {name: \"Daisy\", age: 3, weight: 3.0, friends: [\"Bella\"]} false <stall Cow>\n"
+        );
+        let error = run("stall Cow {\n    farmfunction Cow(num age) {\n    }\n}\nCow(1, 2)").unwrap_err();
+        assert_eq!(error.to_string(), "`Cow` takes 1 argument(s) but 2 were given at 5:1");
+        let error = run("stall Cow {\n    num age\n}\nCow().plant(1)").unwrap_err();
+        assert_eq!(error.to_string(), "cannot call `add` on `stall` with 1 argument(s) at 4:1");
+    }
+
+    #[test]
+    fn test_function_called_before_the_global_it_reads() {
+        let error = run("farmfunction report() {\n    moo herd\n}\nreport()\nnum herd = 3\n").unwrap_err();
+        assert_eq!(error.to_string(), "`herd` is not defined at 2:9");
+        assert_eq!(run("farmfunction report() {\n    moo herd\n}\nnum herd = 3\nreport()\n").unwrap(), "3\n");
+    }
+
+    #[test]
+    fn test_leave_and_skip() {
+        let source = r#"
+num total = 0
+for i in barn(10) {
+    num doubled = i * 2
+    if (i % 2 is 0) {
+        text note = "even"
+        skip
+    }
+    if (i > 6) {
+        leave
+    }
+    total = total + doubled
+}
+barn_map 'text':'num' counts = {"a": 1, "b": 2}
+num n = 0
+while true {
+    n++
+    for key, value in counts {
+        if (value is 2) {
+            leave
+        }
+        moo key, n
+    }
+    if (n is 3) {
+        leave
+    }
+}
+farmfunction 'num' first(coop 'num' items) {
+    for item in items {
+        if (item > 1) {
+            return item
+        }
+    }
+    return 0
+}
+moo total, n, first([1, 5, 9])
+"#;
+        assert_eq!(run(source).unwrap(), "a 1\na 2\na 3\n18 3 5\n");
+    }
+
+    #[test]
+    fn test_compound_and_multiple_assignment() {
+        let source = r#"
+num total = 10
+dec price = 2
+text name = "Daisy"
+coop 'num' counts = [1, 2, 3]
+barn_map 'text':'num' inventory = {"cow": 3}
+stall Cow {
+    num age
+}
+Cow bella = Cow()
+total += 5
+total -= 1
+total *= 3
+total //= 4
+total %= 7
+price /= 4
+price += 1
+name += " May"
+counts[1] += 10
+inventory["cow"] *= 2
+inventory["pig"] = 1
+bella.age += 2
+num a = 1
+num b = 2
+a, b = b, a
+counts[0], counts[2] = counts[2], counts[0]
+moo total, price, name, counts, inventory, bella.age, a, b
+farmfunction 'num' sum(coop 'num' items) {
+    num result = 0
+    for item in items {
+        num doubled = 0
+        doubled, result = item * 2, result + item
+    }
+    return result
+}
+moo sum(counts)
+"#;
+        assert_eq!(
+            run(source).unwrap(),
+            "3 1.5 Daisy May [3, 12, 1] {\"cow\": 6, \"pig\": 1} 2 2 1\n16\n"
+        );
+    }
+
+    #[test]
+    fn test_nested_functions_share_variables() {
+        let source = r#"
+farmfunction 'coop' process(coop 'text' animals, num start) {
+    num total = start
+    coop 'text' seen = []
+    farmfunction update(text animal, num modifier) {
+        total = total + modifier
+        seen.plant(animal)
+        farmfunction report() {
+            moo animal, total
+        }
+        report()
+    }
+    for animal in animals {
+        update(animal, animal.collect())
+    }
+    moo start, total
+    return seen
+}
+farmfunction counter() {
+    num count = 0
+    farmfunction next() {
+        count++
+    }
+    next()
+    next()
+    moo count
+}
+moo process(["cow", "pig"], 1)
+counter()
+"#;
+        assert_eq!(run(source).unwrap(), "cow 4\npig 7\n1 7\n[\"cow\", \"pig\"]\n2\n");
+    }
+
+    #[test]
+    fn test_coop_methods() {
+        let source = r#"
+coop 'num' counts = [5, 3, 9]
+counts.insert(1, 7)
+counts.sort()
+moo counts, counts.index_of(7), counts.index_of(4)
+counts.reverse()
+moo counts.slice(1, 3), counts.remove_at(0), counts
+counts.remove(5)
+moo counts, counts.contains(3)
+"#;
+        assert_eq!(run(source).unwrap(), "[3, 5, 7, 9] 2 -1\n[7, 5] 9 [7, 5, 3]\n[7, 3] true\n");
+
+        let source = "coop 'num' counts = [1]\ncoop 'coop' pens = [counts]\npens[0].add(\"goat\")";
+        assert_eq!(
+            run(source),
+            Err(RuntimeError::ElementTypeMismatch {
+                value: "\"goat\"".to_string(),
+                container: "coop 'num'".to_string(),
+                span: Span::new(3, 1, 19),
+            })
+        );
+        assert_eq!(
+            run("coop 'num' counts = [1]\nmoo counts[-1]"),
+            Err(RuntimeError::NegativeIndex { index: -1, span: Span::new(2, 5, 10) })
+        );
+    }
+
+    #[test]
+    fn test_barn_map_entries() {
+        let source = r#"
+barn_map 'text':'num' stock = {"pig": 2, "cow": 5, "hen": 9}
+stock.put("cow", 6)
+stock.remove("pig")
+stock.put("pig", 1)
+farmfunction report(barn_map 'text':'num' m) {
+    for animal, count in m {
+        farmfunction show() {
+            moo animal, count
+        }
+        show()
+    }
+}
+report(stock)
+moo stock.keys(), stock.values(), stock.size()
+"#;
+        assert_eq!(run(source).unwrap(), "cow 6\nhen 9\npig 1\n[\"cow\", \"hen\", \"pig\"] [6, 9, 1] 3\n");
+    }
+
+    #[test]
+    fn test_barn_ranges() {
+        let source = r#"
+barn steps = barn(10, 0, -3)
+moo steps, steps.size(), steps[1], steps.contains(4), steps.contains(5)
+farmfunction 'num' total(barn numbers) {
+    num sum = 0
+    for n in numbers {
+        sum = sum + n
+    }
+    return sum
+}
+moo total(steps), total(barn(5)), [barn(3), barn(1, 2)], barn(0, 4, 2) is barn(0, 3, 2)
+barn huge = barn(0, 1000000000000)
+moo huge.size(), huge[999999999999]
+"#;
+        assert_eq!(
+            run(source).unwrap(),
+            "barn(10, 0, -3) 4 7 true false\n22 10 [barn(0, 3), barn(1, 2)] true\n1000000000000 999999999999\n"
+        );
+    }
+
+    #[test]
+    fn test_standard_library() {
+        let source = r#"
+texto nombre = escuchar()
+muuu mayusculas(nombre), largo(nombre), escuchar(), escuchar() es ""
+granja 'texto' partes = dividir("vaca,cerdo", ",")
+muuu partes, unir(partes, " y "), dividir("muu", "")
+muuu absoluto(-4), absoluto(-2.5), minimo(3, 7), maximo(3, 7.5), raiz(16), aleatorio(4, 4)
+"#;
+        let manager = LanguageKeywordManager::from_config(MooConfig::from_default());
+        let program = parse_source(source, &manager, "es").unwrap();
+        let input: &[u8] = b"lola\r\nmargarita\n";
+
+        let mut expected = Vec::new();
+        Interpreter::new(&mut expected).with_input(input).run(&program).unwrap();
+        let mut out = Vec::new();
+        Vm::new(&mut out).with_input(input).run(&compile(&program).unwrap()).unwrap();
+        assert_eq!(out, expected);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "LOLA 4 margarita true\n[\"vaca\", \"cerdo\"] vaca y cerdo [\"m\", \"u\", \"u\"]\n4 2.5 3 7.5 4.0 4\n"
+        );
+    }
+
+    #[test]
+    fn test_names_spelled_like_builtins() {
+        let source = r#"
+establo Vaca {
+    numero tamaño
+}
+Vaca lola = Vaca()
+lola.tamaño = 3
+numero largo = 2
+granja 'numero' pesos = [1]
+pesos.agregar(largo)
+muuu lola, largo, lola.tamaño, pesos.tamaño()
+"#;
+        let manager = LanguageKeywordManager::from_config(MooConfig::from_default());
+        let program = parse_source(source, &manager, "es").unwrap();
+
+        let mut expected = Vec::new();
+        let expected_result = Interpreter::new(&mut expected).run(&program);
+        let mut out = Vec::new();
+        assert_eq!(Vm::new(&mut out).run(&compile(&program).unwrap()), expected_result);
+        assert_eq!(out, expected);
+        assert_eq!(String::from_utf8(out).unwrap(), "Vaca {tamaño: 3} 2 3 2\n");
+    }
+
+    #[test]
+    fn test_seeded_random() {
+        let source = "moo random(1, 100), random(1, 100), random(1, 100), random(-5, 5)";
+        let manager = LanguageKeywordManager::from_config(MooConfig::from_default());
+        let program = parse_source(source, &manager, "en").unwrap();
+        let compiled = compile(&program).unwrap();
+
+        let mut expected = Vec::new();
+        Interpreter::new(&mut expected).with_seed(42).run(&program).unwrap();
+        for _ in 0..2 {
+            let mut out = Vec::new();
+            Vm::new(&mut out).with_seed(42).run(&compiled).unwrap();
+            assert_eq!(out, expected);
+        }
+        // Pinned so the numbers stay the same across platforms and releases
+        assert_eq!(String::from_utf8(expected).unwrap(), "75 16 28 -2\n");
+    }
+
+    #[test]
+    fn test_errors_match_the_interpreter() {
+        let sources = [
+            "coop 'num' xs = [1, 2]\nmoo xs[2]",
+            "barn_map 'text':'num' m = {}\nmoo m[\"pig\"]",
+            "num zero = 0\nmoo 1 // zero",
+            "moo to_num(\"cow\")",
+            "num big = 9223372036854775807\nbig++",
+            "num step = 0\nmoo barn(1, 5, step)",
+            "barn pen = barn(3)\nmoo pen[3]",
+            "num below = -1\nmoo sqrt(below)",
+            "moo random(6, 1)",
+        ];
+        for source in sources {
+            run(source).unwrap_err();
+        }
+        let source = "farmfunction forever(num n) {\n    forever(n + 1)\n}\nforever(0)";
+        let manager = LanguageKeywordManager::from_config(MooConfig::from_default());
+        let program = compile(&parse_source(source, &manager, "en").unwrap()).unwrap();
+        let error = Vm::new(&mut Vec::new()).run(&program).unwrap_err();
+        assert_eq!(error, RuntimeError::RecursionLimit { span: Span::new(2, 5, 14) });
+    }
+}
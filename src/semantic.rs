@@ -0,0 +1,9 @@
+pub mod types;
+pub mod checker;
+pub mod resolver;
+pub mod flow;
+
+pub use types::*;
+pub use checker::*;
+pub use resolver::*;
+pub use flow::*;
@@ -0,0 +1,962 @@
+//! Static type checking.
+//!
+//! Runs over a parsed `Program` and reports type errors as diagnostics:
+//! declarations and assignments whose value does not fit the declared type,
+//! operators applied to the wrong operands, non-`boolean` conditions, calls
+//! with the wrong number or types of arguments, `return` values that do not
+//! match the function's declared type, and elements of the wrong type passed
+//! to `plant` / `put`, and fields or methods a stall does not have.
+//!
+//! Names the checker cannot find are typed `Unknown` and not reported here;
+//! undefined names are the resolver's job.
+
+use std::collections::HashMap;
+
+use crate::parser::ast::*;
+use crate::semantic::types::{conversion_hint, Builtin, Method, Type};
+use crate::utils::diagnostics::Diagnostic;
+use crate::utils::tokens::Span;
+
+/// Type check `program`, returning every type error found
+pub fn check_types(program: &Program) -> Vec<Diagnostic> {
+    let mut checker = TypeChecker::new();
+    checker.check_program(program);
+    checker.diagnostics
+}
+
+/// Field and method types of a declared stall
+#[derive(Debug, Clone, Default)]
+struct StallType {
+    fields: Vec<(String, Type)>,
+    methods: Vec<(String, Type)>,
+}
+
+pub struct TypeChecker {
+    scopes: Vec<HashMap<String, Type>>,
+    /// Every stall declared so far, by name
+    stalls: HashMap<String, StallType>,
+    /// Declared return type of each enclosing function, innermost last.
+    /// `None` for functions without a `'type'`, which may return anything.
+    return_types: Vec<Option<Type>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Default for TypeChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TypeChecker {
+    pub fn new() -> Self {
+        Self {
+            scopes: vec![HashMap::new()],
+            stalls: HashMap::new(),
+            return_types: Vec::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    pub fn check_program(&mut self, program: &Program) {
+        self.block(&program.statements);
+    }
+
+    // ================================
+    // Statements
+    // ================================
+
+    /// Check statements in the current scope. Functions and stalls are
+    /// declared first so they can be used before their definition.
+    fn block(&mut self, statements: &[Stmt]) {
+        for stmt in statements {
+            match &stmt.kind {
+                StmtKind::Function(function) => {
+                    let ty = function_type(function);
+                    self.declare(&function.name, ty);
+                }
+                StmtKind::Stall(stall) => self.declare_stall(stall),
+                _ => {}
+            }
+        }
+        for stmt in statements {
+            self.statement(stmt);
+        }
+    }
+
+    fn scoped_block(&mut self, statements: &[Stmt]) {
+        self.scopes.push(HashMap::new());
+        self.block(statements);
+        self.scopes.pop();
+    }
+
+    fn statement(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::VarDecl { ty, name, initializer } => {
+                let declared = Type::from(ty);
+                if let Some(initializer) = initializer {
+                    let value = self.expression_expecting(initializer, &declared);
+                    if !declared.accepts(&value) {
+                        self.mismatch(
+                            format!("cannot assign `{}` to `{}` of type `{}`", value, name, declared),
+                            initializer.span,
+                            &declared,
+                            &value,
+                        );
+                    }
+                }
+                self.declare(name, declared);
+            }
+            StmtKind::Assign { target, value } => self.assign(target, value),
+            StmtKind::CompoundAssign { target, op, value } => {
+                let target_type = self.assignment_target(target);
+                let value_type = self.value(value);
+                let result = self.binary(*op, &target_type, &value_type, stmt.span);
+                if !target_type.accepts(&result) {
+                    self.mismatch(
+                        format!("cannot assign `{}` to `{}`", result, target_type),
+                        value.span,
+                        &target_type,
+                        &result,
+                    );
+                }
+            }
+            StmtKind::MultiAssign { targets, values } => {
+                for (target, value) in targets.iter().zip(values) {
+                    self.assign(target, value);
+                }
+            }
+            StmtKind::Print { values } => {
+                for value in values {
+                    self.value(value);
+                }
+            }
+            StmtKind::If { branches, else_branch } => {
+                for (condition, body) in branches {
+                    self.condition(condition);
+                    self.scoped_block(body);
+                }
+                if let Some(body) = else_branch {
+                    self.scoped_block(body);
+                }
+            }
+            StmtKind::While { condition, body } => {
+                self.condition(condition);
+                self.scoped_block(body);
+            }
+            StmtKind::For { variable, value, iterable, body } => {
+                let scope = match value {
+                    Some(value) => {
+                        let (key_type, value_type) = self.entry_types(iterable);
+                        HashMap::from([(variable.clone(), key_type), (value.clone(), value_type)])
+                    }
+                    None => HashMap::from([(variable.clone(), self.element_type(iterable))]),
+                };
+                self.scopes.push(scope);
+                self.block(body);
+                self.scopes.pop();
+            }
+            StmtKind::Function(function) => self.function(function),
+            StmtKind::Stall(stall) => self.stall(stall),
+            StmtKind::Return(value) => self.return_statement(value.as_ref(), stmt.span),
+            StmtKind::Expression(expr) => {
+                self.expression(expr);
+            }
+            StmtKind::Break | StmtKind::Continue | StmtKind::Bring { .. } | StmtKind::Error => {}
+        }
+    }
+
+    fn function(&mut self, function: &FunctionDecl) {
+        let return_type = function.return_type.as_ref().map(Type::from);
+        self.function_body(function, HashMap::new(), return_type);
+    }
+
+    /// Check `function` with `scope` holding any names besides its parameters
+    fn function_body(&mut self, function: &FunctionDecl, mut scope: HashMap<String, Type>, return_type: Option<Type>) {
+        scope.extend(function.params.iter().map(|param| (param.name.clone(), Type::from(&param.ty))));
+        self.scopes.push(scope);
+        self.return_types.push(return_type);
+        self.block(&function.body);
+        self.return_types.pop();
+        self.scopes.pop();
+    }
+
+    /// Record a stall's fields and methods, and its name as its constructor
+    fn declare_stall(&mut self, stall: &StallDecl) {
+        let stall_type = StallType {
+            fields: stall.fields.iter().map(|field| (field.name.clone(), Type::from(&field.ty))).collect(),
+            methods: stall.methods().map(|method| (method.name.clone(), function_type(method))).collect(),
+        };
+        self.stalls.insert(stall.name.clone(), stall_type);
+        let params = stall.constructor().map_or_else(Vec::new, |constructor| {
+            constructor.params.iter().map(|param| Type::from(&param.ty)).collect()
+        });
+        let returns = Box::new(Type::Stall(stall.name.clone()));
+        self.declare(&stall.name, Type::Function { params, returns });
+    }
+
+    fn stall(&mut self, stall: &StallDecl) {
+        let receiver = HashMap::from([(SELF.to_string(), Type::Stall(stall.name.clone()))]);
+
+        // Field initializers run at the start of the constructor, in its scope
+        let mut scope = receiver.clone();
+        if let Some(constructor) = stall.constructor() {
+            scope.extend(constructor.params.iter().map(|param| (param.name.clone(), Type::from(&param.ty))));
+        }
+        self.scopes.push(scope);
+        for field in &stall.fields {
+            let Some(initializer) = &field.initializer else { continue };
+            let declared = Type::from(&field.ty);
+            let value = self.expression_expecting(initializer, &declared);
+            if !declared.accepts(&value) {
+                self.mismatch(
+                    format!("cannot assign `{}` to field `{}` of type `{}`", value, field.name, declared),
+                    initializer.span,
+                    &declared,
+                    &value,
+                );
+            }
+        }
+        self.scopes.pop();
+
+        for function in &stall.functions {
+            let return_type = if function.name == stall.name {
+                if function.return_type.is_some() {
+                    self.error(format!("the constructor of `{}` cannot declare a return type", stall.name), function.span);
+                }
+                // Constructors return the new instance, never a value of their own
+                Some(Type::Nothing)
+            } else {
+                function.return_type.as_ref().map(Type::from)
+            };
+            self.function_body(function, receiver.clone(), return_type);
+        }
+    }
+
+    fn return_statement(&mut self, value: Option<&Expr>, span: Span) {
+        let expected = self.return_types.last().cloned().flatten().unwrap_or(Type::Unknown);
+        let value_type = value.map(|value| self.expression_expecting(value, &expected));
+        let Some(declared) = self.return_types.last().cloned() else {
+            self.error("`return` outside of a farmfunction", span);
+            return;
+        };
+        if declared == Some(Type::Nothing) {
+            if let Some(value) = value {
+                self.error("a constructor cannot return a value", value.span);
+            }
+            return;
+        }
+        match (declared, value_type) {
+            (Some(declared), Some(found)) if !declared.accepts(&found) => {
+                self.mismatch(
+                    format!("farmfunction returns `{}` but this returns `{}`", declared, found),
+                    value.map_or(span, |value| value.span),
+                    &declared,
+                    &found,
+                );
+            }
+            (Some(declared), None) => {
+                self.error(format!("farmfunction must return a `{}` value", declared), span);
+            }
+            _ => {}
+        }
+    }
+
+    /// Type of an assignment target; only variables, elements and fields
+    /// can be assigned
+    fn assign(&mut self, target: &Expr, value: &Expr) {
+        let target_type = self.assignment_target(target);
+        let value_type = self.expression_expecting(value, &target_type);
+        if !target_type.accepts(&value_type) {
+            self.mismatch(
+                format!("cannot assign `{}` to `{}`", value_type, target_type),
+                value.span,
+                &target_type,
+                &value_type,
+            );
+        }
+    }
+
+    fn assignment_target(&mut self, target: &Expr) -> Type {
+        match &target.kind {
+            ExprKind::Variable(name) if name == SELF => {
+                self.error("`self` cannot be assigned; assign its fields instead", target.span);
+                Type::Unknown
+            }
+            ExprKind::Variable(_) | ExprKind::Index { .. } | ExprKind::Member { .. } => self.expression(target),
+            _ => {
+                self.error("only variables, elements and fields can be assigned", target.span);
+                Type::Unknown
+            }
+        }
+    }
+
+    fn condition(&mut self, condition: &Expr) {
+        let ty = self.expression(condition);
+        if !Type::Boolean.accepts(&ty) {
+            self.error(format!("condition must be `boolean`, found `{}`", ty), condition.span);
+        }
+    }
+
+    /// Type of each value a `for` loop visits
+    fn element_type(&mut self, iterable: &Expr) -> Type {
+        match self.expression(iterable) {
+            Type::Coop(element) => *element,
+            Type::BarnMap(key, _) => *key,
+            Type::Range => Type::Num,
+            Type::Text => Type::Text,
+            Type::Unknown => Type::Unknown,
+            other => {
+                self.error(format!("cannot loop over `{}`", other), iterable.span);
+                Type::Unknown
+            }
+        }
+    }
+
+    /// Key and value types of the barn_map a `for key, value` loop visits
+    fn entry_types(&mut self, iterable: &Expr) -> (Type, Type) {
+        match self.expression(iterable) {
+            Type::BarnMap(key, value) => (*key, *value),
+            Type::Unknown => (Type::Unknown, Type::Unknown),
+            other => {
+                self.error(format!("`for key, value` loops over a `barn_map`, not `{}`", other), iterable.span);
+                (Type::Unknown, Type::Unknown)
+            }
+        }
+    }
+
+    // ================================
+    // Expressions
+    // ================================
+
+    /// An expression whose value is used, so it must produce one
+    fn value(&mut self, expr: &Expr) -> Type {
+        let ty = self.expression(expr);
+        if ty == Type::Nothing {
+            self.error("this call does not produce a value", expr.span);
+            return Type::Unknown;
+        }
+        ty
+    }
+
+    fn expression(&mut self, expr: &Expr) -> Type {
+        match &expr.kind {
+            ExprKind::Integer(_) => Type::Num,
+            ExprKind::Decimal(_) => Type::Dec,
+            ExprKind::Text(_) => Type::Text,
+            ExprKind::Boolean(_) => Type::Boolean,
+            ExprKind::Variable(name) => match self.lookup(name) {
+                Some(ty) => ty,
+                None => {
+                    match Builtin::lookup(name) {
+                        Some(builtin) if builtin.is_conversion() => self.error(
+                            format!("`{}` is a conversion and must be called, e.g. `{}(value)`", name, name),
+                            expr.span,
+                        ),
+                        Some(_) => self.error(format!("`{}` is a builtin and must be called, e.g. `{}(...)`", name, name), expr.span),
+                        None => {}
+                    }
+                    Type::Unknown
+                }
+            },
+            ExprKind::CoopLiteral(elements) => {
+                let mut element_type = Type::Unknown;
+                for element in elements {
+                    let ty = self.value(element);
+                    if !element_type.accepts(&ty) && !ty.accepts(&element_type) {
+                        self.error(
+                            format!("coop elements must all be `{}`, found `{}`", element_type, ty),
+                            element.span,
+                        );
+                    }
+                    element_type = element_type.merge(&ty);
+                }
+                Type::Coop(Box::new(element_type))
+            }
+            ExprKind::BarnMapLiteral(entries) => {
+                let (mut key_type, mut value_type) = (Type::Unknown, Type::Unknown);
+                for (key, value) in entries {
+                    let (key_found, value_found) = (self.value(key), self.value(value));
+                    if !key_type.accepts(&key_found) {
+                        self.error(format!("barn_map keys must all be `{}`, found `{}`", key_type, key_found), key.span);
+                    }
+                    if !value_type.accepts(&value_found) && !value_found.accepts(&value_type) {
+                        self.error(
+                            format!("barn_map values must all be `{}`, found `{}`", value_type, value_found),
+                            value.span,
+                        );
+                    }
+                    key_type = key_type.merge(&key_found);
+                    value_type = value_type.merge(&value_found);
+                }
+                Type::BarnMap(Box::new(key_type), Box::new(value_type))
+            }
+            ExprKind::Range(args) => {
+                if !(1..=3).contains(&args.len()) {
+                    self.error(format!("`barn` takes 1 to 3 arguments but {} were given", args.len()), expr.span);
+                }
+                for arg in args {
+                    self.expect(arg, &Type::Num, "barn bounds");
+                }
+                Type::Range
+            }
+            ExprKind::Grouping(inner) => self.expression(inner),
+            ExprKind::Unary { op: UnaryOp::Negate, operand } => {
+                let ty = self.value(operand);
+                if ty.is_numeric() || ty == Type::Unknown {
+                    ty
+                } else {
+                    self.error(format!("cannot negate `{}`", ty), expr.span);
+                    Type::Unknown
+                }
+            }
+            ExprKind::Binary { op, left, right } => {
+                let left_type = self.value(left);
+                let right_type = self.value(right);
+                self.binary(*op, &left_type, &right_type, expr.span)
+            }
+            ExprKind::Update { op, target, .. } => {
+                let ty = self.assignment_target(target);
+                if ty.is_numeric() || ty == Type::Unknown {
+                    ty
+                } else {
+                    self.error(format!("cannot apply `{}` to `{}`", op.symbol(), ty), expr.span);
+                    Type::Unknown
+                }
+            }
+            ExprKind::Call { callee, args, builtin } => self.call(callee, args, *builtin, expr.span),
+            ExprKind::Index { object, index } => {
+                let object_type = self.value(object);
+                match object_type {
+                    Type::Coop(element) => {
+                        self.expect(index, &Type::Num, "a coop index");
+                        *element
+                    }
+                    Type::BarnMap(key, value) => {
+                        self.expect(index, &key, "a barn_map key");
+                        *value
+                    }
+                    Type::Text => {
+                        self.expect(index, &Type::Num, "a text index");
+                        Type::Text
+                    }
+                    Type::Range => {
+                        self.expect(index, &Type::Num, "a barn index");
+                        Type::Num
+                    }
+                    Type::Unknown => {
+                        self.value(index);
+                        Type::Unknown
+                    }
+                    other => {
+                        self.value(index);
+                        self.error(format!("cannot index into `{}`", other), object.span);
+                        Type::Unknown
+                    }
+                }
+            }
+            ExprKind::Member { object, name } => match self.value(object) {
+                Type::Stall(stall) => self.field(&stall, name, expr.span),
+                Type::Unknown => Type::Unknown,
+                _ => {
+                    self.error(format!("`{}` is a method and must be called, e.g. `{}()`", name, name), expr.span);
+                    Type::Unknown
+                }
+            },
+            ExprKind::Error => Type::Unknown,
+        }
+    }
+
+    fn binary(&mut self, op: BinaryOp, left: &Type, right: &Type, span: Span) -> Type {
+        if *left == Type::Unknown || *right == Type::Unknown {
+            return match op {
+                BinaryOp::Less | BinaryOp::Greater | BinaryOp::Is | BinaryOp::IsNot => Type::Boolean,
+                _ => Type::Unknown,
+            };
+        }
+        match op {
+            BinaryOp::Add if *left == Type::Text && *right == Type::Text => Type::Text,
+            // `/` always divides exactly; `//` is the integer division
+            BinaryOp::Divide if left.is_numeric() && right.is_numeric() => Type::Dec,
+            BinaryOp::Add
+            | BinaryOp::Subtract
+            | BinaryOp::Multiply
+            | BinaryOp::Divide
+            | BinaryOp::IntegerDivide
+            | BinaryOp::Modulo => {
+                if left.is_numeric() && right.is_numeric() {
+                    return left.promote(right);
+                }
+                self.error(format!("cannot apply `{}` to `{}` and `{}`", op.symbol(), left, right), span);
+                Type::Unknown
+            }
+            BinaryOp::Less | BinaryOp::Greater => {
+                if !(left.is_numeric() && right.is_numeric()) {
+                    self.error(format!("cannot compare `{}` and `{}` with `{}`", left, right, op.symbol()), span);
+                }
+                Type::Boolean
+            }
+            BinaryOp::Is | BinaryOp::IsNot => {
+                if !left.accepts(right) && !right.accepts(left) {
+                    self.error(format!("cannot compare `{}` with `{}`", left, right), span);
+                }
+                Type::Boolean
+            }
+        }
+    }
+
+    fn call(&mut self, callee: &Expr, args: &[Expr], builtin: Option<&str>, span: Span) -> Type {
+        if let ExprKind::Member { object, name } = &callee.kind {
+            let object_type = self.value(object);
+            if let Type::Stall(stall) = &object_type {
+                return self.stall_method_call(stall, name, args, span);
+            }
+            return self.method_call(&object_type, name, builtin.and_then(Method::lookup), args, callee.span);
+        }
+        if let ExprKind::Variable(name) = &callee.kind {
+            let builtin = builtin.and_then(Builtin::lookup);
+            if let Some(builtin) = builtin.filter(|_| self.lookup(name).is_none()) {
+                return self.builtin_call(builtin, name, args, span);
+            }
+        }
+
+        match self.value(callee) {
+            Type::Function { params, returns } => {
+                let what = match &callee.kind {
+                    ExprKind::Variable(name) => format!("`{}`", name),
+                    _ => "this farmfunction".to_string(),
+                };
+                self.arguments(&what, &params, args, span);
+                *returns
+            }
+            Type::Unknown => {
+                args.iter().for_each(|arg| {
+                    self.value(arg);
+                });
+                Type::Unknown
+            }
+            other => {
+                self.error(format!("`{}` is not a farmfunction", other), callee.span);
+                Type::Unknown
+            }
+        }
+    }
+
+    /// Check the arguments of a call to a function taking `params`
+    fn arguments(&mut self, what: &str, params: &[Type], args: &[Expr], span: Span) {
+        if params.len() != args.len() {
+            self.error(arity_message(what, params.len(), args.len()), span);
+        }
+        for (arg, param) in args.iter().zip(params) {
+            self.expect(arg, param, "this argument");
+        }
+        for arg in args.iter().skip(params.len()) {
+            self.value(arg);
+        }
+    }
+
+    /// Type of field `name` of an instance of `stall`
+    fn field(&mut self, stall: &str, name: &str, span: Span) -> Type {
+        let stall_type = self.stalls.get(stall).cloned().unwrap_or_default();
+        if let Some((_, ty)) = stall_type.fields.iter().find(|(field, _)| field == name) {
+            return ty.clone();
+        }
+        if stall_type.methods.iter().any(|(method, _)| method == name) {
+            self.error(format!("`{}` is a method and must be called, e.g. `{}()`", name, name), span);
+        } else {
+            self.error(format!("`{}` has no field `{}`", stall, name), span);
+        }
+        Type::Unknown
+    }
+
+    fn stall_method_call(&mut self, stall: &str, name: &str, args: &[Expr], span: Span) -> Type {
+        let method = self.stalls.get(stall)
+            .and_then(|stall_type| stall_type.methods.iter().find(|(method, _)| method == name))
+            .map(|(_, ty)| ty.clone());
+        let Some(Type::Function { params, returns }) = method else {
+            self.error(format!("`{}` has no method `{}`", stall, name), span);
+            args.iter().for_each(|arg| {
+                self.value(arg);
+            });
+            return Type::Unknown;
+        };
+        self.arguments(&format!("`{}`", name), &params, args, span);
+        *returns
+    }
+
+    fn builtin_call(&mut self, builtin: Builtin, name: &str, args: &[Expr], span: Span) -> Type {
+        let params = builtin.params();
+        let arg_types: Vec<Type> = args.iter().enumerate()
+            .map(|(i, arg)| self.expression_expecting(arg, params.get(i).unwrap_or(&Type::Unknown)))
+            .collect();
+        if params.len() != args.len() {
+            self.error(arity_message(&format!("`{}`", name), params.len(), args.len()), span);
+            return builtin.returns(&arg_types);
+        }
+        for ((arg, found), expected) in args.iter().zip(&arg_types).zip(&params) {
+            if builtin.is_conversion() && !builtin.converts(found) {
+                self.error(format!("`{}` cannot convert `{}`", name, found), arg.span);
+            } else if !builtin.is_conversion() && !expected.accepts(found) {
+                let message = format!("`{}` expects `{}` but was given `{}`", name, expected, found);
+                self.mismatch(message, arg.span, expected, found);
+            }
+        }
+        builtin.returns(&arg_types)
+    }
+
+    fn method_call(&mut self, object: &Type, name: &str, method: Option<Method>, args: &[Expr], span: Span) -> Type {
+        let signature = method.and_then(|method| method_signature(method, object));
+        let params = signature.as_ref().map(|(params, _)| params.clone()).unwrap_or_default();
+        let arg_types: Vec<Type> = args.iter().enumerate()
+            .map(|(i, arg)| self.expression_expecting(arg, params.get(i).unwrap_or(&Type::Unknown)))
+            .collect();
+        if *object == Type::Unknown {
+            return Type::Unknown;
+        }
+
+        let Some((params, returns)) = signature else {
+            self.error(format!("`{}` has no method `{}`", object, name), span);
+            return Type::Unknown;
+        };
+        if params.len() != arg_types.len() {
+            self.error(arity_message(&format!("`{}`", name), params.len(), arg_types.len()), span);
+            return returns;
+        }
+
+        for ((arg, found), expected) in args.iter().zip(&arg_types).zip(&params) {
+            if *found == Type::Nothing {
+                continue;
+            }
+            if expected.accepts(found) {
+                continue;
+            }
+            let message = match (method, object) {
+                (Some(Method::Add | Method::Insert), _) => format!("cannot add `{}` to `{}`", found, object),
+                (Some(Method::Put), _) => format!("cannot put `{}` into `{}`", found, object),
+                _ => format!("`{}` expects `{}` but was given `{}`", name, expected, found),
+            };
+            self.error(message, arg.span);
+        }
+        returns
+    }
+
+    /// Check that `expr` has type `expected`, describing it as `what`
+    fn expect(&mut self, expr: &Expr, expected: &Type, what: &str) {
+        let found = self.expression_expecting(expr, expected);
+        if !expected.accepts(&found) {
+            self.mismatch(format!("{} must be `{}`, found `{}`", what, expected, found), expr.span, expected, &found);
+        }
+    }
+
+    /// Type `expr` where a value of type `expected` is wanted. Collection
+    /// literals take their element types from `expected`, so
+    /// `barn_map 'text':'barn_map' m = {"cow": {"age": 5, "color": "brown"}}`
+    /// may hold mixed values where the declaration leaves them open.
+    fn expression_expecting(&mut self, expr: &Expr, expected: &Type) -> Type {
+        match (&expr.kind, expected) {
+            (ExprKind::CoopLiteral(elements), Type::Coop(element)) => {
+                for item in elements {
+                    self.expect(item, element, "this element");
+                }
+                expected.clone()
+            }
+            (ExprKind::BarnMapLiteral(entries), Type::BarnMap(key, value)) => {
+                for (entry_key, entry_value) in entries {
+                    self.expect(entry_key, key, "this key");
+                    self.expect(entry_value, value, "this value");
+                }
+                expected.clone()
+            }
+            (ExprKind::Grouping(inner), _) => self.expression_expecting(inner, expected),
+            _ => self.value(expr),
+        }
+    }
+
+    // ================================
+    // Scopes
+    // ================================
+
+    fn declare(&mut self, name: &str, ty: Type) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), ty);
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<Type> {
+        self.scopes.iter().rev()
+            .find_map(|scope| scope.get(name))
+            .cloned()
+    }
+
+    fn error(&mut self, message: impl Into<String>, span: Span) {
+        self.diagnostics.push(Diagnostic::error_at(message, span));
+    }
+
+    /// A type mismatch, with a conversion hint where one applies
+    fn mismatch(&mut self, message: String, span: Span, expected: &Type, found: &Type) {
+        let mut diagnostic = Diagnostic::error_at(message, span);
+        if let Some(hint) = conversion_hint(expected, found) {
+            diagnostic = diagnostic.with_hint(hint);
+        }
+        self.diagnostics.push(diagnostic);
+    }
+}
+
+fn function_type(function: &FunctionDecl) -> Type {
+    Type::Function {
+        params: function.params.iter().map(|param| Type::from(&param.ty)).collect(),
+        returns: Box::new(function.return_type.as_ref().map_or(Type::Unknown, Type::from)),
+    }
+}
+
+/// Parameter and return types of `method` on a value of type `object`, or
+/// `None` if that type has no such method
+fn method_signature(method: Method, object: &Type) -> Option<(Vec<Type>, Type)> {
+    let signature = match (method, object) {
+        (Method::Add, Type::Coop(element)) => (vec![(**element).clone()], Type::Nothing),
+        (Method::Size, Type::Coop(_) | Type::BarnMap(..) | Type::Text) => (vec![], Type::Num),
+        (Method::Get, Type::Coop(element)) => (vec![Type::Num], (**element).clone()),
+        (Method::Remove, Type::Coop(element)) => (vec![(**element).clone()], Type::Nothing),
+        (Method::Contains, Type::Coop(element)) => (vec![(**element).clone()], Type::Boolean),
+        (Method::RemoveAt, Type::Coop(element)) => (vec![Type::Num], (**element).clone()),
+        (Method::Insert, Type::Coop(element)) => (vec![Type::Num, (**element).clone()], Type::Nothing),
+        (Method::IndexOf, Type::Coop(element)) => (vec![(**element).clone()], Type::Num),
+        (Method::Sort, Type::Coop(element)) if element.is_sortable() => (vec![], Type::Nothing),
+        (Method::Reverse, Type::Coop(_)) => (vec![], Type::Nothing),
+        (Method::Slice, Type::Coop(_)) => (vec![Type::Num, Type::Num], object.clone()),
+        (Method::Size, Type::Range) => (vec![], Type::Num),
+        (Method::Get, Type::Range) => (vec![Type::Num], Type::Num),
+        (Method::Contains, Type::Range) => (vec![Type::Num], Type::Boolean),
+        (Method::Put, Type::BarnMap(key, value)) => (vec![(**key).clone(), (**value).clone()], Type::Nothing),
+        (Method::Get, Type::BarnMap(key, value)) => (vec![(**key).clone()], (**value).clone()),
+        (Method::Remove, Type::BarnMap(key, _)) => (vec![(**key).clone()], Type::Nothing),
+        (Method::Contains, Type::BarnMap(key, _)) => (vec![(**key).clone()], Type::Boolean),
+        (Method::Keys, Type::BarnMap(key, _)) => (vec![], Type::Coop(key.clone())),
+        (Method::Values, Type::BarnMap(_, value)) => (vec![], Type::Coop(value.clone())),
+        _ => return None,
+    };
+    Some(signature)
+}
+
+fn arity_message(what: &str, expected: usize, found: usize) -> String {
+    let plural = if expected == 1 { "" } else { "s" };
+    format!("{} takes {} argument{} but {} were given", what, expected, plural, found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_source;
+    use crate::toml_config::language_config::{LanguageKeywordManager, MooConfig};
+
+    fn check(source: &str, language: &str) -> Vec<String> {
+        let manager = LanguageKeywordManager::from_config(MooConfig::from_default());
+        let program = parse_source(source, &manager, language).unwrap();
+        check_types(&program).iter().map(|d| format!("{}:{} {}", d.line, d.column, d.message)).collect()
+    }
+
+    #[test]
+    fn test_well_typed_program() {
+        let source = "\
+coop 'text' animals = [\"cow\", \"pig\"]
+barn_map 'text':'num' stock = {\"cow\": 5}
+barn_map 'text':'barn_map' details = {\"cow\": {\"age\": 5, \"color\": \"brown\"}}
+farmfunction 'num' total(coop 'num' counts) {
+    num sum = 0
+    for count in counts {
+        sum = sum + count
+    }
+    return sum
+}
+for animal in animals {
+    if (stock.contains(animal)) {
+        stock.put(animal, stock.get(animal) + 1)
+    }
+}
+animals.plant(\"sheep\")
+num size = animals.collect() + total([1, 2])
+moo \"size\", size
+";
+        assert_eq!(check(source, "en"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_type_errors() {
+        let source = "\
+num count = \"ten\"
+coop 'num' counts = [1, 2]
+counts.plant(\"cow\")
+barn_map 'text':'num' stock = {}
+stock.put(1, 2)
+if (count) {
+    moo count + \"s\"
+}
+farmfunction 'text' name(num id) {
+    return id
+}
+name(\"a\", 2)
+";
+        assert_eq!(check(source, "en"), vec![
+            "1:13 cannot assign `text` to `count` of type `num`",
+            "3:14 cannot add `text` to `coop 'num'`",
+            "5:11 cannot put `num` into `barn_map 'text':'num'`",
+            "6:4 condition must be `boolean`, found `num`",
+            "7:9 cannot apply `+` to `num` and `text`",
+            "10:12 farmfunction returns `text` but this returns `num`",
+            "12:1 `name` takes 1 argument but 2 were given",
+            "12:6 this argument must be `num`, found `text`",
+        ]);
+    }
+
+    #[test]
+    fn test_numeric_promotion_and_conversions() {
+        let source = "\
+num count = 3
+dec price = count
+dec total = price * count + 1
+coop 'dec' prices = [1, 2.5]
+num half = count / 2
+num whole = to_num(total // 2)
+text label = to_text(whole) + \" cows\"
+num parsed = to_num(\"12\")
+num wrong = total
+";
+        let manager = LanguageKeywordManager::from_config(MooConfig::from_default());
+        let program = parse_source(source, &manager, "en").unwrap();
+        let diagnostics = check_types(&program);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(messages, vec![
+            "cannot assign `dec` to `half` of type `num`",
+            "cannot assign `dec` to `wrong` of type `num`",
+        ]);
+        assert!(diagnostics[1].hint.as_deref().unwrap().contains("to_num(...)"));
+
+        assert_eq!(check("numero n = a_numero(2.5)\ntexto t = a_texto(n)\n", "es"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_spanish_method_names() {
+        let source = "granja 'numero' cuentas = [1]\ncuentas.agregar(\"vaca\")\nnumero n = cuentas.contar()\n";
+        assert_eq!(check(source, "es"), vec!["2:17 cannot add `text` to `coop 'num'`"]);
+    }
+
+    #[test]
+    fn test_coop_methods() {
+        let source = "\
+coop 'num' counts = [3, 1]
+counts.insert(0, 7)
+counts.sort()
+coop 'num' firsts = counts.slice(0, 2)
+num last = counts.remove_at(counts.index_of(7))
+counts.insert(0, \"cow\")
+coop 'coop' pens = [counts]
+pens.sort()
+text wrong = counts.remove_at(0)
+barn_map 'text':'num' stock = {\"cow\": 5}
+for animal, count in stock {
+    text label = animal + count
+}
+coop 'text' animals = stock.keys()
+for index, animal in animals {
+    moo index
+}
+";
+        assert_eq!(check(source, "en"), vec![
+            "6:18 cannot add `text` to `coop 'num'`",
+            "8:1 `coop 'coop'` has no method `sort`",
+            "9:14 cannot assign `num` to `wrong` of type `text`",
+            "12:18 cannot apply `+` to `text` and `num`",
+            "15:22 `for key, value` loops over a `barn_map`, not `coop 'text'`",
+        ]);
+    }
+
+    #[test]
+    fn test_standard_library() {
+        let source = "\
+num loudest = max(3, 7)
+num root = sqrt(16)
+moo upper(5), join([1, 2], 3), listen(\"name\")
+coop 'text' words = split(\"a b\", \" \")
+moo random
+";
+        assert_eq!(check(source, "en"), vec![
+            "2:12 cannot assign `dec` to `root` of type `num`",
+            "3:11 `upper` expects `text` but was given `num`",
+            "3:28 `join` expects `text` but was given `num`",
+            "3:32 `listen` takes 0 arguments but 1 were given",
+            "5:5 `random` is a builtin and must be called, e.g. `random(...)`",
+        ]);
+    }
+
+    #[test]
+    fn test_barn_ranges() {
+        let source = "\
+barn evens = barn(0, 10, 2)
+num fourth = evens[3]
+barn wrong = barn(1, 2, 3, 4)
+moo barn(\"ten\"), evens.contains(\"cow\")
+text first = evens.get(0)
+";
+        assert_eq!(check(source, "en"), vec![
+            "3:14 `barn` takes 1 to 3 arguments but 4 were given",
+            "4:10 barn bounds must be `num`, found `text`",
+            "4:33 `contains` expects `num` but was given `text`",
+            "5:14 cannot assign `num` to `first` of type `text`",
+        ]);
+    }
+
+    #[test]
+    fn test_stalls() {
+        let source = "\
+stall Cow {
+    text name
+    num age = \"old\"
+    farmfunction 'num' Cow(text n) {
+        self.name = n
+        return 1
+    }
+    farmfunction 'num' older(num years) {
+        self = self
+        return self.age + years
+    }
+}
+Cow daisy = Cow(\"Daisy\")
+num age = daisy.older(2)
+text name = daisy.nam
+moo daisy.older, daisy.fly(), daisy.older(\"two\")
+daisy.age = \"three\"
+Cow wrong = 3
+";
+        assert_eq!(check(source, "en"), vec![
+            "3:15 cannot assign `text` to field `age` of type `num`",
+            "4:5 the constructor of `Cow` cannot declare a return type",
+            "6:16 a constructor cannot return a value",
+            "9:9 `self` cannot be assigned; assign its fields instead",
+            "15:13 `Cow` has no field `nam`",
+            "16:5 `older` is a method and must be called, e.g. `older()`",
+            "16:18 `Cow` has no method `fly`",
+            "16:43 this argument must be `num`, found `text`",
+            "17:13 cannot assign `text` to `num`",
+            "18:13 cannot assign `num` to `wrong` of type `Cow`",
+        ]);
+    }
+
+    #[test]
+    fn test_compound_and_multiple_assignment() {
+        let source = "\
+num count = 1
+text name = \"Daisy\"
+coop 'num' counts = [1]
+count += 2
+count /= 2
+name -= 1
+counts[0] //= 2
+count, name = name, count
+";
+        assert_eq!(check(source, "en"), vec![
+            "5:10 cannot assign `dec` to `num`",
+            "6:1 cannot apply `-` to `text` and `num`",
+            "8:15 cannot assign `text` to `num`",
+            "8:21 cannot assign `num` to `text`",
+        ]);
+    }
+}
@@ -0,0 +1,513 @@
+//! Control-flow analysis.
+//!
+//! Each `farmfunction` body, and the top level of the program, is lowered to
+//! a control-flow graph of basic blocks. The graph is then used to report:
+//!
+//! - typed functions where some path reaches the end without a `return`;
+//! - unreachable statements, such as code after a `return` (warning);
+//! - `while` loops whose condition is always true and that contain no
+//!   `return` or `leave`, so they can never finish (warning);
+//! - variables declared without a value that may be read before they are
+//!   assigned on some path.
+//!
+//! Nested functions, and the farmfunctions of stalls, get their own graph.
+//! Variables they capture from the enclosing function are assumed to be
+//! assigned.
+
+use std::collections::BTreeSet;
+
+use crate::parser::ast::*;
+use crate::utils::diagnostics::Diagnostic;
+use crate::utils::tokens::Span;
+
+/// Run every control-flow check on `program`
+pub fn analyze_flow(program: &Program) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    analyze_body(&program.statements, None, &mut diagnostics);
+    diagnostics
+}
+
+type BlockId = usize;
+
+/// What a basic block does, in order
+#[derive(Debug)]
+enum Event<'a> {
+    /// Evaluate an expression, reading the variables in it
+    Eval(&'a Expr),
+    /// Declare a variable, with or without a value
+    Declare(&'a str, bool),
+    Assign(&'a str),
+}
+
+#[derive(Debug, Default)]
+struct BasicBlock<'a> {
+    events: Vec<Event<'a>>,
+    successors: Vec<BlockId>,
+    predecessors: Vec<BlockId>,
+}
+
+/// Control-flow graph of one function body. Block 0 is the entry and block 1
+/// is the exit that every `return` jumps to.
+#[derive(Debug)]
+struct Cfg<'a> {
+    blocks: Vec<BasicBlock<'a>>,
+}
+
+const ENTRY: BlockId = 0;
+const EXIT: BlockId = 1;
+
+impl<'a> Cfg<'a> {
+    fn new() -> Self {
+        Self { blocks: vec![BasicBlock::default(), BasicBlock::default()] }
+    }
+
+    fn new_block(&mut self) -> BlockId {
+        self.blocks.push(BasicBlock::default());
+        self.blocks.len() - 1
+    }
+
+    fn edge(&mut self, from: BlockId, to: BlockId) {
+        self.blocks[from].successors.push(to);
+        self.blocks[to].predecessors.push(from);
+    }
+
+    fn reachable(&self) -> Vec<bool> {
+        let mut seen = vec![false; self.blocks.len()];
+        let mut stack = vec![ENTRY];
+        while let Some(block) = stack.pop() {
+            if !std::mem::replace(&mut seen[block], true) {
+                stack.extend(&self.blocks[block].successors);
+            }
+        }
+        seen
+    }
+}
+
+/// Lowers statements into a `Cfg`. `current` is `None` while the code being
+/// lowered is unreachable.
+struct Builder<'a, 'd> {
+    cfg: Cfg<'a>,
+    current: Option<BlockId>,
+    /// Number of `return`s lowered so far
+    returns: usize,
+    /// The enclosing loops, innermost last: where `skip` and `leave` go
+    loops: Vec<(BlockId, BlockId)>,
+    /// Functions nested in this body, analysed after it
+    functions: Vec<&'a FunctionDecl>,
+    diagnostics: &'d mut Vec<Diagnostic>,
+}
+
+impl<'a> Builder<'a, '_> {
+    fn block(&mut self, statements: &'a [Stmt]) {
+        for stmt in statements {
+            self.statement(stmt);
+        }
+    }
+
+    /// The block to append to. Code that cannot be reached starts a fresh
+    /// block with no predecessors; its first statement is reported.
+    fn current(&mut self, span: Span) -> BlockId {
+        match self.current {
+            Some(block) => block,
+            None => {
+                let block = self.cfg.new_block();
+                self.diagnostics.push(
+                    Diagnostic::warning_at("unreachable code", span)
+                        .with_hint("nothing before this line lets the program get here"),
+                );
+                self.current = Some(block);
+                block
+            }
+        }
+    }
+
+    fn push(&mut self, event: Event<'a>) {
+        if let Some(block) = self.current {
+            self.cfg.blocks[block].events.push(event);
+        }
+    }
+
+    /// Continue in `to` from the current block, if it is reachable
+    fn jump(&mut self, to: BlockId) {
+        if let Some(from) = self.current {
+            self.cfg.edge(from, to);
+        }
+    }
+
+    fn assign(&mut self, target: &'a Expr) {
+        match &target.kind {
+            ExprKind::Variable(name) => self.push(Event::Assign(name)),
+            _ => self.push(Event::Eval(target)),
+        }
+    }
+
+    fn statement(&mut self, stmt: &'a Stmt) {
+        match &stmt.kind {
+            StmtKind::Function(function) => {
+                self.functions.push(function);
+                return;
+            }
+            // A stall's farmfunctions are analysed like nested functions
+            StmtKind::Stall(stall) => {
+                self.functions.extend(&stall.functions);
+                return;
+            }
+            _ => {}
+        }
+        self.current(stmt.span);
+
+        match &stmt.kind {
+            StmtKind::VarDecl { name, initializer, .. } => {
+                if let Some(initializer) = initializer {
+                    self.push(Event::Eval(initializer));
+                }
+                self.push(Event::Declare(name, initializer.is_some()));
+            }
+            StmtKind::Assign { target, value } => {
+                self.push(Event::Eval(value));
+                self.assign(target);
+            }
+            StmtKind::CompoundAssign { target, value, .. } => {
+                self.push(Event::Eval(value));
+                self.push(Event::Eval(target));
+            }
+            StmtKind::MultiAssign { targets, values } => {
+                for value in values {
+                    self.push(Event::Eval(value));
+                }
+                for target in targets {
+                    self.assign(target);
+                }
+            }
+            StmtKind::Print { values } => {
+                for value in values {
+                    self.push(Event::Eval(value));
+                }
+            }
+            StmtKind::Expression(expr) => self.push(Event::Eval(expr)),
+            StmtKind::Return(value) => {
+                if let Some(value) = value {
+                    self.push(Event::Eval(value));
+                }
+                self.returns += 1;
+                self.jump(EXIT);
+                self.current = None;
+            }
+            StmtKind::Break | StmtKind::Continue => {
+                if let Some(&(header, after)) = self.loops.last() {
+                    self.jump(if stmt.kind == StmtKind::Break { after } else { header });
+                }
+                self.current = None;
+            }
+            StmtKind::If { branches, else_branch } => {
+                let join = self.cfg.new_block();
+                for (condition, body) in branches {
+                    self.push(Event::Eval(condition));
+                    let branch = self.cfg.new_block();
+                    let next = self.cfg.new_block();
+                    self.jump(branch);
+                    self.jump(next);
+
+                    self.current = Some(branch);
+                    self.block(body);
+                    self.jump(join);
+                    self.current = Some(next);
+                }
+                if let Some(body) = else_branch {
+                    self.block(body);
+                }
+                self.jump(join);
+                self.current = (!self.cfg.blocks[join].predecessors.is_empty()).then_some(join);
+            }
+            StmtKind::While { condition, body } => {
+                let header = self.cfg.new_block();
+                let after = self.cfg.new_block();
+                self.jump(header);
+                self.current = Some(header);
+                self.push(Event::Eval(condition));
+
+                let always_true = is_always_true(condition);
+                if !always_true {
+                    self.jump(after);
+                }
+                let body_block = self.cfg.new_block();
+                self.jump(body_block);
+
+                let returns_before = self.returns;
+                self.current = Some(body_block);
+                self.loops.push((header, after));
+                self.block(body);
+                self.loops.pop();
+                self.jump(header);
+
+                // With an always-true condition, only a `leave` reaches `after`
+                let leaves = !self.cfg.blocks[after].predecessors.is_empty();
+                if always_true && self.returns == returns_before && !leaves {
+                    self.diagnostics.push(
+                        Diagnostic::warning_at("this loop never ends", stmt.span)
+                            .with_hint("the condition is always true and the loop has no `return` or `leave`"),
+                    );
+                }
+                self.current = (!self.cfg.blocks[after].predecessors.is_empty()).then_some(after);
+            }
+            StmtKind::For { variable, value, iterable, body } => {
+                self.push(Event::Eval(iterable));
+                let header = self.cfg.new_block();
+                let after = self.cfg.new_block();
+                self.jump(header);
+
+                let body_block = self.cfg.new_block();
+                self.cfg.edge(header, body_block);
+                self.cfg.edge(header, after);
+                self.current = Some(body_block);
+                self.push(Event::Declare(variable, true));
+                if let Some(value) = value {
+                    self.push(Event::Declare(value, true));
+                }
+                self.loops.push((header, after));
+                self.block(body);
+                self.loops.pop();
+                self.jump(header);
+                self.current = Some(after);
+            }
+            StmtKind::Function(_) | StmtKind::Stall(_) | StmtKind::Bring { .. } | StmtKind::Error => {}
+        }
+    }
+}
+
+/// Build and check the graph for one body. `function` is `None` for the top
+/// level of the program.
+fn analyze_body(statements: &[Stmt], function: Option<&FunctionDecl>, diagnostics: &mut Vec<Diagnostic>) {
+    let mut builder = Builder {
+        cfg: Cfg::new(),
+        current: Some(ENTRY),
+        returns: 0,
+        loops: Vec::new(),
+        functions: Vec::new(),
+        diagnostics,
+    };
+    builder.block(statements);
+    builder.jump(EXIT);
+    let falls_off_end = builder.current;
+    let Builder { cfg, functions, diagnostics, .. } = builder;
+
+    let reachable = cfg.reachable();
+    if let Some(function) = function.filter(|function| function.return_type.is_some()) {
+        if falls_off_end.is_some_and(|block| reachable[block]) {
+            diagnostics.push(
+                Diagnostic::error_at(
+                    format!("`{}` does not return a value on every path", function.name),
+                    function.span,
+                )
+                .with_hint("add a `return` at the end, or in every branch of the last `if`"),
+            );
+        }
+    }
+    check_assigned_before_read(&cfg, &reachable, diagnostics);
+
+    for nested in functions {
+        analyze_body(&nested.body, Some(nested), diagnostics);
+    }
+}
+
+/// `true`, possibly in parentheses
+fn is_always_true(condition: &Expr) -> bool {
+    match &condition.kind {
+        ExprKind::Boolean(value) => *value,
+        ExprKind::Grouping(inner) => is_always_true(inner),
+        _ => false,
+    }
+}
+
+/// Forward "maybe unassigned" analysis: a variable is in the set on entry to
+/// a block if it was declared without a value and not assigned on at least
+/// one path leading there
+fn check_assigned_before_read(cfg: &Cfg, reachable: &[bool], diagnostics: &mut Vec<Diagnostic>) {
+    let mut entry_sets: Vec<BTreeSet<&str>> = vec![BTreeSet::new(); cfg.blocks.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (id, block) in cfg.blocks.iter().enumerate().filter(|(id, _)| reachable[*id]) {
+            let mut set = entry_sets[id].clone();
+            for event in &block.events {
+                transfer(event, &mut set, &mut |_, _| {});
+            }
+            for &successor in &block.successors {
+                let before = entry_sets[successor].len();
+                entry_sets[successor].extend(set.iter().copied());
+                changed |= entry_sets[successor].len() != before;
+            }
+        }
+    }
+
+    let mut reported: BTreeSet<&str> = BTreeSet::new();
+    for (id, block) in cfg.blocks.iter().enumerate().filter(|(id, _)| reachable[*id]) {
+        let mut set = entry_sets[id].clone();
+        for event in &block.events {
+            transfer(event, &mut set, &mut |name, span| {
+                if reported.insert(name) {
+                    diagnostics.push(
+                        Diagnostic::error_at(format!("`{}` may be read before it is given a value", name), span)
+                            .with_hint(format!("give `{}` a value when declaring it, or assign it on every path first", name)),
+                    );
+                }
+            });
+        }
+    }
+}
+
+/// Apply one event to the maybe-unassigned set, calling `on_unassigned_read`
+/// for each read of a variable in the set
+fn transfer<'a>(event: &Event<'a>, set: &mut BTreeSet<&'a str>, on_unassigned_read: &mut impl FnMut(&'a str, Span)) {
+    match event {
+        Event::Eval(expr) => {
+            let mut reads = Vec::new();
+            collect_reads(expr, &mut reads);
+            for (name, span) in reads {
+                if set.contains(name) {
+                    on_unassigned_read(name, span);
+                }
+            }
+        }
+        Event::Declare(name, true) | Event::Assign(name) => {
+            set.remove(name);
+        }
+        Event::Declare(name, false) => {
+            set.insert(name);
+        }
+    }
+}
+
+fn collect_reads<'a>(expr: &'a Expr, reads: &mut Vec<(&'a str, Span)>) {
+    match &expr.kind {
+        ExprKind::Variable(name) => reads.push((name, expr.span)),
+        ExprKind::Integer(_) | ExprKind::Decimal(_) | ExprKind::Text(_) | ExprKind::Boolean(_) | ExprKind::Error => {}
+        ExprKind::CoopLiteral(items) | ExprKind::Range(items) => {
+            items.iter().for_each(|item| collect_reads(item, reads));
+        }
+        ExprKind::BarnMapLiteral(entries) => {
+            for (key, value) in entries {
+                collect_reads(key, reads);
+                collect_reads(value, reads);
+            }
+        }
+        ExprKind::Grouping(inner) => collect_reads(inner, reads),
+        ExprKind::Unary { operand, .. } => collect_reads(operand, reads),
+        ExprKind::Binary { left, right, .. } => {
+            collect_reads(left, reads);
+            collect_reads(right, reads);
+        }
+        ExprKind::Update { target, .. } => collect_reads(target, reads),
+        ExprKind::Call { callee, args, .. } => {
+            collect_reads(callee, reads);
+            args.iter().for_each(|arg| collect_reads(arg, reads));
+        }
+        ExprKind::Index { object, index } => {
+            collect_reads(object, reads);
+            collect_reads(index, reads);
+        }
+        ExprKind::Member { object, .. } => collect_reads(object, reads),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_source;
+    use crate::toml_config::language_config::{LanguageKeywordManager, MooConfig};
+
+    fn analyze(source: &str) -> Vec<String> {
+        let manager = LanguageKeywordManager::from_config(MooConfig::from_default());
+        let program = parse_source(source, &manager, "en").unwrap();
+        analyze_flow(&program).iter()
+            .map(|d| format!("{} {}:{} {}", d.severity, d.line, d.column, d.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_missing_return() {
+        let source = "\
+farmfunction 'num' sign(num n) {
+    if (n < 0) {
+        return -1
+    } else if (n > 0) {
+        return 1
+    }
+}
+farmfunction 'num' always(num n) {
+    if (n < 0) {
+        return -1
+    } else {
+        return 1
+    }
+}
+";
+        assert_eq!(analyze(source), vec!["error 1:1 `sign` does not return a value on every path"]);
+    }
+
+    #[test]
+    fn test_unreachable_code_and_infinite_loops() {
+        let source = "\
+farmfunction 'num' f() {
+    return 1
+    moo \"never\"
+    moo \"reported once\"
+}
+while (true) {
+    moo \"forever\"
+}
+moo \"after\"
+while (true) {
+    return
+}
+";
+        assert_eq!(analyze(source), vec![
+            "warning 6:1 this loop never ends",
+            "warning 9:1 unreachable code",
+            "warning 3:5 unreachable code",
+        ]);
+    }
+
+    #[test]
+    fn test_leave_and_skip() {
+        let source = "\
+num found
+while (true) {
+    for i in barn(3) {
+        skip
+        moo i
+    }
+    found = 1
+    leave
+    moo \"never\"
+}
+moo found
+";
+        assert_eq!(analyze(source), vec!["warning 5:9 unreachable code", "warning 9:5 unreachable code"]);
+    }
+
+    #[test]
+    fn test_read_before_assignment() {
+        let source = "\
+num a
+num b
+num c
+if (true) {
+    a = 1
+    b = 1
+} else {
+    a = 2
+}
+moo a, b
+moo c
+c = 3
+for i in barn(3) {
+    moo i
+}
+";
+        assert_eq!(analyze(source), vec![
+            "error 10:8 `b` may be read before it is given a value",
+            "error 11:5 `c` may be read before it is given a value",
+        ]);
+    }
+}
@@ -0,0 +1,531 @@
+//! Name resolution.
+//!
+//! Builds the lexical scopes of a program and reports:
+//!
+//! - undefined names, with a "did you mean" hint for close spellings;
+//! - names declared twice in the same scope;
+//! - variables used before their declaration further down the same block;
+//! - variables and parameters that are never read (warnings);
+//! - `self` outside a stall's farmfunctions, and unknown stall types.
+//!
+//! Scopes: the program, each `farmfunction` (its parameters and body share
+//! one scope), each `for` loop (its variable and body) and each `if` /
+//! `else` / `while` block. Functions and stalls are visible throughout the
+//! block that declares them, so they may be used before their definition.
+//! A stall's field initializers share the constructor's scope, and every
+//! farmfunction of a stall sees `self`.
+//!
+//! Shadowing: a block may reuse a name from outside the enclosing function
+//! freely, since parameters often share names with globals. Reusing a name
+//! from an enclosing block of the same function is allowed but warned about,
+//! because students usually meant to assign the existing variable.
+//!
+//! A function body may read a global declared later in the file. Whether
+//! the function is called before that declaration is not checked here: such
+//! a call fails when the program runs, with "`herd` is not defined". Names
+//! starting with `_` are never reported as unused. Builtins such as `to_num` resolve when
+//! no declaration of the same name is in scope.
+
+use crate::lexer::suggestions::edit_distance;
+use crate::parser::ast::*;
+use crate::semantic::types::Builtin;
+use crate::utils::diagnostics::Diagnostic;
+use crate::utils::tokens::Span;
+
+/// Resolve every name in `program`, returning errors and warnings
+pub fn resolve_names(program: &Program) -> Vec<Diagnostic> {
+    let mut resolver = Resolver::default();
+    resolver.scopes.push(Scope::new(true));
+    resolver.block(&program.statements);
+    resolver.end_scope();
+    resolver.diagnostics
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BindingKind {
+    Variable,
+    Parameter,
+    Function,
+}
+
+#[derive(Debug)]
+struct Binding {
+    name: String,
+    kind: BindingKind,
+    span: Span,
+    used: bool,
+}
+
+#[derive(Debug, Default)]
+struct Scope {
+    bindings: Vec<Binding>,
+    /// Variables declared further down this block, not yet in scope
+    pending: Vec<(String, Span)>,
+    /// Pending globals already read from inside a function body
+    read_early: Vec<String>,
+    /// The program scope or a function scope
+    boundary: bool,
+}
+
+impl Scope {
+    fn new(boundary: bool) -> Self {
+        Self { boundary, ..Self::default() }
+    }
+
+    fn find(&self, name: &str) -> Option<usize> {
+        self.bindings.iter().position(|binding| binding.name == name)
+    }
+}
+
+#[derive(Default)]
+struct Resolver {
+    scopes: Vec<Scope>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Resolver {
+    // ================================
+    // Statements
+    // ================================
+
+    /// Resolve a block's statements in the current scope
+    fn block(&mut self, statements: &[Stmt]) {
+        for stmt in statements {
+            match &stmt.kind {
+                StmtKind::Function(function) => self.declare(&function.name, BindingKind::Function, function.span),
+                StmtKind::Stall(stall) => self.declare(&stall.name, BindingKind::Function, stall.span),
+                StmtKind::VarDecl { name, .. } => {
+                    self.current().pending.push((name.clone(), stmt.span));
+                }
+                _ => {}
+            }
+        }
+        for stmt in statements {
+            self.statement(stmt);
+        }
+    }
+
+    fn scoped_block(&mut self, statements: &[Stmt]) {
+        self.scopes.push(Scope::new(false));
+        self.block(statements);
+        self.end_scope();
+    }
+
+    fn statement(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::VarDecl { ty, name, initializer } => {
+                self.type_names(ty, stmt.span);
+                if let Some(initializer) = initializer {
+                    self.expression(initializer);
+                }
+                let scope = self.current();
+                if let Some(index) = scope.pending.iter().position(|(pending, _)| pending == name) {
+                    scope.pending.remove(index);
+                }
+                self.declare(name, BindingKind::Variable, stmt.span);
+            }
+            StmtKind::Assign { target, value } => {
+                self.expression(value);
+                self.assignment_target(target);
+            }
+            // The target is read as well as written
+            StmtKind::CompoundAssign { target, value, .. } => {
+                self.expression(value);
+                self.expression(target);
+            }
+            StmtKind::MultiAssign { targets, values } => {
+                values.iter().for_each(|value| self.expression(value));
+                targets.iter().for_each(|target| self.assignment_target(target));
+            }
+            StmtKind::Print { values } => values.iter().for_each(|value| self.expression(value)),
+            StmtKind::If { branches, else_branch } => {
+                for (condition, body) in branches {
+                    self.expression(condition);
+                    self.scoped_block(body);
+                }
+                if let Some(body) = else_branch {
+                    self.scoped_block(body);
+                }
+            }
+            StmtKind::While { condition, body } => {
+                self.expression(condition);
+                self.scoped_block(body);
+            }
+            StmtKind::For { variable, value, iterable, body } => {
+                self.expression(iterable);
+                self.scopes.push(Scope::new(false));
+                self.declare(variable, BindingKind::Variable, stmt.span);
+                if let Some(value) = value {
+                    self.declare(value, BindingKind::Variable, stmt.span);
+                }
+                self.block(body);
+                self.end_scope();
+            }
+            StmtKind::Function(function) => self.function(function, false),
+            StmtKind::Stall(stall) => self.stall(stall),
+            StmtKind::Return(value) => {
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+            }
+            StmtKind::Expression(expr) => self.expression(expr),
+            StmtKind::Break | StmtKind::Continue => {}
+            StmtKind::Bring { path } => self.diagnostics.push(
+                Diagnostic::error_at(format!("`{}` was never loaded", path), stmt.span)
+                    .with_hint("brought files are loaded by `modules::load_modules` before names are resolved"),
+            ),
+            StmtKind::Error => {}
+        }
+    }
+
+    /// Resolve a function in its own scope; the farmfunctions of a stall
+    /// also see `self`
+    fn function(&mut self, function: &FunctionDecl, method: bool) {
+        if let Some(return_type) = &function.return_type {
+            self.type_names(return_type, function.span);
+        }
+        self.scopes.push(Scope::new(true));
+        if method {
+            let receiver = Binding { name: SELF.to_string(), kind: BindingKind::Parameter, span: function.span, used: true };
+            self.current().bindings.push(receiver);
+        }
+        for param in &function.params {
+            self.type_names(&param.ty, param.span);
+            self.declare(&param.name, BindingKind::Parameter, param.span);
+        }
+        self.block(&function.body);
+        self.end_scope();
+    }
+
+    fn stall(&mut self, stall: &StallDecl) {
+        let mut members: Vec<(&str, Span)> = Vec::new();
+        let fields = stall.fields.iter().map(|field| (field.name.as_str(), field.span));
+        let functions = stall.functions.iter().map(|function| (function.name.as_str(), function.span));
+        for (name, span) in fields.chain(functions) {
+            if let Some((_, previous)) = members.iter().find(|(member, _)| *member == name) {
+                self.diagnostics.push(
+                    Diagnostic::error_at(format!("`{}` already has a member `{}`", stall.name, name), span)
+                        .with_hint(format!("the first declaration is at {}", previous)),
+                );
+                continue;
+            }
+            members.push((name, span));
+        }
+        for field in &stall.fields {
+            self.type_names(&field.ty, field.span);
+        }
+
+        // Field initializers run in the constructor's scope
+        self.function(&stall.init(), true);
+        for method in stall.methods() {
+            self.function(method, true);
+        }
+    }
+
+    /// Stall names used as types must refer to a declared stall
+    fn type_names(&mut self, ty: &TypeExpr, span: Span) {
+        match ty {
+            TypeExpr::Stall(name) => self.reference(name, span, true),
+            TypeExpr::Coop(Some(element)) => self.type_names(element, span),
+            TypeExpr::BarnMap(Some((key, value))) => {
+                self.type_names(key, span);
+                self.type_names(value, span);
+            }
+            _ => {}
+        }
+    }
+
+    /// A plain `name = value` writes the variable without reading it
+    fn assignment_target(&mut self, target: &Expr) {
+        match &target.kind {
+            ExprKind::Variable(name) => self.reference(name, target.span, false),
+            _ => self.expression(target),
+        }
+    }
+
+    // ================================
+    // Expressions
+    // ================================
+
+    fn expression(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Integer(_)
+            | ExprKind::Decimal(_)
+            | ExprKind::Text(_)
+            | ExprKind::Boolean(_)
+            | ExprKind::Error => {}
+            ExprKind::Variable(name) => self.reference(name, expr.span, true),
+            ExprKind::CoopLiteral(elements) | ExprKind::Range(elements) => {
+                elements.iter().for_each(|element| self.expression(element));
+            }
+            ExprKind::BarnMapLiteral(entries) => {
+                for (key, value) in entries {
+                    self.expression(key);
+                    self.expression(value);
+                }
+            }
+            ExprKind::Grouping(inner) => self.expression(inner),
+            ExprKind::Unary { operand, .. } => self.expression(operand),
+            ExprKind::Binary { left, right, .. } => {
+                self.expression(left);
+                self.expression(right);
+            }
+            ExprKind::Update { target, .. } => self.expression(target),
+            ExprKind::Call { callee, args, builtin } => {
+                match &callee.kind {
+                    // Calling an undeclared name spelled like a builtin calls the builtin
+                    ExprKind::Variable(name) if builtin.and_then(Builtin::lookup).is_some() && !self.declares(name) => {}
+                    _ => self.expression(callee),
+                }
+                args.iter().for_each(|arg| self.expression(arg));
+            }
+            ExprKind::Index { object, index } => {
+                self.expression(object);
+                self.expression(index);
+            }
+            // Method names belong to the value, not to any scope
+            ExprKind::Member { object, .. } => self.expression(object),
+        }
+    }
+
+    // ================================
+    // Scopes
+    // ================================
+
+    fn current(&mut self) -> &mut Scope {
+        self.scopes.last_mut().expect("the program scope is never popped early")
+    }
+
+    fn declare(&mut self, name: &str, kind: BindingKind, span: Span) {
+        if let Some(index) = self.current().find(name) {
+            let previous = &self.current().bindings[index];
+            // Functions are declared ahead of time; don't report them twice
+            if previous.kind == BindingKind::Function && previous.span == span {
+                return;
+            }
+            let previous_span = previous.span;
+            self.diagnostics.push(
+                Diagnostic::error_at(format!("`{}` is already declared in this scope", name), span)
+                    .with_hint(format!("the first declaration is at {}", previous_span)),
+            );
+            return;
+        }
+
+        if kind != BindingKind::Function {
+            if let Some(outer) = self.shadowed(name) {
+                self.diagnostics.push(
+                    Diagnostic::warning_at(format!("`{}` shadows a variable declared at {}", name, outer), span)
+                        .with_hint(format!("to change the existing variable write `{} = ...` without the type", name)),
+                );
+            }
+        }
+
+        let scope = self.current();
+        let used = kind == BindingKind::Function || scope.read_early.iter().any(|early| early == name);
+        scope.bindings.push(Binding { name: name.to_string(), kind, span, used });
+    }
+
+    /// Span of a variable with the same name in an enclosing block of the
+    /// current function
+    fn shadowed(&self, name: &str) -> Option<Span> {
+        let (_, outer) = self.scopes.split_last()?;
+        if self.scopes.last().is_some_and(|scope| scope.boundary) {
+            return None;
+        }
+        for scope in outer.iter().rev() {
+            if let Some(index) = scope.find(name) {
+                let binding = &scope.bindings[index];
+                return (binding.kind != BindingKind::Function).then_some(binding.span);
+            }
+            if scope.boundary {
+                break;
+            }
+        }
+        None
+    }
+
+    /// Whether any enclosing scope declares `name`, even further down
+    fn declares(&self, name: &str) -> bool {
+        self.scopes.iter()
+            .any(|scope| scope.find(name).is_some() || scope.pending.iter().any(|(pending, _)| pending == name))
+    }
+
+    /// Resolve a use of `name`; `read` is false for plain assignment targets
+    fn reference(&mut self, name: &str, span: Span, read: bool) {
+        let mut crossed_function = false;
+        for depth in (0..self.scopes.len()).rev() {
+            let scope = &mut self.scopes[depth];
+            if let Some(index) = scope.find(name) {
+                scope.bindings[index].used |= read;
+                return;
+            }
+            if let Some((_, declared)) = scope.pending.iter().find(|(pending, _)| pending == name) {
+                if crossed_function {
+                    scope.read_early.push(name.to_string());
+                    return;
+                }
+                let declared = *declared;
+                self.diagnostics.push(
+                    Diagnostic::error_at(format!("`{}` is used before it is declared", name), span)
+                        .with_hint(format!("`{}` is declared at {}; move the declaration above this line", name, declared)),
+                );
+                return;
+            }
+            crossed_function |= scope.boundary;
+        }
+        if name == SELF {
+            self.diagnostics.push(Diagnostic::error_at(
+                "`self` can only be used inside a stall's farmfunctions",
+                span,
+            ));
+            return;
+        }
+
+        let mut diagnostic = Diagnostic::error_at(format!("undefined name `{}`", name), span);
+        if let Some(suggestion) = self.closest_name(name) {
+            diagnostic = diagnostic.with_hint(format!("did you mean `{}`?", suggestion));
+        }
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// The visible name closest in spelling to `name`, if any is close enough
+    fn closest_name(&self, name: &str) -> Option<String> {
+        let max_distance = (name.chars().count() / 3).max(1);
+        self.scopes.iter()
+            .flat_map(|scope| scope.bindings.iter())
+            .map(|binding| (edit_distance(name, &binding.name), &binding.name))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, candidate)| candidate.clone())
+    }
+
+    fn end_scope(&mut self) {
+        let Some(scope) = self.scopes.pop() else { return };
+        for binding in scope.bindings {
+            if binding.used || binding.name.starts_with('_') {
+                continue;
+            }
+            let what = match binding.kind {
+                BindingKind::Parameter => "parameter",
+                _ => "variable",
+            };
+            self.diagnostics.push(
+                Diagnostic::warning_at(format!("unused {} `{}`", what, binding.name), binding.span)
+                    .with_hint(format!("remove it, or rename it to `_{}` if that is intended", binding.name)),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_source;
+    use crate::toml_config::language_config::{LanguageKeywordManager, MooConfig};
+    use crate::utils::diagnostics::Severity;
+
+    fn resolve(source: &str) -> Vec<String> {
+        let manager = LanguageKeywordManager::from_config(MooConfig::from_default());
+        let program = parse_source(source, &manager, "en").unwrap();
+        resolve_names(&program).iter()
+            .map(|d| format!("{} {}:{} {}", d.severity, d.line, d.column, d.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_functions_can_be_called_before_definition() {
+        let source = "showInventory(3)\nfarmfunction showInventory(num count) {\n    moo count\n}\n";
+        assert_eq!(resolve(source), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_resolution_errors() {
+        let source = "\
+moo total
+num total = 1
+num total = 2
+moo totl
+farmfunction f(num a, num a) {
+    moo a
+}
+";
+        assert_eq!(resolve(source), vec![
+            "error 1:5 `total` is used before it is declared",
+            "error 3:1 `total` is already declared in this scope",
+            "error 4:5 undefined name `totl`",
+            "error 5:23 `a` is already declared in this scope",
+            "warning 2:1 unused variable `total`",
+        ]);
+        let manager = LanguageKeywordManager::from_config(MooConfig::from_default());
+        let program = parse_source("num total = 1\nmoo totl\n", &manager, "en").unwrap();
+        assert_eq!(resolve_names(&program)[0].hint.as_deref(), Some("did you mean `total`?"));
+    }
+
+    #[test]
+    fn test_unused_and_shadowing_warnings() {
+        let source = "\
+num count = 0
+farmfunction f(num unused, num _ignored) {
+    num count = 1
+    if (count < 2) {
+        num count = 2
+        count = 3
+    }
+}
+f(1, 2)
+";
+        let diagnostics = resolve(source);
+        assert_eq!(diagnostics, vec![
+            "warning 5:9 `count` shadows a variable declared at 3:5",
+            "warning 5:9 unused variable `count`",
+            "warning 2:16 unused parameter `unused`",
+            "warning 1:1 unused variable `count`",
+        ]);
+        assert!(diagnostics.iter().all(|d| d.starts_with(&Severity::Warning.to_string())));
+    }
+
+    #[test]
+    fn test_functions_may_read_later_globals() {
+        let source = "farmfunction report() {\n    moo herd\n}\nnum herd = 3\nreport()\n";
+        assert_eq!(resolve(source), Vec::<String>::new());
+        // Calling it too early is left to the runtime
+        let source = "farmfunction report() {\n    moo herd\n}\nreport()\nnum herd = 3\n";
+        assert_eq!(resolve(source), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_builtins_follow_the_keyword_pack() {
+        assert_eq!(resolve("moo length(\"cow\")\n"), Vec::<String>::new());
+        assert_eq!(resolve("moo largo(\"cow\")\n"), vec!["error 1:5 undefined name `largo`"]);
+        let manager = LanguageKeywordManager::from_config(MooConfig::from_default());
+        let program = parse_source("muuu largo(\"vaca\")\n", &manager, "es").unwrap();
+        assert!(resolve_names(&program).is_empty());
+        // Only calls are builtins; declarations and uses keep their own name
+        let program = parse_source("numero tamaño = 3\nmuuu tamaño, largo\n", &manager, "es").unwrap();
+        let messages: Vec<String> = resolve_names(&program).into_iter().map(|d| d.message).collect();
+        assert_eq!(messages, ["undefined name `largo`"]);
+        let program = parse_source("numero tamaño = 3\n", &manager, "es").unwrap();
+        assert_eq!(resolve_names(&program)[0].message, "unused variable `tamaño`");
+    }
+
+    #[test]
+    fn test_stalls() {
+        let source = "\
+stall Cow {
+    text name
+    num name
+    farmfunction speak(num times) {
+        moo self.name, times
+    }
+}
+Cwo daisy = Cow()
+moo self
+";
+        assert_eq!(resolve(source), vec![
+            "error 3:5 `Cow` already has a member `name`",
+            "error 8:1 undefined name `Cwo`",
+            "error 9:5 `self` can only be used inside a stall's farmfunctions",
+            "warning 8:1 unused variable `daisy`",
+        ]);
+    }
+}
@@ -0,0 +1,308 @@
+//! Static types, the built-in collection methods and functions.
+
+use std::fmt;
+
+use crate::parser::ast::TypeExpr;
+
+/// The type of a MooLang value as seen by the checker.
+///
+/// `Unknown` stands for "could be anything": an element type left out of a
+/// declaration (`coop 'coop'`), the result of a function without a declared
+/// return type, or an expression that already produced an error. It is
+/// compatible with every type so one mistake is reported once.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Text,
+    Num,
+    Dec,
+    Boolean,
+    Coop(Box<Type>),
+    BarnMap(Box<Type>, Box<Type>),
+    /// `barn(...)`
+    Range,
+    /// An instance of the named stall
+    Stall(String),
+    Function { params: Vec<Type>, returns: Box<Type> },
+    /// Result of calls that produce no value, such as `animals.plant("cow")`
+    Nothing,
+    Unknown,
+}
+
+impl Type {
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, Type::Num | Type::Dec)
+    }
+
+    /// Whether a value of type `from` may be stored where `self` is expected.
+    ///
+    /// `num` widens implicitly to `dec`. Narrowing `dec` to `num` needs an
+    /// explicit `to_num`. Widening applies to single values only: a
+    /// `coop 'num'` is not a `coop 'dec'`, since adding `2.5` through one name
+    /// would break the other.
+    pub fn accepts(&self, from: &Type) -> bool {
+        matches!((self, from), (Type::Dec, Type::Num)) || self.matches(from)
+    }
+
+    /// Whether a coop of this element type can be sorted: numbers, texts
+    /// and booleans have an order
+    pub fn is_sortable(&self) -> bool {
+        matches!(self, Type::Num | Type::Dec | Type::Text | Type::Boolean | Type::Unknown)
+    }
+
+    /// Type equality where `Unknown` matches anything
+    fn matches(&self, other: &Type) -> bool {
+        match (self, other) {
+            (Type::Unknown, _) | (_, Type::Unknown) => true,
+            (Type::Coop(a), Type::Coop(b)) => a.matches(b),
+            (Type::BarnMap(a_key, a_value), Type::BarnMap(b_key, b_value)) => {
+                a_key.matches(b_key) && a_value.matches(b_value)
+            }
+            (a, b) => a == b,
+        }
+    }
+
+    /// Result type of arithmetic between two numbers: `dec` if either
+    /// operand is `dec`, otherwise `num`
+    pub fn promote(&self, other: &Type) -> Type {
+        if *self == Type::Dec || *other == Type::Dec {
+            Type::Dec
+        } else {
+            Type::Num
+        }
+    }
+
+    /// The more specific of two compatible types, e.g. for the elements of
+    /// `[[], [1]]`
+    pub fn merge(&self, other: &Type) -> Type {
+        match (self, other) {
+            (Type::Unknown, known) | (known, Type::Unknown) => known.clone(),
+            (Type::Coop(a), Type::Coop(b)) => Type::Coop(Box::new(a.merge(b))),
+            (Type::BarnMap(ak, av), Type::BarnMap(bk, bv)) => {
+                Type::BarnMap(Box::new(ak.merge(bk)), Box::new(av.merge(bv)))
+            }
+            (a, b) if a.is_numeric() && b.is_numeric() => a.promote(b),
+            (a, _) => a.clone(),
+        }
+    }
+}
+
+impl From<&TypeExpr> for Type {
+    fn from(ty: &TypeExpr) -> Self {
+        match ty {
+            TypeExpr::Text => Type::Text,
+            TypeExpr::Num => Type::Num,
+            TypeExpr::Dec => Type::Dec,
+            TypeExpr::Boolean => Type::Boolean,
+            TypeExpr::Coop(element) => Type::Coop(Box::new(element.as_deref().map_or(Type::Unknown, Type::from))),
+            TypeExpr::BarnMap(None) => Type::BarnMap(Box::new(Type::Unknown), Box::new(Type::Unknown)),
+            TypeExpr::BarnMap(Some((key, value))) => {
+                Type::BarnMap(Box::new(Type::from(key.as_ref())), Box::new(Type::from(value.as_ref())))
+            }
+            TypeExpr::Range => Type::Range,
+            TypeExpr::Stall(name) => Type::Stall(name.clone()),
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    /// Source spelling in English, e.g. `coop 'num'`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Text => write!(f, "text"),
+            Type::Num => write!(f, "num"),
+            Type::Dec => write!(f, "dec"),
+            Type::Boolean => write!(f, "boolean"),
+            Type::Coop(element) if **element == Type::Unknown => write!(f, "coop"),
+            Type::Coop(element) => write!(f, "coop '{}'", element),
+            Type::BarnMap(key, value) if **key == Type::Unknown && **value == Type::Unknown => write!(f, "barn_map"),
+            Type::BarnMap(key, value) => write!(f, "barn_map '{}':'{}'", key, value),
+            Type::Range => write!(f, "barn"),
+            Type::Stall(name) => write!(f, "{}", name),
+            Type::Function { .. } => write!(f, "farmfunction"),
+            Type::Nothing => write!(f, "nothing"),
+            Type::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// Methods built into `coop` and `barn_map` values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// `add` / `plant`: append to a coop
+    Add,
+    /// `collect` / `size`: number of elements
+    Size,
+    /// `put`: insert or update a map entry
+    Put,
+    Get,
+    /// A barn_map key, or the first coop element equal to the argument
+    Remove,
+    Contains,
+    /// `remove_at(index)`: remove and return a coop element
+    RemoveAt,
+    /// `insert(index, value)`
+    Insert,
+    /// `index_of(value)`: position of the first equal element, or -1
+    IndexOf,
+    Sort,
+    Reverse,
+    /// `slice(start, end)`: a new coop of the elements from `start` up to `end`
+    Slice,
+    /// `keys()`: a barn_map's keys as a coop, in insertion order
+    Keys,
+    Values,
+}
+
+/// The canonical names of each method, the field names of `BuiltinNameSet`.
+/// The lexer turns the spelling of the active keyword pack into these.
+const METHOD_NAMES: &[(Method, &[&str])] = &[
+    (Method::Add, &["add", "plant"]),
+    (Method::Size, &["collect", "size"]),
+    (Method::Put, &["put"]),
+    (Method::Get, &["get"]),
+    (Method::Remove, &["remove"]),
+    (Method::Contains, &["contains"]),
+    (Method::RemoveAt, &["remove_at"]),
+    (Method::Insert, &["insert"]),
+    (Method::IndexOf, &["index_of"]),
+    (Method::Sort, &["sort"]),
+    (Method::Reverse, &["reverse"]),
+    (Method::Slice, &["slice"]),
+    (Method::Keys, &["keys"]),
+    (Method::Values, &["values"]),
+];
+
+impl Method {
+    pub fn lookup(name: &str) -> Option<Method> {
+        METHOD_NAMES.iter()
+            .find(|(_, names)| names.contains(&name))
+            .map(|(method, _)| *method)
+    }
+
+    /// English name, used in messages
+    pub fn name(&self) -> &'static str {
+        METHOD_NAMES.iter()
+            .find(|(method, _)| method == self)
+            .map_or("", |(_, names)| names[0])
+    }
+}
+
+/// Built-in functions: the conversions and the standard library
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Builtin {
+    /// `to_num`: `dec` rounds toward zero, `text` is parsed
+    ToNum,
+    /// `to_dec`: `num` widens, `text` is parsed
+    ToDec,
+    /// `to_text`: any value, formatted as `moo` prints it
+    ToText,
+    /// `length(text)`: the number of characters
+    Length,
+    /// `upper(text)`
+    Upper,
+    /// `split(text, separator)`: the pieces between separators, or each
+    /// character for an empty separator
+    Split,
+    /// `join(coop, separator)`: the elements as `moo` prints them
+    Join,
+    Abs,
+    Min,
+    Max,
+    /// `sqrt(number)`: always a `dec`
+    Sqrt,
+    /// `random(low, high)`: a whole number from `low` to `high`, both included
+    Random,
+    /// `listen()`: the next line typed in, without its line break, or `""`
+    /// once there is no more input
+    Listen,
+}
+
+/// The canonical name of each builtin, a field name of `BuiltinNameSet`.
+/// The lexer turns the spelling of the active keyword pack into these.
+const BUILTIN_NAMES: &[(Builtin, &str)] = &[
+    (Builtin::ToNum, "to_num"),
+    (Builtin::ToDec, "to_dec"),
+    (Builtin::ToText, "to_text"),
+    (Builtin::Length, "length"),
+    (Builtin::Upper, "upper"),
+    (Builtin::Split, "split"),
+    (Builtin::Join, "join"),
+    (Builtin::Abs, "abs"),
+    (Builtin::Min, "min"),
+    (Builtin::Max, "max"),
+    (Builtin::Sqrt, "sqrt"),
+    (Builtin::Random, "random"),
+    (Builtin::Listen, "listen"),
+];
+
+impl Builtin {
+    pub fn lookup(name: &str) -> Option<Builtin> {
+        BUILTIN_NAMES.iter()
+            .find(|(_, canonical)| *canonical == name)
+            .map(|(builtin, _)| *builtin)
+    }
+
+    /// Canonical (English) name, used in messages
+    pub fn name(&self) -> &'static str {
+        BUILTIN_NAMES.iter()
+            .find(|(builtin, _)| builtin == self)
+            .map_or("", |(_, canonical)| canonical)
+    }
+
+    pub fn is_conversion(&self) -> bool {
+        matches!(self, Builtin::ToNum | Builtin::ToDec | Builtin::ToText)
+    }
+
+    /// The argument types; a `dec` parameter also takes a `num`.
+    /// Conversions take one argument of several types, see `converts`.
+    pub fn params(&self) -> Vec<Type> {
+        match self {
+            Builtin::ToNum | Builtin::ToDec | Builtin::ToText => vec![Type::Unknown],
+            Builtin::Length | Builtin::Upper => vec![Type::Text],
+            Builtin::Split => vec![Type::Text, Type::Text],
+            Builtin::Join => vec![Type::Coop(Box::new(Type::Unknown)), Type::Text],
+            Builtin::Abs | Builtin::Sqrt => vec![Type::Dec],
+            Builtin::Min | Builtin::Max => vec![Type::Dec, Type::Dec],
+            Builtin::Random => vec![Type::Num, Type::Num],
+            Builtin::Listen => vec![],
+        }
+    }
+
+    /// The type a call produces for arguments of type `args`: `abs`, `min`
+    /// and `max` keep `num` when every argument is a `num`
+    pub fn returns(&self, args: &[Type]) -> Type {
+        match self {
+            Builtin::ToNum | Builtin::Length | Builtin::Random => Type::Num,
+            Builtin::ToDec | Builtin::Sqrt => Type::Dec,
+            Builtin::ToText | Builtin::Upper | Builtin::Join | Builtin::Listen => Type::Text,
+            Builtin::Split => Type::Coop(Box::new(Type::Text)),
+            Builtin::Abs | Builtin::Min | Builtin::Max if args.contains(&Type::Dec) => Type::Dec,
+            Builtin::Abs | Builtin::Min | Builtin::Max if args.iter().all(|arg| *arg == Type::Num) => Type::Num,
+            Builtin::Abs | Builtin::Min | Builtin::Max => Type::Unknown,
+        }
+    }
+
+    /// Whether a conversion can convert a value of type `from`
+    pub fn converts(&self, from: &Type) -> bool {
+        match self {
+            Builtin::ToNum | Builtin::ToDec => matches!(from, Type::Num | Type::Dec | Type::Text | Type::Unknown),
+            Builtin::ToText => !matches!(from, Type::Nothing | Type::Function { .. }),
+            _ => false,
+        }
+    }
+}
+
+/// The conversion to suggest when a `from` value is given where `to` is
+/// expected
+pub fn conversion_hint(to: &Type, from: &Type) -> Option<String> {
+    let builtin = match (to, from) {
+        (Type::Num, Type::Dec) => {
+            return Some("`dec` is never narrowed implicitly; use `to_num(...)`, which rounds toward zero".to_string())
+        }
+        (Type::Num, Type::Text) => Builtin::ToNum,
+        (Type::Dec, Type::Text) => Builtin::ToDec,
+        (Type::Text, Type::Num | Type::Dec | Type::Boolean) => Builtin::ToText,
+        _ => return None,
+    };
+    Some(format!("convert it with `{}(...)`", builtin.name()))
+}
@@ -5,6 +5,7 @@ use std::path::Path;
 use std::fmt::{Display, Formatter};
 use crate::toml_config::config_errors::ConfigError;
 use crate::utils::tokens::TokenType;
+use crate::utils::unicode;
 
 // ================================
 // Configuration Structures
@@ -15,6 +16,10 @@ use crate::utils::tokens::TokenType;
 pub struct MooConfig {
     pub language: LanguageSettings,
     pub keywords: HashMap<String, KeyWordSet>,
+    #[serde(default)]
+    pub builtins: HashMap<String, BuiltinNameSet>,
+    #[serde(default)]
+    pub matching: HashMap<String, KeywordMatching>,
 }
 
 /// Language settings and metadata
@@ -45,6 +50,8 @@ pub struct KeyWordSet {
     pub while_keyword: String,
     pub for_keyword: String,
     pub in_keyword: String,
+    pub break_keyword: String,
+    pub continue_keyword: String,
 
     // Functions
     pub function_keyword: String,
@@ -56,6 +63,73 @@ pub struct KeyWordSet {
 
     // Special
     pub range_keyword: String,
+
+    // Records
+    pub stall_keyword: String,
+    pub self_keyword: String,
+
+    // Modules
+    pub bring_keyword: String,
+
+    // Literals
+    pub true_literal: String,
+    pub false_literal: String,
+}
+
+/// Names of the builtin functions and of the `coop` / `barn_map` methods
+/// in a language. Field names are the canonical names the compiler uses
+/// after lexing; `add` / `plant` and `collect` / `size` are two spellings
+/// of the same method.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BuiltinNameSet {
+    // Conversions
+    pub to_num: String,
+    pub to_dec: String,
+    pub to_text: String,
+
+    // Text
+    pub length: String,
+    pub upper: String,
+    pub split: String,
+    pub join: String,
+
+    // Math
+    pub abs: String,
+    pub min: String,
+    pub max: String,
+    pub sqrt: String,
+    pub random: String,
+
+    // Input
+    pub listen: String,
+
+    // Coop and barn_map methods
+    pub add: String,
+    pub plant: String,
+    pub collect: String,
+    pub size: String,
+    pub put: String,
+    pub get: String,
+    pub remove: String,
+    pub contains: String,
+    pub remove_at: String,
+    pub insert: String,
+    pub index_of: String,
+    pub sort: String,
+    pub reverse: String,
+    pub slice: String,
+    pub keys: String,
+    pub values: String,
+}
+
+/// How loosely keywords are matched for a language.
+/// Both options default to `false` (exact match after NFC normalisation).
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct KeywordMatching {
+    #[serde(default)]
+    pub case_insensitive: bool,
+    #[serde(default)]
+    pub accent_insensitive: bool,
 }
 
 // ================================
@@ -66,16 +140,23 @@ pub struct KeyWordSet {
 pub struct LanguageKeywordManager {
     config: MooConfig,
     token_maps: HashMap<String, HashMap<String, TokenType>>,
+    builtin_maps: HashMap<String, HashMap<String, &'static str>>,
 }
 
 impl LanguageKeywordManager {
     /// Create from loaded config
-    pub fn from_config(config: MooConfig) -> Self {
+    pub fn from_config(mut config: MooConfig) -> Self {
+        for lang in config.keywords.keys() {
+            config.builtins.entry(lang.clone())
+                .or_insert_with(|| MooConfig::get_default_builtins_for_language(lang));
+        }
         let mut manager = Self {
             config,
             token_maps: HashMap::new(),
+            builtin_maps: HashMap::new(),
         };
         manager.build_token_maps();
+        manager.build_builtin_maps();
         manager
     }
 
@@ -85,7 +166,8 @@ impl LanguageKeywordManager {
         Ok(Self::from_config(config))
     }
 
-    /// Build token mappings from keyword sets
+    /// Build token mappings from keyword sets.
+    /// Keys are stored folded according to the language's `KeywordMatching`.
     fn build_token_maps(&mut self) {
         for (lang, keyword_set) in &self.config.keywords {
             let mut token_map = HashMap::new();
@@ -104,16 +186,43 @@ impl LanguageKeywordManager {
             token_map.insert(keyword_set.while_keyword.clone(), self.get_while_token(lang));
             token_map.insert(keyword_set.for_keyword.clone(), self.get_for_token(lang));
             token_map.insert(keyword_set.in_keyword.clone(), self.get_in_token(lang));
+            token_map.insert(keyword_set.break_keyword.clone(), self.get_break_token(lang));
+            token_map.insert(keyword_set.continue_keyword.clone(), self.get_continue_token(lang));
             token_map.insert(keyword_set.function_keyword.clone(), self.get_function_token(lang));
             token_map.insert(keyword_set.return_keyword.clone(), self.get_return_token(lang));
             token_map.insert(keyword_set.is_keyword.clone(), self.get_is_token(lang));
             token_map.insert(keyword_set.is_not_keyword.clone(), self.get_is_not_token(lang));
             token_map.insert(keyword_set.range_keyword.clone(), self.get_range_token(lang));
+            token_map.insert(keyword_set.stall_keyword.clone(), self.get_stall_token(lang));
+            token_map.insert(keyword_set.self_keyword.clone(), self.get_self_token(lang));
+            token_map.insert(keyword_set.bring_keyword.clone(), self.get_bring_token(lang));
+
+            let matching = self.get_matching(lang);
+            let token_map = token_map.into_iter()
+                .map(|(word, token)| (unicode::fold_keyword(&word, &matching), token))
+                .collect();
 
             self.token_maps.insert(lang.clone(), token_map);
         }
     }
 
+    /// Map each language's builtin names to their canonical names, folded
+    /// like its keywords
+    fn build_builtin_maps(&mut self) {
+        for (lang, builtin_set) in &self.config.builtins {
+            let matching = self.get_matching(lang);
+            let builtin_map = builtin_set.entries().into_iter()
+                .map(|(canonical, word)| (unicode::fold_keyword(word, &matching), canonical))
+                .collect();
+            self.builtin_maps.insert(lang.clone(), builtin_map);
+        }
+    }
+
+    /// Get keyword matching rules for a language
+    pub fn get_matching(&self, language: &str) -> KeywordMatching {
+        self.config.matching.get(language).cloned().unwrap_or_default()
+    }
+
     /// Get token mappings for a specific language
     pub fn get_token_map(&self, language: &str) -> Option<&HashMap<String, TokenType>> {
         self.token_maps.get(language)
@@ -124,20 +233,56 @@ impl LanguageKeywordManager {
         self.config.keywords.get(language)
     }
 
+    /// Get builtin names for a language
+    pub fn get_builtin_set(&self, language: &str) -> Option<&BuiltinNameSet> {
+        self.config.builtins.get(language)
+    }
+
     /// Get available languages
     pub fn get_available_languages(&self) -> Vec<&String> {
         self.config.keywords.keys().collect()
     }
 
+    /// Get the configured default language
+    pub fn default_language(&self) -> &str {
+        &self.config.language.default_language
+    }
+
+    /// Language version of the configuration, recorded in compiled `.moob` files
+    pub fn version(&self) -> &str {
+        &self.config.language.version
+    }
+
     /// Check if a word is a keyword in any language
     pub fn is_keyword(&self, word: &str) -> bool {
-        self.token_maps.values()
-            .any(|map| map.contains_key(word))
+        self.token_maps.keys()
+            .any(|lang| self.get_token_type(word, lang).is_some())
     }
 
-    /// Get TokenType for a word in a specific language
+    /// The canonical name of a builtin function or method written as `word`
+    /// in a specific language, e.g. `length` for `largo` in Spanish
+    pub fn get_builtin(&self, word: &str, language: &str) -> Option<&'static str> {
+        let folded = unicode::fold_keyword(word, &self.get_matching(language));
+        self.builtin_maps.get(language)?.get(&folded).copied()
+    }
+
+    /// The value of a boolean literal such as `true` or `verdadero` in a
+    /// specific language, matched like a keyword
+    pub fn get_boolean_literal(&self, word: &str, language: &str) -> Option<bool> {
+        let keyword_set = self.config.keywords.get(language)?;
+        let matching = self.get_matching(language);
+        let folded = unicode::fold_keyword(word, &matching);
+        [(&keyword_set.true_literal, true), (&keyword_set.false_literal, false)]
+            .into_iter()
+            .find(|(literal, _)| unicode::fold_keyword(literal, &matching) == folded)
+            .map(|(_, value)| value)
+    }
+
+    /// Get TokenType for a word in a specific language.
+    /// The word is NFC-normalised and folded per the language's matching rules.
     pub fn get_token_type(&self, word: &str, language: &str) -> Option<TokenType> {
-        self.token_maps.get(language)?.get(word).cloned()
+        let folded = unicode::fold_keyword(word, &self.get_matching(language));
+        self.token_maps.get(language)?.get(&folded).cloned()
     }
 
     // Helper methods to map language-specific tokens
@@ -197,6 +342,22 @@ impl LanguageKeywordManager {
         }
     }
 
+    fn get_break_token(&self, lang: &str) -> TokenType {
+        match lang {
+            "en" => TokenType::Leave,
+            "es" => TokenType::Salir,
+            _ => TokenType::Leave,
+        }
+    }
+
+    fn get_continue_token(&self, lang: &str) -> TokenType {
+        match lang {
+            "en" => TokenType::Skip,
+            "es" => TokenType::Saltar,
+            _ => TokenType::Skip,
+        }
+    }
+
     fn get_function_token(&self, lang: &str) -> TokenType {
         match lang {
             "en" => TokenType::Farmfunction,
@@ -236,6 +397,30 @@ impl LanguageKeywordManager {
             _ => TokenType::Barn,
         }
     }
+
+    fn get_stall_token(&self, lang: &str) -> TokenType {
+        match lang {
+            "en" => TokenType::Stall,
+            "es" => TokenType::Establo,
+            _ => TokenType::Stall,
+        }
+    }
+
+    fn get_self_token(&self, lang: &str) -> TokenType {
+        match lang {
+            "en" => TokenType::SelfKw,
+            "es" => TokenType::Este,
+            _ => TokenType::SelfKw,
+        }
+    }
+
+    fn get_bring_token(&self, lang: &str) -> TokenType {
+        match lang {
+            "en" => TokenType::Bring,
+            "es" => TokenType::Traer,
+            _ => TokenType::Bring,
+        }
+    }
 }
 
 // ================================
@@ -249,6 +434,92 @@ impl Display for MooConfig {
     }
 }
 
+impl KeyWordSet {
+    /// All keywords paired with their field name, e.g. `("if_keyword", "si")`.
+    /// Field names are stable across languages, which makes them useful for
+    /// translating a keyword from one pack into another.
+    pub fn entries(&self) -> Vec<(&'static str, &str)> {
+        vec![
+            ("print", &self.print),
+            ("text_type", &self.text_type),
+            ("number_type", &self.number_type),
+            ("decimal_type", &self.decimal_type),
+            ("boolean_type", &self.boolean_type),
+            ("array_type", &self.array_type),
+            ("map_type", &self.map_type),
+            ("if_keyword", &self.if_keyword),
+            ("else_if_keyword", &self.else_if_keyword),
+            ("else_keyword", &self.else_keyword),
+            ("while_keyword", &self.while_keyword),
+            ("for_keyword", &self.for_keyword),
+            ("in_keyword", &self.in_keyword),
+            ("break_keyword", &self.break_keyword),
+            ("continue_keyword", &self.continue_keyword),
+            ("function_keyword", &self.function_keyword),
+            ("return_keyword", &self.return_keyword),
+            ("is_keyword", &self.is_keyword),
+            ("is_not_keyword", &self.is_not_keyword),
+            ("range_keyword", &self.range_keyword),
+            ("stall_keyword", &self.stall_keyword),
+            ("self_keyword", &self.self_keyword),
+            ("bring_keyword", &self.bring_keyword),
+            ("true_literal", &self.true_literal),
+            ("false_literal", &self.false_literal),
+        ]
+    }
+
+    /// Look up a keyword by its field name
+    pub fn get(&self, field: &str) -> Option<&str> {
+        self.entries().into_iter()
+            .find(|(name, _)| *name == field)
+            .map(|(_, keyword)| keyword)
+    }
+}
+
+impl BuiltinNameSet {
+    /// Every name paired with its canonical name, e.g. `("length", "largo")`
+    pub fn entries(&self) -> Vec<(&'static str, &str)> {
+        vec![
+            ("to_num", &self.to_num),
+            ("to_dec", &self.to_dec),
+            ("to_text", &self.to_text),
+            ("length", &self.length),
+            ("upper", &self.upper),
+            ("split", &self.split),
+            ("join", &self.join),
+            ("abs", &self.abs),
+            ("min", &self.min),
+            ("max", &self.max),
+            ("sqrt", &self.sqrt),
+            ("random", &self.random),
+            ("listen", &self.listen),
+            ("add", &self.add),
+            ("plant", &self.plant),
+            ("collect", &self.collect),
+            ("size", &self.size),
+            ("put", &self.put),
+            ("get", &self.get),
+            ("remove", &self.remove),
+            ("contains", &self.contains),
+            ("remove_at", &self.remove_at),
+            ("insert", &self.insert),
+            ("index_of", &self.index_of),
+            ("sort", &self.sort),
+            ("reverse", &self.reverse),
+            ("slice", &self.slice),
+            ("keys", &self.keys),
+            ("values", &self.values),
+        ]
+    }
+
+    /// Look up a name by its canonical name
+    pub fn get(&self, canonical: &str) -> Option<&str> {
+        self.entries().into_iter()
+            .find(|(name, _)| *name == canonical)
+            .map(|(_, word)| word)
+    }
+}
+
 impl MooConfig {
     /// Smart config loader with defaults
     pub fn load_with_defaults(path: &Path) -> Result<Self, ConfigError> {
@@ -306,7 +577,26 @@ impl MooConfig {
             keywords = defaults.keywords;
         }
 
-        Self { language, keywords }
+        let mut partial_builtins = partial.builtins.unwrap_or_default();
+        let builtins = keywords.keys()
+            .map(|lang| {
+                let default_builtins = defaults.builtins.get(lang)
+                    .cloned()
+                    .unwrap_or_else(|| Self::get_default_builtins_for_language(lang));
+                let merged = match partial_builtins.remove(lang) {
+                    Some(builtin_set) => Self::merge_builtin_sets(default_builtins, builtin_set),
+                    None => default_builtins,
+                };
+                (lang.clone(), merged)
+            })
+            .collect();
+
+        let mut matching = defaults.matching;
+        if let Some(partial_matching) = partial.matching {
+            matching.extend(partial_matching);
+        }
+
+        Self { language, keywords, builtins, matching }
     }
 
     /// Merge keyword sets
@@ -325,11 +615,122 @@ impl MooConfig {
             while_keyword: partial.while_keyword.unwrap_or(default.while_keyword),
             for_keyword: partial.for_keyword.unwrap_or(default.for_keyword),
             in_keyword: partial.in_keyword.unwrap_or(default.in_keyword),
+            break_keyword: partial.break_keyword.unwrap_or(default.break_keyword),
+            continue_keyword: partial.continue_keyword.unwrap_or(default.continue_keyword),
             function_keyword: partial.function_keyword.unwrap_or(default.function_keyword),
             return_keyword: partial.return_keyword.unwrap_or(default.return_keyword),
             is_keyword: partial.is_keyword.unwrap_or(default.is_keyword),
             is_not_keyword: partial.is_not_keyword.unwrap_or(default.is_not_keyword),
             range_keyword: partial.range_keyword.unwrap_or(default.range_keyword),
+            stall_keyword: partial.stall_keyword.unwrap_or(default.stall_keyword),
+            self_keyword: partial.self_keyword.unwrap_or(default.self_keyword),
+            bring_keyword: partial.bring_keyword.unwrap_or(default.bring_keyword),
+            true_literal: partial.true_literal.unwrap_or(default.true_literal),
+            false_literal: partial.false_literal.unwrap_or(default.false_literal),
+        }
+    }
+
+    /// Merge builtin name sets
+    fn merge_builtin_sets(default: BuiltinNameSet, partial: PartialBuiltinNameSet) -> BuiltinNameSet {
+        BuiltinNameSet {
+            to_num: partial.to_num.unwrap_or(default.to_num),
+            to_dec: partial.to_dec.unwrap_or(default.to_dec),
+            to_text: partial.to_text.unwrap_or(default.to_text),
+            length: partial.length.unwrap_or(default.length),
+            upper: partial.upper.unwrap_or(default.upper),
+            split: partial.split.unwrap_or(default.split),
+            join: partial.join.unwrap_or(default.join),
+            abs: partial.abs.unwrap_or(default.abs),
+            min: partial.min.unwrap_or(default.min),
+            max: partial.max.unwrap_or(default.max),
+            sqrt: partial.sqrt.unwrap_or(default.sqrt),
+            random: partial.random.unwrap_or(default.random),
+            listen: partial.listen.unwrap_or(default.listen),
+            add: partial.add.unwrap_or(default.add),
+            plant: partial.plant.unwrap_or(default.plant),
+            collect: partial.collect.unwrap_or(default.collect),
+            size: partial.size.unwrap_or(default.size),
+            put: partial.put.unwrap_or(default.put),
+            get: partial.get.unwrap_or(default.get),
+            remove: partial.remove.unwrap_or(default.remove),
+            contains: partial.contains.unwrap_or(default.contains),
+            remove_at: partial.remove_at.unwrap_or(default.remove_at),
+            insert: partial.insert.unwrap_or(default.insert),
+            index_of: partial.index_of.unwrap_or(default.index_of),
+            sort: partial.sort.unwrap_or(default.sort),
+            reverse: partial.reverse.unwrap_or(default.reverse),
+            slice: partial.slice.unwrap_or(default.slice),
+            keys: partial.keys.unwrap_or(default.keys),
+            values: partial.values.unwrap_or(default.values),
+        }
+    }
+
+    /// Builtin names for a language without a `[builtins]` table: Spanish
+    /// for `es`, English for every other pack
+    fn get_default_builtins_for_language(lang: &str) -> BuiltinNameSet {
+        match lang {
+            "es" => BuiltinNameSet {
+                to_num: "a_numero".to_string(),
+                to_dec: "a_decimal".to_string(),
+                to_text: "a_texto".to_string(),
+                length: "largo".to_string(),
+                upper: "mayusculas".to_string(),
+                split: "dividir".to_string(),
+                join: "unir".to_string(),
+                abs: "absoluto".to_string(),
+                min: "minimo".to_string(),
+                max: "maximo".to_string(),
+                sqrt: "raiz".to_string(),
+                random: "aleatorio".to_string(),
+                listen: "escuchar".to_string(),
+                add: "agregar".to_string(),
+                plant: "plantar".to_string(),
+                collect: "contar".to_string(),
+                size: "tamaño".to_string(),
+                put: "poner".to_string(),
+                get: "obtener".to_string(),
+                remove: "quitar".to_string(),
+                contains: "contiene".to_string(),
+                remove_at: "quitar_en".to_string(),
+                insert: "insertar".to_string(),
+                index_of: "indice_de".to_string(),
+                sort: "ordenar".to_string(),
+                reverse: "invertir".to_string(),
+                slice: "rebanar".to_string(),
+                keys: "claves".to_string(),
+                values: "valores".to_string(),
+            },
+            _ => BuiltinNameSet {
+                to_num: "to_num".to_string(),
+                to_dec: "to_dec".to_string(),
+                to_text: "to_text".to_string(),
+                length: "length".to_string(),
+                upper: "upper".to_string(),
+                split: "split".to_string(),
+                join: "join".to_string(),
+                abs: "abs".to_string(),
+                min: "min".to_string(),
+                max: "max".to_string(),
+                sqrt: "sqrt".to_string(),
+                random: "random".to_string(),
+                listen: "listen".to_string(),
+                add: "add".to_string(),
+                plant: "plant".to_string(),
+                collect: "collect".to_string(),
+                size: "size".to_string(),
+                put: "put".to_string(),
+                get: "get".to_string(),
+                remove: "remove".to_string(),
+                contains: "contains".to_string(),
+                remove_at: "remove_at".to_string(),
+                insert: "insert".to_string(),
+                index_of: "index_of".to_string(),
+                sort: "sort".to_string(),
+                reverse: "reverse".to_string(),
+                slice: "slice".to_string(),
+                keys: "keys".to_string(),
+                values: "values".to_string(),
+            },
         }
     }
 
@@ -350,11 +751,18 @@ impl MooConfig {
                 while_keyword: "während".to_string(),
                 for_keyword: "für".to_string(),
                 in_keyword: "in".to_string(),
+                break_keyword: "raus".to_string(),
+                continue_keyword: "weiter".to_string(),
                 function_keyword: "bauernfunktion".to_string(),
                 return_keyword: "zurück".to_string(),
                 is_keyword: "ist".to_string(),
                 is_not_keyword: "ist nicht".to_string(),
                 range_keyword: "scheune".to_string(),
+                stall_keyword: "gehege".to_string(),
+                self_keyword: "selbst".to_string(),
+                bring_keyword: "bringe".to_string(),
+                true_literal: "wahr".to_string(),
+                false_literal: "falsch".to_string(),
             },
             "ru" => KeyWordSet {
                 print: "му".to_string(),
@@ -370,11 +778,18 @@ impl MooConfig {
                 while_keyword: "пока".to_string(),
                 for_keyword: "для".to_string(),
                 in_keyword: "в".to_string(),
+                break_keyword: "выйти".to_string(),
+                continue_keyword: "дальше".to_string(),
                 function_keyword: "фермафункция".to_string(),
                 return_keyword: "вернуть".to_string(),
                 is_keyword: "есть".to_string(),
                 is_not_keyword: "не есть".to_string(),
                 range_keyword: "сарай".to_string(),
+                stall_keyword: "стойло".to_string(),
+                self_keyword: "сам".to_string(),
+                bring_keyword: "принеси".to_string(),
+                true_literal: "истина".to_string(),
+                false_literal: "ложь".to_string(),
             },
             _ => KeyWordSet {
                 print: "print".to_string(),
@@ -390,11 +805,18 @@ impl MooConfig {
                 while_keyword: "while".to_string(),
                 for_keyword: "for".to_string(),
                 in_keyword: "in".to_string(),
+                break_keyword: "break".to_string(),
+                continue_keyword: "continue".to_string(),
                 function_keyword: "function".to_string(),
                 return_keyword: "return".to_string(),
                 is_keyword: "==".to_string(),
                 is_not_keyword: "!=".to_string(),
                 range_keyword: "range".to_string(),
+                stall_keyword: "class".to_string(),
+                self_keyword: "self".to_string(),
+                bring_keyword: "import".to_string(),
+                true_literal: "true".to_string(),
+                false_literal: "false".to_string(),
             }
         }
     }
@@ -448,11 +870,18 @@ impl MooConfig {
             while_keyword: "while".to_string(),
             for_keyword: "for".to_string(),
             in_keyword: "in".to_string(),
+            break_keyword: "leave".to_string(),
+            continue_keyword: "skip".to_string(),
             function_keyword: "farmfunction".to_string(),
             return_keyword: "return".to_string(),
             is_keyword: "is".to_string(),
             is_not_keyword: "is not".to_string(),
             range_keyword: "barn".to_string(),
+            stall_keyword: "stall".to_string(),
+            self_keyword: "self".to_string(),
+            bring_keyword: "bring".to_string(),
+            true_literal: "true".to_string(),
+            false_literal: "false".to_string(),
         });
 
         // Spanish defaults
@@ -470,19 +899,39 @@ impl MooConfig {
             while_keyword: "mientras".to_string(),
             for_keyword: "para".to_string(),
             in_keyword: "en".to_string(),
+            break_keyword: "salir".to_string(),
+            continue_keyword: "saltar".to_string(),
             function_keyword: "funciongranja".to_string(),
             return_keyword: "regresa".to_string(),
             is_keyword: "es".to_string(),
             is_not_keyword: "no es".to_string(),
             range_keyword: "granero".to_string(),
+            stall_keyword: "establo".to_string(),
+            self_keyword: "este".to_string(),
+            bring_keyword: "traer".to_string(),
+            true_literal: "verdadero".to_string(),
+            false_literal: "falso".to_string(),
+        });
+
+        // Spanish students frequently type `número` for `numero`
+        let mut matching = HashMap::new();
+        matching.insert("es".to_string(), KeywordMatching {
+            case_insensitive: false,
+            accent_insensitive: true,
         });
 
+        let builtins = keywords.keys()
+            .map(|lang| (lang.clone(), Self::get_default_builtins_for_language(lang)))
+            .collect();
+
         Self {
             language: LanguageSettings {
                 version: "1.0.0".to_string(),
                 default_language: "en".to_string(),
             },
             keywords,
+            builtins,
+            matching,
         }
     }
 
@@ -496,11 +945,11 @@ impl MooConfig {
     }
 
     pub fn keyword_count(&self, language: &str) -> usize {
-        if self.keywords.contains_key(language) { 17 } else { 0 }
+        self.keywords.get(language).map_or(0, |keywords| keywords.entries().len())
     }
 
     pub fn total_keywords(&self) -> usize {
-        self.keywords.len() * 17
+        self.keywords.values().map(|keywords| keywords.entries().len()).sum()
     }
 }
 
@@ -512,6 +961,8 @@ impl MooConfig {
 struct PartialMooConfig {
     language: Option<LanguageSettings>,
     keywords: Option<HashMap<String, PartialKeyWordSet>>,
+    builtins: Option<HashMap<String, PartialBuiltinNameSet>>,
+    matching: Option<HashMap<String, KeywordMatching>>,
 }
 
 #[derive(serde::Deserialize, Debug)]
@@ -529,11 +980,51 @@ struct PartialKeyWordSet {
     while_keyword: Option<String>,
     for_keyword: Option<String>,
     in_keyword: Option<String>,
+    break_keyword: Option<String>,
+    continue_keyword: Option<String>,
     function_keyword: Option<String>,
     return_keyword: Option<String>,
     is_keyword: Option<String>,
     is_not_keyword: Option<String>,
     range_keyword: Option<String>,
+    stall_keyword: Option<String>,
+    self_keyword: Option<String>,
+    bring_keyword: Option<String>,
+    true_literal: Option<String>,
+    false_literal: Option<String>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct PartialBuiltinNameSet {
+    to_num: Option<String>,
+    to_dec: Option<String>,
+    to_text: Option<String>,
+    length: Option<String>,
+    upper: Option<String>,
+    split: Option<String>,
+    join: Option<String>,
+    abs: Option<String>,
+    min: Option<String>,
+    max: Option<String>,
+    sqrt: Option<String>,
+    random: Option<String>,
+    listen: Option<String>,
+    add: Option<String>,
+    plant: Option<String>,
+    collect: Option<String>,
+    size: Option<String>,
+    put: Option<String>,
+    get: Option<String>,
+    remove: Option<String>,
+    contains: Option<String>,
+    remove_at: Option<String>,
+    insert: Option<String>,
+    index_of: Option<String>,
+    sort: Option<String>,
+    reverse: Option<String>,
+    slice: Option<String>,
+    keys: Option<String>,
+    values: Option<String>,
 }
 
 // ================================
@@ -561,4 +1052,47 @@ mod tests {
         assert_eq!(manager.get_token_type("moo", "en"), Some(TokenType::Moo));
         assert_eq!(manager.get_token_type("muuu", "es"), Some(TokenType::Muuu));
     }
+
+    #[test]
+    fn test_keyword_normalisation() {
+        let mut config = MooConfig::merge_with_defaults(PartialMooConfig {
+            language: None,
+            keywords: Some(HashMap::from([
+                ("es".to_string(), toml::from_str("").unwrap()),
+                ("de".to_string(), toml::from_str("").unwrap()),
+            ])),
+            builtins: None,
+            matching: None,
+        });
+        config.matching.insert("de".to_string(), KeywordMatching {
+            case_insensitive: true,
+            accent_insensitive: false,
+        });
+        let manager = LanguageKeywordManager::from_config(config);
+
+        // NFD "während" matches the NFC keyword
+        assert_eq!(manager.get_token_type("wa\u{308}hrend", "de"), Some(TokenType::While));
+        assert_eq!(manager.get_token_type("WÄHREND", "de"), Some(TokenType::While));
+        assert_eq!(manager.get_token_type("wahrend", "de"), None);
+        // Spanish is accent-insensitive by default
+        assert_eq!(manager.get_token_type("número", "es"), Some(TokenType::Num));
+        assert_eq!(manager.get_token_type("Numero", "es"), None);
+    }
+
+    #[test]
+    fn test_builtin_names_per_language() {
+        let partial: PartialMooConfig = toml::from_str(
+            "[keywords.es]\n[keywords.de]\n[builtins.de]\nlength = \"länge\"\n"
+        ).unwrap();
+        let manager = LanguageKeywordManager::from_config(MooConfig::merge_with_defaults(partial));
+
+        assert_eq!(manager.get_builtin("largo", "es"), Some("length"));
+        assert_eq!(manager.get_builtin("plantar", "es"), Some("plant"));
+        assert_eq!(manager.get_builtin("tamano", "es"), Some("size"));
+        assert_eq!(manager.get_builtin("length", "es"), None);
+        // A pack without a full table falls back to the English names
+        assert_eq!(manager.get_builtin("länge", "de"), Some("length"));
+        assert_eq!(manager.get_builtin("upper", "de"), Some("upper"));
+        assert_eq!(manager.get_builtin("largo", "de"), None);
+    }
 }
\ No newline at end of file
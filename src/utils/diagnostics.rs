@@ -0,0 +1,90 @@
+use std::fmt;
+
+use crate::utils::tokens::Span;
+
+/// How serious a `Diagnostic` is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Hint,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Hint => write!(f, "hint"),
+        }
+    }
+}
+
+/// A message about the source code that does not stop compilation on its own,
+/// such as a lint warning or a "did you mean" hint.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+    /// The brought file the diagnostic is about, see `Span::file`
+    pub file: Option<&'static str>,
+    pub hint: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>, line: usize, column: usize) -> Self {
+        Self {
+            severity,
+            message: message.into(),
+            line,
+            column,
+            file: None,
+            hint: None,
+        }
+    }
+
+    /// A diagnostic at the start of `span`, in the file it comes from
+    pub fn at(severity: Severity, message: impl Into<String>, span: Span) -> Self {
+        Self {
+            file: span.file,
+            ..Self::new(severity, message, span.line, span.column)
+        }
+    }
+
+    pub fn error_at(message: impl Into<String>, span: Span) -> Self {
+        Self::at(Severity::Error, message, span)
+    }
+
+    pub fn warning_at(message: impl Into<String>, span: Span) -> Self {
+        Self::at(Severity::Warning, message, span)
+    }
+
+    pub fn error(message: impl Into<String>, line: usize, column: usize) -> Self {
+        Self::new(Severity::Error, message, line, column)
+    }
+
+    pub fn warning(message: impl Into<String>, line: usize, column: usize) -> Self {
+        Self::new(Severity::Warning, message, line, column)
+    }
+
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at ", self.severity)?;
+        if let Some(file) = self.file {
+            write!(f, "{}:", file)?;
+        }
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)?;
+        if let Some(hint) = &self.hint {
+            write!(f, "\n  hint: {}", hint)?;
+        }
+        Ok(())
+    }
+}
@@ -14,14 +14,14 @@ impl FileType {
             } else if extension == "muu" {
                 Ok(FileType::Muu(path.to_str().unwrap().to_string()))
             } else {
-                return Err(FileReaderError::WrongFileType(
+                Err(FileReaderError::WrongFileType(
                     "File must have .moo (English) or .muu (Español) extension".to_string()
-                ));
+                ))
             }
         } else {
-            return Err(FileReaderError::Other(
+            Err(FileReaderError::Other(
                 "File has no extension".to_string()
-            ));
+            ))
         }
     }
 
@@ -1,3 +1,9 @@
+use std::fmt::Write;
+use std::sync::Mutex;
+
+use serde::Serialize;
+
+use crate::utils::unicode;
 
 /**
  * Represents the various token types that can be encountered during the lexical analysis 
@@ -8,7 +14,8 @@
  *
  * ### Literals
  * - `StringLiteral`: A token representing string values (e.g., `"Hello, world!"`).
- * - `NumberLiteral`: A token representing numeric values (e.g., `42`, `3.14`).
+ * - `IntegerLiteral`: A token representing `num` values (e.g., `42`, `1_000`, `0xFF`, `0b101`).
+ * - `DecimalLiteral`: A token representing `dec` values (e.g., `3.14`, `2.5e-3`).
  * - `BooleanLiteral`: A token representing boolean values (e.g., `true`, `false`).
  *
  * ### Identifiers
@@ -20,10 +27,14 @@
  * - `If`, `ElseIf`, `Else`: Conditional constructs.
  * - `While`: A loop construct.
  * - `For`, `In`: Loop constructs with iteration.
+ * - `Leave`, `Skip`: Exit a loop, or go on to its next round.
  * - `Barn`: Represents a specific construct or block (e.g., a scoped area).
  * - `Farmfunction`: Defines a function in the language.
  * - `Return`: Indicates a return statement.
  * - `Is`, `IsNot`: Indicates equality or inequality checks.
+ * - `Stall`, `SelfKw`: Declare a record type and refer to the record a
+ *   method was called on.
+ * - `Bring`: Loads another source file.
  *
  * ### Keywords - Spanish
  * Tokens representing reserved keywords in the Spanish version of the language:
@@ -31,10 +42,13 @@
  * - `Si`, `SinoSi`, `Sino`: Conditional constructs in Spanish.
  * - `Mientras`: Loop construct in Spanish.
  * - `Para`, `En`: Loop constructs with iteration in Spanish.
+ * - `Salir`, `Saltar`: Correspond to `Leave` and `Skip`.
  * - `Granero`: Corresponds to the English keyword `Barn`.
  * - `Funciongranja`: Corresponds to the English keyword `Farmfunction`.
  * - `Regresa`: Corresponds to the English keyword `Return`.
  * - `Es`, `NoEs`: Corresponds to `Is` and `IsNot`.
+ * - `Establo`, `Este`: Correspond to `Stall` and `SelfKw`.
+ * - `Traer`: Corresponds to `Bring`.
  *
  * ### Operators
  * Tokens representing mathematical or logical operations:
@@ -48,6 +62,9 @@
  * - `GreaterThan`: Greater-than comparison operator (`>`).
  * - `Increment`: Increments a value (`++`).
  * - `Decrement`: Decrements a value (`--`).
+ * - `PlusEquals`, `MinusEquals`, `MultiplyEquals`, `DivideEquals`,
+ *   `IntegerDivideEquals`, `ModuloEquals`: Compound assignment (`+=`, `-=`,
+ *   `*=`, `/=`, `//=`, `%=`).
  *
  * ### Punctuation
  * Tokens representing structural elements or delimiters in the syntax:
@@ -66,17 +83,21 @@
  * - `Newline`: Represents the end of a line in the source code.
  * - `EOF`: Represents the end of the source file.
  * - `Comment`: Represents a comment in the source code.
+ * - `Whitespace`: Spaces, tabs and carriage returns. The lexer skips these;
+ *   only the concrete syntax tree keeps them, as trivia.
  *
  * ## Traits
  * - `Debug`: Allows for formatted debugging output for the `TokenType` enum.
  * - `Clone`: Enables cloning of `TokenType` instances.
- * - `PartialEq`: Enables comparison of `TokenType` instances for equality.
+ * - `PartialEq`, `Eq`: Enables comparison of `TokenType` instances for equality.
+ * - `Hash`: Allows `TokenType` to be used as a map key.
  */
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
 pub enum TokenType {
     // Literals
     StringLiteral,
-    NumberLiteral,
+    IntegerLiteral,
+    DecimalLiteral,
     BooleanLiteral,
     
     // Identifiers
@@ -84,17 +105,19 @@ pub enum TokenType {
     
     // Keywords - English
     Moo, Text, Num, Dec, Boolean, Coop, BarnMap,
-    If, ElseIf, Else, While, For, In, Barn,
-    Farmfunction, Return, Is, IsNot,
+    If, ElseIf, Else, While, For, In, Leave, Skip, Barn,
+    Farmfunction, Return, Is, IsNot, Stall, SelfKw, Bring,
     
     // Keywords - Spanish  
     Muuu, Texto, Numero, Decimal, Booleano, Granja, MapaEstablo,
-    Si, SinoSi, Sino, Mientras, Para, En, Granero,
-    Funciongranja, Regresa, Es, NoEs,
+    Si, SinoSi, Sino, Mientras, Para, En, Salir, Saltar, Granero,
+    Funciongranja, Regresa, Es, NoEs, Establo, Este, Traer,
     
     // Operators
     Plus, Minus, Multiply, Divide, IntegerDivide, Modulo,
     LessThan, GreaterThan, Increment, Decrement,
+    PlusEquals, MinusEquals, MultiplyEquals, DivideEquals,
+    IntegerDivideEquals, ModuloEquals,
     
     // Punctuation
     LeftParen, RightParen, LeftBrace, RightBrace,
@@ -102,14 +125,237 @@ pub enum TokenType {
     Semicolon, Quote, Equals,
     
     // Special
-    Newline, EOF, Comment,
+    Newline, EOF, Comment, Whitespace,
+}
+
+impl TokenType {
+    /// Map a language-specific keyword token to its English counterpart
+    /// (`Muuu` -> `Moo`, `Granja` -> `Coop`, ...). Other tokens are unchanged.
+    /// Later stages match on canonical tokens so every keyword pack behaves
+    /// identically.
+    pub fn canonical(&self) -> TokenType {
+        match self {
+            TokenType::Muuu => TokenType::Moo,
+            TokenType::Texto => TokenType::Text,
+            TokenType::Numero => TokenType::Num,
+            TokenType::Decimal => TokenType::Dec,
+            TokenType::Booleano => TokenType::Boolean,
+            TokenType::Granja => TokenType::Coop,
+            TokenType::MapaEstablo => TokenType::BarnMap,
+            TokenType::Si => TokenType::If,
+            TokenType::SinoSi => TokenType::ElseIf,
+            TokenType::Sino => TokenType::Else,
+            TokenType::Mientras => TokenType::While,
+            TokenType::Para => TokenType::For,
+            TokenType::En => TokenType::In,
+            TokenType::Salir => TokenType::Leave,
+            TokenType::Saltar => TokenType::Skip,
+            TokenType::Granero => TokenType::Barn,
+            TokenType::Funciongranja => TokenType::Farmfunction,
+            TokenType::Regresa => TokenType::Return,
+            TokenType::Es => TokenType::Is,
+            TokenType::NoEs => TokenType::IsNot,
+            TokenType::Establo => TokenType::Stall,
+            TokenType::Este => TokenType::SelfKw,
+            TokenType::Traer => TokenType::Bring,
+            other => other.clone(),
+        }
+    }
+
+    /// Language-neutral description for error messages, e.g. "`(`" or
+    /// "if keyword". Keywords are described by role because their spelling
+    /// depends on the keyword pack.
+    pub fn describe(&self) -> String {
+        let text = match self.canonical() {
+            TokenType::StringLiteral => "text literal",
+            TokenType::IntegerLiteral => "whole number",
+            TokenType::DecimalLiteral => "decimal number",
+            TokenType::BooleanLiteral => "true/false value",
+            TokenType::Identifier => "name",
+            TokenType::Moo => "print keyword",
+            TokenType::Text => "text type",
+            TokenType::Num => "num type",
+            TokenType::Dec => "dec type",
+            TokenType::Boolean => "boolean type",
+            TokenType::Coop => "coop type",
+            TokenType::BarnMap => "barn_map type",
+            TokenType::If => "if keyword",
+            TokenType::ElseIf => "else-if keyword",
+            TokenType::Else => "else keyword",
+            TokenType::While => "while keyword",
+            TokenType::For => "for keyword",
+            TokenType::In => "in keyword",
+            TokenType::Leave => "break keyword",
+            TokenType::Skip => "continue keyword",
+            TokenType::Barn => "range keyword",
+            TokenType::Farmfunction => "function keyword",
+            TokenType::Return => "return keyword",
+            TokenType::Is => "equality keyword",
+            TokenType::IsNot => "inequality keyword",
+            TokenType::Stall => "stall keyword",
+            TokenType::SelfKw => "self keyword",
+            TokenType::Bring => "bring keyword",
+            TokenType::Plus => "`+`",
+            TokenType::Minus => "`-`",
+            TokenType::Multiply => "`*`",
+            TokenType::Divide => "`/`",
+            TokenType::IntegerDivide => "`//`",
+            TokenType::Modulo => "`%`",
+            TokenType::PlusEquals => "`+=`",
+            TokenType::MinusEquals => "`-=`",
+            TokenType::MultiplyEquals => "`*=`",
+            TokenType::DivideEquals => "`/=`",
+            TokenType::IntegerDivideEquals => "`//=`",
+            TokenType::ModuloEquals => "`%=`",
+            TokenType::LessThan => "`<`",
+            TokenType::GreaterThan => "`>`",
+            TokenType::Increment => "`++`",
+            TokenType::Decrement => "`--`",
+            TokenType::LeftParen => "`(`",
+            TokenType::RightParen => "`)`",
+            TokenType::LeftBrace => "`{`",
+            TokenType::RightBrace => "`}`",
+            TokenType::LeftBracket => "`[`",
+            TokenType::RightBracket => "`]`",
+            TokenType::Comma => "`,`",
+            TokenType::Dot => "`.`",
+            TokenType::Colon => "`:`",
+            TokenType::Semicolon => "`;`",
+            TokenType::Quote => "`'`",
+            TokenType::Equals => "`=`",
+            TokenType::Newline => "end of line",
+            TokenType::EOF => "end of file",
+            TokenType::Comment => "comment",
+            TokenType::Whitespace => "whitespace",
+            _ => "keyword",
+        };
+        text.to_string()
+    }
+}
+
+/// Value parsed from a numeric or boolean literal at lex time, so later
+/// stages never re-parse `lexeme`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum Literal {
+    Integer(i64),
+    Decimal(f64),
+    Boolean(bool),
+    /// An identifier that names a builtin function or method in the active
+    /// keyword pack, with its canonical name (`length` for `largo`)
+    Builtin(&'static str),
+}
+
+/// A region of source code: 1-based line and grapheme column of the first
+/// character, plus its length in grapheme clusters. `file` names the brought
+/// file the code comes from, and is `None` in the file being compiled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub length: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<&'static str>,
+}
+
+impl Span {
+    pub fn new(line: usize, column: usize, length: usize) -> Self {
+        Self { line, column, length, file: None }
+    }
+
+    /// The same region in the file named `file`
+    pub fn in_file(self, file: Option<&'static str>) -> Span {
+        Span { file, ..self }
+    }
+
+    /// Span from the start of `self` to the end of `end`. Spans crossing lines
+    /// keep the length of the first line's part.
+    pub fn to(&self, end: Span) -> Span {
+        if end.line == self.line && end.column >= self.column {
+            Span { length: end.column + end.length - self.column, ..*self }
+        } else {
+            *self
+        }
+    }
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(file) = self.file {
+            write!(f, "{}:", file)?;
+        }
+        write!(f, "{}:{}", self.line, self.column)
+    }
 }
 
-//TODO: Plan token structure and methods
+/// The `&'static` name for `Span::file`. Each distinct name is leaked once,
+/// which keeps `Span` `Copy`; a run only ever sees a handful of files.
+pub fn file_name(name: &str) -> &'static str {
+    static NAMES: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+    let mut names = NAMES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(known) = names.iter().find(|known| **known == name) {
+        return known;
+    }
+    let leaked: &'static str = Box::leak(name.into());
+    names.push(leaked);
+    leaked
+}
+
+/// A single lexed token.
+///
+/// `lexeme` is the exact source text of the token (string literals keep their
+/// quotes, keywords keep the spelling the student typed). `line` and `column`
+/// are 1-based; columns count grapheme clusters, not bytes. Numeric and
+/// boolean literals carry their parsed value in `literal`, and builtin names
+/// their canonical name.
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct Token {
     pub token_type: TokenType,
     pub lexeme: String,
     pub line: usize,
     pub column: usize,
+    pub literal: Option<Literal>,
 }
 
+impl Token {
+    pub fn new(token_type: TokenType, lexeme: impl Into<String>, line: usize, column: usize) -> Self {
+        Self {
+            token_type,
+            lexeme: lexeme.into(),
+            line,
+            column,
+            literal: None,
+        }
+    }
+
+    pub fn with_literal(mut self, literal: Literal) -> Self {
+        self.literal = Some(literal);
+        self
+    }
+
+    /// The canonical name of the builtin function or method this identifier
+    /// is spelled as in its keyword pack
+    pub fn builtin(&self) -> Option<&'static str> {
+        match self.literal {
+            Some(Literal::Builtin(name)) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Source region covered by this token
+    pub fn span(&self) -> Span {
+        Span::new(self.line, self.column, unicode::grapheme_width(&self.lexeme))
+    }
+}
+
+
+/// Tokens as an aligned `position  type  lexeme` table, for `--dump-tokens`
+pub fn token_table(tokens: &[Token]) -> String {
+    let mut text = String::new();
+    let _ = writeln!(text, "{:<9} {:<16} Lexeme", "Position", "Type");
+    for token in tokens {
+        let position = format!("{}:{}", token.line, token.column);
+        let token_type = format!("{:?}", token.token_type);
+        let _ = writeln!(text, "{:<9} {:<16} {:?}", position, token_type, token.lexeme);
+    }
+    text
+}
@@ -0,0 +1,46 @@
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_xid::UnicodeXID;
+use crate::toml_config::language_config::KeywordMatching;
+
+/// Returns true if `c` may start an identifier (Unicode `XID_Start` or `_`).
+pub fn is_identifier_start(c: char) -> bool {
+    c == '_' || c.is_xid_start()
+}
+
+/// Returns true if `c` may continue an identifier (Unicode `XID_Continue`).
+pub fn is_identifier_continue(c: char) -> bool {
+    c.is_xid_continue()
+}
+
+/// Normalises text to Unicode Normalization Form C, so that `während`
+/// typed as `a` + combining diaeresis compares equal to the precomposed form.
+pub fn nfc(text: &str) -> String {
+    text.nfc().collect()
+}
+
+/// Removes accents and other combining marks (`número` -> `numero`).
+pub fn strip_accents(text: &str) -> String {
+    text.nfd().filter(|c| !is_combining_mark(*c)).nfc().collect()
+}
+
+/// Folds a word into the form used for keyword lookup under the given
+/// matching rules. The result is always NFC-normalised.
+pub fn fold_keyword(word: &str, matching: &KeywordMatching) -> String {
+    let mut folded = if matching.accent_insensitive {
+        strip_accents(word)
+    } else {
+        nfc(word)
+    };
+    if matching.case_insensitive {
+        folded = folded.to_lowercase();
+    }
+    folded
+}
+
+/// Counts the user-perceived characters (extended grapheme clusters) in `text`.
+/// Used for column numbers so diagnostics line up with what students see.
+pub fn grapheme_width(text: &str) -> usize {
+    text.graphemes(true).count()
+}
@@ -0,0 +1,81 @@
+//! Runs the `moolang` binary the way students do.
+
+use std::path::PathBuf;
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Output, Stdio};
+
+/// Write `files` to a fresh directory and run the binary there with `args`
+fn moolang(test: &str, files: &[(&str, &str)], args: &[&str]) -> Output {
+    let directory = std::env::temp_dir().join(format!("moolang-cli-{}-{}", test, std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    for (name, source) in files {
+        let path: PathBuf = directory.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, source).unwrap();
+    }
+    let output = Command::new(env!("CARGO_BIN_EXE_compiler"))
+        .args(args)
+        .current_dir(&directory)
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(&directory).unwrap();
+    output
+}
+
+fn text(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+#[test]
+fn test_help_shows_the_precedence_table() {
+    let output = moolang("help", &[], &["--help"]);
+    let help = text(&output.stderr);
+    assert!(help.contains("operators, highest precedence first:"), "{}", help);
+    assert!(help.contains("x.name   postfix  left             member access"), "{}", help);
+}
+
+#[test]
+fn test_json_alone_dumps_without_running() {
+    let output = moolang("json", &[("main.moo", "moo 1\n")], &["--json", "main.moo"]);
+    let stdout = text(&output.stdout);
+    assert!(output.status.success(), "{}", text(&output.stderr));
+    let json: serde_json::Value = serde_json::from_str(&stdout).expect("only JSON on stdout");
+    assert!(json["statements"].is_array(), "{}", stdout);
+}
+
+#[test]
+fn test_syntax_errors_hint_at_keywords() {
+    let output = moolang("typo", &[("typo.moo", "mo \"hi\"\n")], &["typo.moo"]);
+    let stderr = text(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("  hint: did you mean `moo`?"), "{}", stderr);
+
+    let output = moolang("english", &[("english.muu", "num x = 1\nif (x es 1) {\n    muuu x\n}\n")], &["english.muu"]);
+    let stderr = text(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("  hint: `if` is the English keyword; in Spanish use `si`"), "{}", stderr);
+}
+
+#[test]
+fn test_closed_output_stops_quietly() {
+    let directory = std::env::temp_dir().join(format!("moolang-cli-pipe-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    std::fs::write(directory.join("main.moo"), "for i in barn(1000000) {\n    moo i\n}\n").unwrap();
+    for args in [&["main.moo"][..], &["--tree-walker", "main.moo"]] {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_compiler"))
+            .args(args)
+            .current_dir(&directory)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        // Read the first line, then close the pipe like `| head -1`
+        let mut first = String::new();
+        BufReader::new(child.stdout.take().unwrap()).read_line(&mut first).unwrap();
+        assert_eq!(first, "0\n");
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success(), "{}", text(&output.stderr));
+        assert_eq!(text(&output.stderr), "");
+    }
+    std::fs::remove_dir_all(&directory).unwrap();
+}